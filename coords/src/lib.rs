@@ -0,0 +1,130 @@
+//-----------------------------------------------------------------------------
+// Coordinate Conversions
+//   - Latitude/longitude in degrees to and from world XYZ on a sphere
+//   - World positions to and from positions relative to a region's center
+//   - Kept in its own crate so the server, PebbleVault and plugins share one
+//     implementation instead of each doing the math by hand
+//
+// World axes follow the usual geographic convention: the sphere is centered
+// on the origin, +Z points through the north pole, and +X through latitude 0,
+// longitude 0.
+//-----------------------------------------------------------------------------
+
+/// Point on a sphere of `radius` around the origin at `lat`, `lon` in degrees
+pub fn latlon_to_xyz(lat: f64, lon: f64, radius: f64) -> [f64; 3] {
+    let (lat, lon) = (lat.to_radians(), lon.to_radians());
+    [
+        radius * lat.cos() * lon.cos(),
+        radius * lat.cos() * lon.sin(),
+        radius * lat.sin(),
+    ]
+}
+
+/// Latitude and longitude in degrees of the direction from the origin to a point
+///
+/// Latitude is in `-90..=90` and longitude in `(-180, 180]`, so both sides
+/// of the antimeridian map to 180. The poles, where longitude is undefined,
+/// and the origin report longitude 0.
+pub fn xyz_to_latlon(x: f64, y: f64, z: f64) -> (f64, f64) {
+    let horizontal = x.hypot(y);
+    if horizontal == 0.0 {
+        let lat = if z > 0.0 { 90.0 } else if z < 0.0 { -90.0 } else { 0.0 };
+        return (lat, 0.0);
+    }
+    let lat = z.atan2(horizontal).to_degrees();
+    (lat, normalize_longitude(y.atan2(x).to_degrees()))
+}
+
+/// Wraps a longitude in degrees into `(-180, 180]`
+pub fn normalize_longitude(lon: f64) -> f64 {
+    let wrapped = (lon + 180.0).rem_euclid(360.0) - 180.0;
+    if wrapped == -180.0 { 180.0 } else { wrapped }
+}
+
+/// Position of a world point relative to a region's center
+pub fn world_to_region_local(point: [f64; 3], region_center: [f64; 3]) -> [f64; 3] {
+    [0, 1, 2].map(|axis| point[axis] - region_center[axis])
+}
+
+/// World position of a point given relative to a region's center
+pub fn region_local_to_world(local: [f64; 3], region_center: [f64; 3]) -> [f64; 3] {
+    [0, 1, 2].map(|axis| local[axis] + region_center[axis])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RADIUS: f64 = 6371.0;
+    const EPSILON: f64 = 1e-9;
+
+    /// Difference between two longitudes the short way around the sphere
+    fn longitude_gap(a: f64, b: f64) -> f64 {
+        normalize_longitude(a - b).abs()
+    }
+
+    fn round_trip(lat: f64, lon: f64) -> (f64, f64) {
+        let [x, y, z] = latlon_to_xyz(lat, lon, RADIUS);
+        xyz_to_latlon(x, y, z)
+    }
+
+    #[test]
+    fn latlon_survives_a_round_trip_across_the_sphere() {
+        // Every half degree, stopping short of the poles, which are checked on their own
+        for lat_step in -179..=179 {
+            for lon_step in -360..=360 {
+                let (lat, lon) = (lat_step as f64 * 0.5, lon_step as f64 * 0.5);
+                let (back_lat, back_lon) = round_trip(lat, lon);
+                assert!((back_lat - lat).abs() < EPSILON, "latitude {} came back as {}", lat, back_lat);
+                assert!(longitude_gap(back_lon, lon) < EPSILON, "longitude {} came back as {}", lon, back_lon);
+                assert!(back_lon > -180.0 && back_lon <= 180.0, "longitude {} is out of range", back_lon);
+            }
+        }
+    }
+
+    #[test]
+    fn the_poles_come_back_at_longitude_zero() {
+        for lon in [-180.0, -90.0, 0.0, 45.0, 180.0] {
+            let [x, y, z] = latlon_to_xyz(90.0, lon, RADIUS);
+            assert!((z - RADIUS).abs() < EPSILON);
+            assert!(x.hypot(y) < 1e-6);
+
+            // Rounding leaves the point a hair off the axis, so only latitude is exact
+            let (lat, _) = xyz_to_latlon(x, y, z);
+            assert!((lat - 90.0).abs() < 1e-6);
+            let (lat, _) = round_trip(-90.0, lon);
+            assert!((lat + 90.0).abs() < 1e-6);
+        }
+        assert_eq!(xyz_to_latlon(0.0, 0.0, RADIUS), (90.0, 0.0));
+        assert_eq!(xyz_to_latlon(0.0, 0.0, -RADIUS), (-90.0, 0.0));
+        assert_eq!(xyz_to_latlon(0.0, 0.0, 0.0), (0.0, 0.0));
+    }
+
+    #[test]
+    fn both_sides_of_the_antimeridian_meet_at_180() {
+        for lat in [-60.0, 0.0, 60.0] {
+            let (_, east) = round_trip(lat, 180.0);
+            let (_, west) = round_trip(lat, -180.0);
+            assert!(longitude_gap(east, 180.0) < EPSILON);
+            assert!(longitude_gap(west, 180.0) < EPSILON);
+
+            // Points just either side of the seam stay on their own side
+            assert!((round_trip(lat, 179.999).1 - 179.999).abs() < EPSILON);
+            assert!((round_trip(lat, -179.999).1 + 179.999).abs() < EPSILON);
+        }
+        assert_eq!(normalize_longitude(-180.0), 180.0);
+        assert_eq!(normalize_longitude(540.0), 180.0);
+        assert_eq!(normalize_longitude(-190.0), 170.0);
+    }
+
+    #[test]
+    fn region_local_positions_survive_a_round_trip() {
+        let center = [1000.0, -250.0, 42.5];
+        for point in [[0.0; 3], center, [1e6, -1e6, 3.25], [-7.5, 0.125, -99.0]] {
+            let local = world_to_region_local(point, center);
+            assert_eq!(region_local_to_world(local, center), point);
+        }
+        assert_eq!(world_to_region_local(center, center), [0.0; 3]);
+        assert_eq!(world_to_region_local([1001.0, -250.0, 42.5], center), [1.0, 0.0, 0.0]);
+    }
+}
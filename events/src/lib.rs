@@ -0,0 +1,183 @@
+//-----------------------------------------------------------------------------
+// Horizon Game Events
+//   - Events shared between the server and plugins
+//   - Host services plugins reach at runtime through the plugin context
+//   - Kept in its own crate so plugins can use them without depending on
+//     plugin_api, which depends on every plugin
+//-----------------------------------------------------------------------------
+
+use parking_lot::RwLock;
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, OnceLock};
+use uuid::Uuid;
+
+/// Services the host server exposes to plugins
+pub trait ServerHandle: Send + Sync {
+    /// Sends a `server_message` event carrying `message` to every connected client
+    fn broadcast_message(&self, message: &str);
+
+    /// Sends the named event with an arbitrary payload to every connected client
+    fn broadcast_event(&self, name: &str, payload: serde_json::Value);
+
+    /// Sends the named event to the players of `pool_group` within `radius` of
+    /// `center`, returning how many it was delivered to
+    fn broadcast_to_region(&self, pool_group: &str, center: [f64; 3], radius: f64, name: &str, payload: serde_json::Value) -> usize;
+
+    /// Directory plugins keep the state they save between runs in
+    fn plugin_data_dir(&self) -> PathBuf;
+
+    /// Writes `message` to the server log as an error in the `category` subsystem
+    fn log_error(&self, category: &str, message: &str);
+}
+
+/// Errors returned when routing an RPC between plugins
+#[derive(Debug, Clone, PartialEq)]
+pub enum RpcError {
+    /// No active plugin has the given name
+    UnknownPlugin(String),
+    /// The plugin exists but does not handle the named RPC
+    UnknownRpc { plugin: String, rpc: String },
+}
+
+impl fmt::Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RpcError::UnknownPlugin(plugin) => write!(f, "No plugin named {} is loaded", plugin),
+            RpcError::UnknownRpc { plugin, rpc } => write!(f, "Plugin {} has no RPC named {}", plugin, rpc),
+        }
+    }
+}
+
+impl std::error::Error for RpcError {}
+
+/// Routes RPCs to the plugins that registered them
+pub trait RpcRouter: Send + Sync {
+    /// Calls `rpc_name` on the loaded plugin named `plugin_name`
+    ///
+    /// The result is whatever the target plugin returned, to be downcast by
+    /// the caller to the type the RPC documents.
+    fn call_rpc(&self, plugin_name: &str, rpc_name: &str, params: &(dyn Any + Send + Sync)) -> Result<Box<dyn Any + Send + Sync>, RpcError>;
+}
+
+/// Context handed to plugins so they can reach the running server
+#[derive(Clone)]
+pub struct PluginContext {
+    pub server: Arc<dyn ServerHandle>,
+    /// The plugin manager's RPC router, for calling other plugins by name
+    pub rpc: Arc<dyn RpcRouter>,
+}
+
+static PLUGIN_CONTEXT: RwLock<Option<PluginContext>> = parking_lot::const_rwlock(None);
+
+/// Installs the context returned by `plugin_context`, replacing any previous one
+///
+/// Hosts call this before constructing plugins so constructors can use it.
+pub fn set_plugin_context(context: PluginContext) {
+    *PLUGIN_CONTEXT.write() = Some(context);
+}
+
+/// Context installed by the host, or `None` when running without a server
+pub fn plugin_context() -> Option<PluginContext> {
+    PLUGIN_CONTEXT.read().clone()
+}
+
+/// Something that happened in the game world
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameEvent {
+    PlayerJoined { player: Uuid },
+    PlayerLeft { player: Uuid },
+    PlayerMoved { player: Uuid, from: [f64; 3], to: [f64; 3] },
+    ChatMessage { player: Uuid, channel: String, text: String },
+    /// A player lost `amount` health, caused by `source` if it was another entity
+    ///
+    /// Replaces the `player_damaged` custom event.
+    PlayerDamaged { player: Uuid, source: Option<Uuid>, amount: i64 },
+    /// A player's health reached zero, killed by `killer` if it was another entity
+    ///
+    /// Replaces the `player_died` custom event; plugins matching
+    /// `Custom { event_type: "player_died", .. }` should match this instead.
+    PlayerDied { player: Uuid, killer: Option<Uuid> },
+    /// Game-specific event identified by `event_type`
+    Custom { event_type: String, data: serde_json::Value },
+}
+
+impl GameEvent {
+    /// Builds a custom event
+    pub fn custom(event_type: impl Into<String>, data: serde_json::Value) -> Self {
+        GameEvent::Custom { event_type: event_type.into(), data }
+    }
+}
+
+/// Bus shared by the server and every plugin in the process
+///
+/// The server delivers its game events here, and plugins emit their own
+/// events on it so other plugins can subscribe instead of polling.
+pub fn global_bus() -> &'static EventBus {
+    static GLOBAL_BUS: OnceLock<EventBus> = OnceLock::new();
+    GLOBAL_BUS.get_or_init(EventBus::new)
+}
+
+/// Callback invoked with every event it is subscribed to
+pub type EventHandler = Arc<dyn Fn(&GameEvent) + Send + Sync>;
+
+/// Delivers game events to their subscribers
+///
+/// Custom events reach handlers registered for their `event_type` and
+/// handlers subscribed to every event, never handlers of other types.
+#[derive(Default)]
+pub struct EventBus {
+    /// Handlers receiving every event
+    handlers: RwLock<Vec<EventHandler>>,
+    /// Handlers receiving every event except custom ones
+    builtin_handlers: RwLock<Vec<EventHandler>>,
+    /// Handlers receiving custom events of one type, by type
+    custom_handlers: RwLock<HashMap<String, Vec<EventHandler>>>,
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a handler that receives every event
+    pub fn subscribe(&self, handler: impl Fn(&GameEvent) + Send + Sync + 'static) {
+        self.handlers.write().push(Arc::new(handler));
+    }
+
+    /// Registers a handler that receives every event except custom ones
+    pub fn subscribe_builtin(&self, handler: impl Fn(&GameEvent) + Send + Sync + 'static) {
+        self.builtin_handlers.write().push(Arc::new(handler));
+    }
+
+    /// Registers a handler that only receives custom events of `event_type`
+    pub fn register_custom_event(&self, event_type: impl Into<String>, handler: impl Fn(&GameEvent) + Send + Sync + 'static) {
+        self.custom_handlers
+            .write()
+            .entry(event_type.into())
+            .or_default()
+            .push(Arc::new(handler));
+    }
+
+    /// Delivers an event to its subscribers
+    ///
+    /// Handlers subscribed to every event run first, in subscription order,
+    /// followed by the builtin or custom handlers matching the event.
+    pub fn emit(&self, event: &GameEvent) {
+        // Clone the lists so handlers may subscribe or emit without deadlocking
+        let mut handlers: Vec<EventHandler> = self.handlers.read().clone();
+        match event {
+            GameEvent::Custom { event_type, .. } => {
+                if let Some(custom) = self.custom_handlers.read().get(event_type) {
+                    handlers.extend(custom.iter().cloned());
+                }
+            }
+            _ => handlers.extend(self.builtin_handlers.read().iter().cloned()),
+        }
+        for handler in handlers {
+            handler(event);
+        }
+    }
+}
@@ -35,7 +35,7 @@ fn main() {
     println!("cargo:rerun-if-changed=Cargo.toml");
 }
 
-fn discover_plugins(plugins_dir: &Path) -> Vec<(String, String, String)> {
+fn discover_plugins(plugins_dir: &Path) -> Vec<(String, String, String, bool)> {
     let mut valid_plugins = Vec::new();
     
     if let Ok(entries) = fs::read_dir(plugins_dir) {
@@ -67,26 +67,12 @@ fn discover_plugins(plugins_dir: &Path) -> Vec<(String, String, String)> {
                 if let Ok(mut file) = File::open(&cargo_toml) {
                     let mut contents = String::new();
                     if file.read_to_string(&mut contents).is_ok() {
-                        // Simple parsing for package name and version
-                        let mut name = None;
-                        let mut version = None;
-                        
-                        for line in contents.lines() {
-                            let line = line.trim();
-                            if line.starts_with("name") {
-                                name = line.split('=')
-                                    .nth(1)
-                                    .map(|s| s.trim().trim_matches('"').to_string());
-                            } else if line.starts_with("version") {
-                                version = line.split('=')
-                                    .nth(1)
-                                    .map(|s| s.trim().trim_matches('"').to_string());
+                        match read_manifest(&contents) {
+                            Some((name, version, default_enabled)) => {
+                                println!("cargo:warning=Found plugin: {} v{} in {}", name, version, plugin_name);
+                                valid_plugins.push((name, version, plugin_name, default_enabled));
                             }
-                        }
-                        
-                        if let (Some(name), Some(version)) = (name, version) {
-                            println!("cargo:warning=Found plugin: {} v{} in {}", name, version, plugin_name);
-                            valid_plugins.push((name, version, plugin_name));
+                            None => println!("cargo:warning=Skipping {}: its Cargo.toml has no package name and version", plugin_name),
                         }
                     }
                 }
@@ -97,10 +83,28 @@ fn discover_plugins(plugins_dir: &Path) -> Vec<(String, String, String)> {
     valid_plugins
 }
 
+/// Reads a plugin's package name, version and whether it is a default feature
+///
+/// Plugins opt out of the default feature set with `default = false` under
+/// `[package.metadata.horizon]`.
+fn read_manifest(contents: &str) -> Option<(String, String, bool)> {
+    let manifest = contents.parse::<toml_edit::DocumentMut>().ok()?;
+    let package = manifest.get("package")?;
+    let name = package.get("name")?.as_str()?.to_string();
+    let version = package.get("version")?.as_str()?.to_string();
+    let default_enabled = package
+        .get("metadata")
+        .and_then(|metadata| metadata.get("horizon"))
+        .and_then(|horizon| horizon.get("default"))
+        .and_then(|default| default.as_bool())
+        .unwrap_or(true);
+    Some((name, version, default_enabled))
+}
+
 const AUTO_GENERATED_START: &str = "###### BEGIN AUTO-GENERATED PLUGIN DEPENDENCIES - DO NOT EDIT THIS SECTION ######";
 const AUTO_GENERATED_END: &str = "###### END AUTO-GENERATED PLUGIN DEPENDENCIES ######";
 
-fn update_cargo_toml(plugin_paths: &[(String, String, String)]) -> std::io::Result<()> {
+fn update_cargo_toml(plugin_paths: &[(String, String, String, bool)]) -> std::io::Result<()> {
     let cargo_path = "Cargo.toml";
     let mut contents = String::new();
     File::open(cargo_path)?.read_to_string(&mut contents)?;
@@ -133,12 +137,25 @@ fn update_cargo_toml(plugin_paths: &[(String, String, String)]) -> std::io::Resu
     let mut sorted_plugins = plugin_paths.to_vec();
     sorted_plugins.sort_by(|a, b| a.0.cmp(&b.0));
     
-    for (name, version, plugin_dir) in sorted_plugins {
+    for (name, version, plugin_dir, _) in &sorted_plugins {
         new_section.push_str(&format!(
-            "{} = {{ path = \"../plugins/{}\", version = \"{}\" }}\n",
+            "{} = {{ path = \"../plugins/{}\", version = \"{}\", optional = true }}\n",
             name, plugin_dir, version
         ));
     }
+
+    // Every plugin is a feature of the same name; the default set excludes
+    // plugins that opted out
+    new_section.push_str("\n[features]\n");
+    let defaults: Vec<String> = sorted_plugins
+        .iter()
+        .filter(|(_, _, _, default_enabled)| *default_enabled)
+        .map(|(name, _, _, _)| format!("\"{}\"", name))
+        .collect();
+    new_section.push_str(&format!("default = [{}]\n", defaults.join(", ")));
+    for (name, _, _, _) in &sorted_plugins {
+        new_section.push_str(&format!("{} = [\"dep:{}\"]\n", name, name));
+    }
     
     new_section.push_str(AUTO_GENERATED_END);
 
@@ -157,7 +174,7 @@ fn update_cargo_toml(plugin_paths: &[(String, String, String)]) -> std::io::Resu
     Ok(())
 }
 
-fn generate_plugin_files(plugin_paths: &[(String, String, String)]) -> std::io::Result<()> {
+fn generate_plugin_files(plugin_paths: &[(String, String, String, bool)]) -> std::io::Result<()> {
     // Create the output directory if it doesn't exist
     let out_dir = Path::new("src");
     fs::create_dir_all(out_dir)?;
@@ -168,7 +185,7 @@ fn generate_plugin_files(plugin_paths: &[(String, String, String)]) -> std::io::
     Ok(())
 }
 
-fn generate_imports_file(plugin_paths: &[(String, String, String)], out_dir: &Path) -> std::io::Result<()> {
+fn generate_imports_file(plugin_paths: &[(String, String, String, bool)], out_dir: &Path) -> std::io::Result<()> {
     let mut file = fs::File::create(out_dir.join("plugin_imports.rs"))?;
     
     // Write the header
@@ -176,25 +193,28 @@ fn generate_imports_file(plugin_paths: &[(String, String, String)], out_dir: &Pa
     writeln!(file, "// Do not edit this file manually!\n")?;
     writeln!(file, "use horizon_plugin_api::{{Pluginstate, LoadedPlugin, Plugin}};")?;
     writeln!(file, "use std::collections::HashMap;\n")?;
-    for (i, (name, _, _)) in plugin_paths.iter().enumerate() {
+    for (i, (name, _, _, _)) in plugin_paths.iter().enumerate() {
+        write!(file, "#[cfg(feature = \"{}\")]\n", name)?;
         write!(file, "pub use {};\n", name)?;
+        write!(file, "#[cfg(feature = \"{}\")]\n", name)?;
         write!(file, "pub use {}::*;\n", name)?;
+        write!(file, "#[cfg(feature = \"{}\")]\n", name)?;
         write!(file, "pub use {}::Plugin as {}_plugin;\n", name, name)?;
     }
     writeln!(file, "\n");
 
 
     // Use the macro with discovered plugins
-    writeln!(file, "// Invoke the macro with all discovered plugins")?;
-    writeln!(file, "pub fn load_plugins() -> HashMap<String, (Pluginstate, Plugin)> {{")?;
-    write!(file, "    let plugins = crate::load_plugins!(")?;
+    writeln!(file, "// Invoke the macro with all discovered plugins, skipping disabled features")?;
+    writeln!(file, "pub fn load_plugins(enabled: Option<&[String]>) -> Result<crate::PluginSet, crate::PluginLoadError> {{")?;
+    write!(file, "    let plugins = crate::load_plugins!(enabled = enabled;")?;
     
     // Add each plugin to the macro invocation
-    for (i, (name, _, _)) in plugin_paths.iter().enumerate() {
+    for (i, (name, _, _, _)) in plugin_paths.iter().enumerate() {
         if i > 0 {
             write!(file, ",")?;
         }
-        write!(file, "\n        {}", name)?;
+        write!(file, "\n        #[cfg(feature = \"{}\")] {}", name, name)?;
     }
     
     writeln!(file, "\n    );")?;
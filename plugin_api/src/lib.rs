@@ -1,5 +1,9 @@
+use std::any::Any;
 use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, RwLock};
 pub use horizon_plugin_api::{Plugin, Pluginstate, Version, get_plugin, LoadedPlugin};
+pub use horizon_events::{global_bus, plugin_context, EventBus, GameEvent, PluginContext, RpcError, RpcRouter, ServerHandle};
 
 pub mod plugin_macro;
 pub mod plugin_imports;
@@ -13,24 +17,425 @@ const PLUGIN_API_VERSION: Version = Version {
 
 #[derive(Clone)]
 pub struct PluginManager {
-    plugins: HashMap<String,(Pluginstate,Plugin)>
+    plugins: HashMap<String,(Pluginstate,Plugin)>,
+    tick_hooks: Vec<TickHook>,
+    event_hooks: Vec<EventHook>,
+    shutdown_hooks: Vec<ShutdownHook>,
+    rpc_handlers: RpcTable,
+    failed: Vec<FailedPlugin>,
+    context: Option<PluginContext>,
 }
 
+/// Signature of a plugin's `call_rpc`
+pub type RpcFn = fn(&Plugin, &str, &(dyn Any + Send + Sync)) -> Option<Box<dyn Any + Send + Sync>>;
+
+/// A loaded plugin's `call_rpc`, bound to its instance
+#[derive(Clone)]
+pub struct RpcHandler {
+    pub plugin: Plugin,
+    pub call_rpc: RpcFn,
+}
+
+/// RPC handlers of a manager's active plugins, shared with the router handed to plugins
+///
+/// The router sees plugins loaded after it was handed out, so constructors
+/// can keep it and use it once loading has finished.
+#[derive(Clone, Default)]
+struct RpcTable(Arc<RwLock<HashMap<String, RpcHandler>>>);
+
+impl RpcRouter for RpcTable {
+    fn call_rpc(&self, plugin_name: &str, rpc_name: &str, params: &(dyn Any + Send + Sync)) -> Result<Box<dyn Any + Send + Sync>, RpcError> {
+        let handler = self
+            .0
+            .read()
+            .unwrap()
+            .get(plugin_name)
+            .cloned()
+            .ok_or_else(|| RpcError::UnknownPlugin(plugin_name.to_string()))?;
+
+        (handler.call_rpc)(&handler.plugin, rpc_name, params).ok_or_else(|| RpcError::UnknownRpc {
+            plugin: plugin_name.to_string(),
+            rpc: rpc_name.to_string(),
+        })
+    }
+}
+
+/// Routes RPCs to the plugins of several managers, such as one per pool group
+///
+/// A plugin is reached through the first added manager that has it active,
+/// so a plugin loaded by any group answers calls made from every group.
+/// Managers added after the router was handed out are seen as well.
+#[derive(Clone, Default)]
+pub struct SharedRpcRouter(Arc<RwLock<Vec<RpcTable>>>);
+
+impl SharedRpcRouter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Makes `manager`'s active plugins reachable through this router
+    pub fn add(&self, manager: &PluginManager) {
+        self.0.write().unwrap().push(manager.rpc_handlers.clone());
+    }
+}
+
+impl RpcRouter for SharedRpcRouter {
+    fn call_rpc(&self, plugin_name: &str, rpc_name: &str, params: &(dyn Any + Send + Sync)) -> Result<Box<dyn Any + Send + Sync>, RpcError> {
+        let tables = self.0.read().unwrap().clone();
+        for table in tables {
+            match table.call_rpc(plugin_name, rpc_name, params) {
+                Err(RpcError::UnknownPlugin(_)) => continue,
+                result => return result,
+            }
+        }
+        Err(RpcError::UnknownPlugin(plugin_name.to_string()))
+    }
+}
+
+/// A loaded plugin's `on_game_tick`, bound to its instance
+#[derive(Clone)]
+pub struct TickHook {
+    pub name: String,
+    pub plugin: Plugin,
+    pub on_game_tick: fn(&Plugin, f64),
+    /// Lower priorities tick first
+    pub priority: i32,
+}
+
+impl TickHook {
+    /// Advances the plugin by `delta_time` seconds
+    pub fn tick(&self, delta_time: f64) {
+        (self.on_game_tick)(&self.plugin, delta_time)
+    }
+}
+
+/// A loaded plugin's `on_game_event`, bound to its instance
+#[derive(Clone)]
+pub struct EventHook {
+    pub name: String,
+    pub plugin: Plugin,
+    pub on_game_event: fn(&Plugin, &GameEvent),
+    /// Custom event types the plugin handles, or None for all of them
+    pub custom_events: Option<Vec<&'static str>>,
+}
+
+impl EventHook {
+    /// Subscribes this hook to `bus`, routing it only the custom events it registered for
+    pub fn subscribe_with(self, bus: &EventBus, deliver: impl Fn(&GameEvent) + Send + Sync + Clone + 'static) {
+        match self.custom_events {
+            None => bus.subscribe(deliver),
+            Some(event_types) => {
+                bus.subscribe_builtin(deliver.clone());
+                for event_type in event_types {
+                    bus.register_custom_event(event_type, deliver.clone());
+                }
+            }
+        }
+    }
+}
+
+/// A loaded plugin's `on_shutdown`, bound to its instance
+#[derive(Clone)]
+pub struct ShutdownHook {
+    pub name: String,
+    pub plugin: Plugin,
+    pub on_shutdown: fn(&Plugin),
+}
+
+impl ShutdownHook {
+    /// Lets the plugin save its state before the server exits
+    pub fn shutdown(&self) {
+        (self.on_shutdown)(&self.plugin)
+    }
+}
+
+/// A plugin built by `construct_in_order`, with the hooks the host calls on it
+pub struct ConstructedPlugin {
+    pub name: String,
+    pub state: Pluginstate,
+    pub plugin: Plugin,
+    pub on_game_tick: fn(&Plugin, f64),
+    pub tick_priority: i32,
+    pub on_game_event: fn(&Plugin, &GameEvent),
+    pub custom_events: Option<Vec<&'static str>>,
+    pub on_shutdown: fn(&Plugin),
+    pub call_rpc: RpcFn,
+}
+
+/// Result of constructing the discovered plugins
+pub struct PluginSet {
+    /// Plugins that were built, in construction order
+    pub constructed: Vec<ConstructedPlugin>,
+    /// Plugins that could not be built, in construction order
+    pub failed: Vec<FailedPlugin>,
+}
+
+/// A plugin left out of the loaded set, and why
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailedPlugin {
+    pub name: String,
+    pub reason: String,
+}
+
+/// Errors that prevent the plugin set from being loaded
+#[derive(Debug, Clone)]
+pub enum PluginLoadError {
+    /// Plugins depend on each other in a loop, listed in dependency order
+    DependencyCycle(Vec<String>),
+    /// A plugin declared a dependency that is not part of the build
+    MissingDependency { plugin: String, dependency: String },
+}
+
+impl fmt::Display for PluginLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PluginLoadError::DependencyCycle(path) => write!(f, "Plugin dependency cycle: {}", path.join(" -> ")),
+            PluginLoadError::MissingDependency { plugin, dependency } => {
+                write!(f, "Plugin {} depends on {}, which is not loaded", plugin, dependency)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PluginLoadError {}
+
+/// Everything needed to construct a plugin once its dependencies are ready
+pub struct PluginConstructor {
+    pub name: &'static str,
+    pub dependencies: Vec<&'static str>,
+    pub api_version: Version,
+    pub construct: fn(HashMap<String, (Pluginstate, Plugin)>) -> Plugin,
+    pub on_game_tick: fn(&Plugin, f64),
+    pub tick_priority: i32,
+    pub on_game_event: fn(&Plugin, &GameEvent),
+    pub custom_events: Option<Vec<&'static str>>,
+    pub on_shutdown: fn(&Plugin),
+    pub call_rpc: RpcFn,
+}
+
+/// Collects the given plugins and constructs them in dependency order
+///
+/// Each plugin's constructor receives the plugins built so far, and may assume
+/// every plugin named in its `PluginConstruct::dependencies` is already present.
+/// Plugins may be preceded by attributes such as `#[cfg(feature = "...")]`.
+/// An optional leading `enabled = <Option<&[String]>>;` limits construction to
+/// the named plugins and their dependencies, as in `enabled_constructors`.
 #[macro_export]
 macro_rules! load_plugins {
-    ($($plugin:ident),* $(,)?) => {
+    (enabled = $enabled:expr; $($(#[$meta:meta])* $plugin:ident),* $(,)?) => {
         {
-            let mut plugins = HashMap::new();
+            #[allow(unused_mut)]
+            let mut constructors: Vec<$crate::PluginConstructor> = Vec::new();
             $(
-                plugins.insert(
-                    stringify!($plugin).to_string(),
-                    (Pluginstate::ACTIVE, <$plugin::Plugin as $plugin::PluginConstruct>::new(plugins.clone())),
-                );
+                $(#[$meta])*
+                constructors.push($crate::PluginConstructor {
+                    name: stringify!($plugin),
+                    dependencies: <$plugin::Plugin as $plugin::PluginConstruct>::dependencies(),
+                    api_version: <$plugin::Plugin as $plugin::PluginConstruct>::api_version(),
+                    construct: <$plugin::Plugin as $plugin::PluginConstruct>::new,
+                    on_game_tick: <$plugin::Plugin as $plugin::PluginConstruct>::on_game_tick,
+                    tick_priority: <$plugin::Plugin as $plugin::PluginConstruct>::tick_priority(),
+                    on_game_event: <$plugin::Plugin as $plugin::PluginConstruct>::on_game_event,
+                    custom_events: <$plugin::Plugin as $plugin::PluginConstruct>::custom_events(),
+                    on_shutdown: <$plugin::Plugin as $plugin::PluginConstruct>::on_shutdown,
+                    call_rpc: <$plugin::Plugin as $plugin::PluginConstruct>::call_rpc,
+                });
             )*
-            
-            plugins
+
+            $crate::construct_in_order($crate::enabled_constructors(constructors, $enabled))
         }
     };
+    ($($(#[$meta:meta])* $plugin:ident),* $(,)?) => {
+        $crate::load_plugins!(enabled = None; $($(#[$meta])* $plugin),*)
+    };
+}
+
+/// Keeps the constructors of the `enabled` plugins and of everything they depend on
+///
+/// The rest are dropped before anything is constructed, so disabled plugins
+/// never run their constructors. `None` keeps every plugin.
+pub fn enabled_constructors(constructors: Vec<PluginConstructor>, enabled: Option<&[String]>) -> Vec<PluginConstructor> {
+    let Some(enabled) = enabled else {
+        return constructors;
+    };
+
+    let mut wanted: Vec<&str> = enabled.iter().map(String::as_str).collect();
+    let mut index = 0;
+    while index < wanted.len() {
+        if let Some(constructor) = constructors.iter().find(|constructor| constructor.name == wanted[index]) {
+            for dependency in &constructor.dependencies {
+                if !wanted.contains(dependency) {
+                    wanted.push(dependency);
+                }
+            }
+        }
+        index += 1;
+    }
+
+    let wanted: Vec<String> = wanted.into_iter().map(str::to_string).collect();
+    constructors.into_iter().filter(|constructor| wanted.iter().any(|name| name == constructor.name)).collect()
+}
+
+/// Sorts hooks into tick order: ascending priority, then plugin name
+pub fn sort_tick_hooks(hooks: &mut [TickHook]) {
+    hooks.sort_by(|a, b| a.priority.cmp(&b.priority).then_with(|| a.name.cmp(&b.name)));
+}
+
+/// Whether a plugin built against `plugin` can be loaded by a host at `host`
+///
+/// Major versions must match and the plugin's minor version may not be newer
+/// than the host's. Hotfix versions never affect compatibility.
+pub fn is_compatible(host: &Version, plugin: &Version) -> bool {
+    plugin.major == host.major && plugin.minor <= host.minor
+}
+
+fn format_version(version: &Version) -> String {
+    format!("{}.{}.{}", version.major, version.minor, version.hotfix)
+}
+
+/// Constructs plugins so that each one is built after everything it depends on
+///
+/// Plugins built against an incompatible plugin API version are skipped with
+/// a reason naming both versions. A plugin whose constructor panics is skipped
+/// the same way. Either way every plugin depending on it is skipped too, the
+/// rest still load, and the skipped ones are listed in `PluginSet::failed`.
+pub fn construct_in_order(constructors: Vec<PluginConstructor>) -> Result<PluginSet, PluginLoadError> {
+    let order = dependency_order(&constructors)?;
+
+    let mut plugins = HashMap::new();
+    let mut constructed = Vec::with_capacity(order.len());
+    let mut failed: Vec<FailedPlugin> = Vec::new();
+    for index in order {
+        let constructor = &constructors[index];
+
+        if !is_compatible(&PLUGIN_API_VERSION, &constructor.api_version) {
+            let reason = format!(
+                "built against plugin API {}, but the host provides {}",
+                format_version(&constructor.api_version),
+                format_version(&PLUGIN_API_VERSION),
+            );
+            failed.push(FailedPlugin { name: constructor.name.to_string(), reason });
+            continue;
+        }
+
+        if let Some(dependency) = constructor
+            .dependencies
+            .iter()
+            .find(|dependency| failed.iter().any(|plugin| plugin.name == **dependency))
+        {
+            let reason = format!("its dependency {} failed to load", dependency);
+            failed.push(FailedPlugin { name: constructor.name.to_string(), reason });
+            continue;
+        }
+
+        let available = plugins.clone();
+        let plugin = match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| (constructor.construct)(available))) {
+            Ok(plugin) => plugin,
+            Err(payload) => {
+                let reason = format!("panicked while loading: {}", panic_message(payload.as_ref()));
+                failed.push(FailedPlugin { name: constructor.name.to_string(), reason });
+                continue;
+            }
+        };
+        plugins.insert(constructor.name.to_string(), (Pluginstate::ACTIVE, plugin.clone()));
+        constructed.push(ConstructedPlugin {
+            name: constructor.name.to_string(),
+            state: Pluginstate::ACTIVE,
+            plugin,
+            on_game_tick: constructor.on_game_tick,
+            tick_priority: constructor.tick_priority,
+            on_game_event: constructor.on_game_event,
+            custom_events: constructor.custom_events.clone(),
+            on_shutdown: constructor.on_shutdown,
+            call_rpc: constructor.call_rpc,
+        });
+    }
+
+    Ok(PluginSet { constructed, failed })
+}
+
+/// Text of a panic payload, for the common `&str` and `String` cases
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic payload"
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum VisitState {
+    Unvisited,
+    InProgress,
+    Done,
+}
+
+/// Topologically sorts plugins by their declared dependencies
+///
+/// Independent plugins are visited in name order so the result is the same on
+/// every run.
+fn dependency_order(constructors: &[PluginConstructor]) -> Result<Vec<usize>, PluginLoadError> {
+    let index: HashMap<&str, usize> = constructors
+        .iter()
+        .enumerate()
+        .map(|(i, constructor)| (constructor.name, i))
+        .collect();
+
+    let mut roots: Vec<usize> = (0..constructors.len()).collect();
+    roots.sort_by_key(|&i| constructors[i].name);
+
+    let mut state = vec![VisitState::Unvisited; constructors.len()];
+    let mut path = Vec::new();
+    let mut order = Vec::with_capacity(constructors.len());
+    for root in roots {
+        visit_dependencies(root, constructors, &index, &mut state, &mut path, &mut order)?;
+    }
+
+    Ok(order)
+}
+
+fn visit_dependencies(
+    current: usize,
+    constructors: &[PluginConstructor],
+    index: &HashMap<&str, usize>,
+    state: &mut [VisitState],
+    path: &mut Vec<usize>,
+    order: &mut Vec<usize>,
+) -> Result<(), PluginLoadError> {
+    match state[current] {
+        VisitState::Done => return Ok(()),
+        VisitState::InProgress => {
+            // Report the loop starting from where it was first entered
+            let start = path.iter().position(|&i| i == current).unwrap_or(0);
+            let mut cycle: Vec<String> = path[start..]
+                .iter()
+                .map(|&i| constructors[i].name.to_string())
+                .collect();
+            cycle.push(constructors[current].name.to_string());
+            return Err(PluginLoadError::DependencyCycle(cycle));
+        }
+        VisitState::Unvisited => {}
+    }
+
+    state[current] = VisitState::InProgress;
+    path.push(current);
+
+    for dependency in &constructors[current].dependencies {
+        let Some(&next) = index.get(dependency) else {
+            return Err(PluginLoadError::MissingDependency {
+                plugin: constructors[current].name.to_string(),
+                dependency: dependency.to_string(),
+            });
+        };
+        visit_dependencies(next, constructors, index, state, path, order)?;
+    }
+
+    path.pop();
+    state[current] = VisitState::Done;
+    order.push(current);
+    Ok(())
 }
 
 impl PluginManager {
@@ -38,11 +443,48 @@ impl PluginManager {
     pub fn new() -> PluginManager {
         let new_manager = PluginManager {
             plugins: HashMap::new(), 
+            tick_hooks: Vec::new(),
+            event_hooks: Vec::new(),
+            shutdown_hooks: Vec::new(),
+            rpc_handlers: RpcTable::default(),
+            failed: Vec::new(),
+            context: None,
         };
 
         new_manager 
     }
 
+    /// Attach the host's services so plugins can reach the live server and each other
+    ///
+    /// Plugins read the resulting context with `horizon_events::plugin_context()`,
+    /// including from their constructors when this is called before `load_all`
+    /// or `load_enabled`.
+    /// Its `rpc` router dispatches to this manager's active plugins.
+    pub fn set_context(&mut self, server: Arc<dyn ServerHandle>) {
+        let context = PluginContext { server, rpc: self.rpc_router() };
+        horizon_events::set_plugin_context(context.clone());
+        self.context = Some(context);
+    }
+
+    /// Joins a context shared by several managers, whose `rpc` is `router`
+    ///
+    /// This manager's plugins become reachable through `router`. Unlike
+    /// `set_context`, the process-wide context is left alone: the host
+    /// installs the shared one once, so no manager's plugins replace another's.
+    pub fn share_context(&mut self, context: PluginContext, router: &SharedRpcRouter) {
+        router.add(self);
+        self.context = Some(context);
+    }
+
+    /// Router for this manager's RPCs, as handed to plugins through the context
+    pub fn rpc_router(&self) -> Arc<dyn RpcRouter> {
+        Arc::new(self.rpc_handlers.clone())
+    }
+
+    pub fn context(&self) -> Option<&PluginContext> {
+        self.context.as_ref()
+    }
+
     pub fn load_plugin(mut self,name: String, plugin: Plugin) {
         self.plugins.insert(name, (Pluginstate::ACTIVE, plugin));
     }
@@ -55,8 +497,116 @@ impl PluginManager {
         self.plugins
     }
 
-    pub fn load_all(&mut self) ->  HashMap<String, LoadedPlugin> {
-        self.plugins = plugin_imports::load_plugins();
+    /// `on_game_tick` hooks of the active plugins, in tick order
+    ///
+    /// Hooks are sorted by ascending `tick_priority`, and plugins with equal
+    /// priorities by name, so the order is the same on every run.
+    pub fn tick_hooks(&self) -> Vec<TickHook> {
+        let mut hooks = self.tick_hooks.clone();
+        sort_tick_hooks(&mut hooks);
+        hooks
+    }
+
+    /// Plugins that failed to load during the last `load_all` or `load_enabled`, with the reason
+    pub fn failed_plugins(&self) -> Vec<FailedPlugin> {
+        self.failed.clone()
+    }
+
+    /// Calls an RPC registered by another loaded plugin
+    ///
+    /// The result is whatever the target plugin returned, to be downcast by
+    /// the caller to the type the RPC documents.
+    pub fn call_rpc(&self, plugin_name: &str, rpc_name: &str, params: &(dyn Any + Send + Sync)) -> Result<Box<dyn Any + Send + Sync>, RpcError> {
+        self.rpc_handlers.call_rpc(plugin_name, rpc_name, params)
+    }
+
+    /// `on_game_event` hooks of the active plugins, in construction order
+    pub fn event_hooks(&self) -> Vec<EventHook> {
+        self.event_hooks.clone()
+    }
+
+    /// `on_shutdown` hooks of the active plugins, in construction order
+    pub fn shutdown_hooks(&self) -> Vec<ShutdownHook> {
+        self.shutdown_hooks.clone()
+    }
+
+    /// Drops every loaded plugin whose name is not in `keep`
+    ///
+    /// Dropped plugins no longer tick, receive events or answer RPCs through
+    /// this manager.
+    pub fn retain_plugins(&mut self, keep: &[String]) {
+        self.plugins.retain(|name, _| keep.contains(name));
+        self.tick_hooks.retain(|hook| keep.contains(&hook.name));
+        self.event_hooks.retain(|hook| keep.contains(&hook.name));
+        self.shutdown_hooks.retain(|hook| keep.contains(&hook.name));
+        self.rpc_handlers.0.write().unwrap().retain(|name, _| keep.contains(name));
+    }
+
+    /// Subscribes every active plugin's `on_game_event` to `bus`
+    ///
+    /// Handlers run synchronously inside `EventBus::emit`. Hosts that must not
+    /// be held up by a slow plugin should dispatch `event_hooks` themselves.
+    pub fn subscribe_plugins(&self, bus: &EventBus) {
+        for hook in self.event_hooks.clone() {
+            let (plugin, on_game_event) = (hook.plugin.clone(), hook.on_game_event);
+            hook.subscribe_with(bus, move |event| on_game_event(&plugin, event));
+        }
+    }
+
+    /// Loads every discovered plugin, constructing dependencies first
+    ///
+    /// Plugins that panic during construction or target an incompatible
+    /// plugin API are left out, along with their dependents, and reported by
+    /// `failed_plugins`.
+    pub fn load_all(&mut self) -> Result<HashMap<String, LoadedPlugin>, PluginLoadError> {
+        self.load_enabled(None)
+    }
+
+    /// Loads the `enabled` plugins and their dependencies, or every plugin when `None`
+    ///
+    /// Other plugins are never constructed. Failures are handled as in `load_all`.
+    pub fn load_enabled(&mut self, enabled: Option<&[String]>) -> Result<HashMap<String, LoadedPlugin>, PluginLoadError> {
+        let set = plugin_imports::load_plugins(enabled)?;
+        Ok(self.install(set))
+    }
+
+    /// Replaces the managed plugins with a constructed set and binds their hooks
+    fn install(&mut self, set: PluginSet) -> HashMap<String, LoadedPlugin> {
+        let PluginSet { constructed, failed } = set;
+
+        self.failed = failed;
+        self.plugins = HashMap::new();
+        self.tick_hooks = Vec::new();
+        self.event_hooks = Vec::new();
+        self.shutdown_hooks = Vec::new();
+        let mut rpc_handlers = HashMap::new();
+        for plugin in constructed {
+            if plugin.state == Pluginstate::ACTIVE {
+                self.tick_hooks.push(TickHook {
+                    name: plugin.name.clone(),
+                    plugin: plugin.plugin.clone(),
+                    on_game_tick: plugin.on_game_tick,
+                    priority: plugin.tick_priority,
+                });
+                self.event_hooks.push(EventHook {
+                    name: plugin.name.clone(),
+                    plugin: plugin.plugin.clone(),
+                    on_game_event: plugin.on_game_event,
+                    custom_events: plugin.custom_events.clone(),
+                });
+                self.shutdown_hooks.push(ShutdownHook {
+                    name: plugin.name.clone(),
+                    plugin: plugin.plugin.clone(),
+                    on_shutdown: plugin.on_shutdown,
+                });
+                rpc_handlers.insert(plugin.name.clone(), RpcHandler {
+                    plugin: plugin.plugin.clone(),
+                    call_rpc: plugin.call_rpc,
+                });
+            }
+            self.plugins.insert(plugin.name, (plugin.state, plugin.plugin));
+        }
+        *self.rpc_handlers.0.write().unwrap() = rpc_handlers;
     
         //let my_test_plugin = get_plugin!(test_plugin, plugins);
         //let result = my_test_plugin.thing();
@@ -72,3 +622,323 @@ impl PluginManager {
         loaded_plugins
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Held by tests that install the process-wide plugin context
+    static CONTEXT: Mutex<()> = Mutex::new(());
+
+    /// Server handle that remembers every message it was asked to broadcast
+    #[derive(Default)]
+    struct RecordingServer {
+        messages: Mutex<Vec<String>>,
+    }
+
+    impl ServerHandle for RecordingServer {
+        fn broadcast_message(&self, message: &str) {
+            self.messages.lock().unwrap().push(message.to_string());
+        }
+
+        fn broadcast_event(&self, name: &str, _payload: serde_json::Value) {
+            self.messages.lock().unwrap().push(name.to_string());
+        }
+
+        fn broadcast_to_region(&self, _pool_group: &str, _center: [f64; 3], _radius: f64, name: &str, _payload: serde_json::Value) -> usize {
+            self.messages.lock().unwrap().push(name.to_string());
+            1
+        }
+
+        fn plugin_data_dir(&self) -> std::path::PathBuf {
+            std::env::temp_dir()
+        }
+
+        fn log_error(&self, _category: &str, message: &str) {
+            self.messages.lock().unwrap().push(message.to_string());
+        }
+    }
+
+    fn constructor(
+        name: &'static str,
+        dependencies: Vec<&'static str>,
+        construct: fn(HashMap<String, (Pluginstate, Plugin)>) -> Plugin,
+    ) -> PluginConstructor {
+        PluginConstructor {
+            name,
+            dependencies,
+            api_version: PLUGIN_API_VERSION,
+            construct,
+            on_game_tick: |_, _| {},
+            tick_priority: 0,
+            on_game_event: |_, _| {},
+            custom_events: None,
+            on_shutdown: |_| {},
+            call_rpc: |_, _, _| None,
+        }
+    }
+
+    #[test]
+    fn constructors_reach_the_server_through_the_context() {
+        let _context = CONTEXT.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let server = Arc::new(RecordingServer::default());
+        let mut manager = PluginManager::new();
+        manager.set_context(server.clone());
+
+        let set = construct_in_order(vec![constructor("announcer", vec![], |_| {
+            plugin_context().expect("context is set before construction").server.broadcast_message("constructed");
+            Plugin {}
+        })])
+        .unwrap();
+
+        assert_eq!(set.constructed.len(), 1);
+        assert_eq!(*server.messages.lock().unwrap(), vec!["constructed".to_string()]);
+    }
+
+    #[test]
+    fn plugins_call_each_other_through_the_context() {
+        let _context = CONTEXT.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut manager = PluginManager::new();
+        manager.set_context(Arc::new(RecordingServer::default()));
+
+        let mut answerer = constructor("answerer", vec![], |_| Plugin {});
+        answerer.call_rpc = |_, rpc, params| {
+            let question = params.downcast_ref::<u32>()?;
+            (rpc == "double").then(|| Box::new(question * 2) as Box<dyn Any + Send + Sync>)
+        };
+        let asker = constructor("asker", vec!["answerer"], |_| Plugin {});
+        manager.install(construct_in_order(vec![asker, answerer]).unwrap());
+
+        // What the asker would do from its hooks
+        let rpc = plugin_context().expect("context is set").rpc;
+        let answer = rpc.call_rpc("answerer", "double", &21u32).unwrap();
+        assert_eq!(answer.downcast_ref::<u32>(), Some(&42));
+        assert_eq!(
+            rpc.call_rpc("answerer", "triple", &21u32).err(),
+            Some(RpcError::UnknownRpc { plugin: "answerer".to_string(), rpc: "triple".to_string() })
+        );
+        assert_eq!(rpc.call_rpc("missing", "double", &21u32).err(), Some(RpcError::UnknownPlugin("missing".to_string())));
+
+        // Plugins dropped from the manager can no longer be called
+        manager.retain_plugins(&["asker".to_string()]);
+        assert!(rpc.call_rpc("answerer", "double", &21u32).is_err());
+    }
+
+    #[test]
+    fn plugins_of_one_group_are_reached_from_every_group() {
+        let _context = CONTEXT.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let router = SharedRpcRouter::new();
+        let context = PluginContext { server: Arc::new(RecordingServer::default()), rpc: Arc::new(router.clone()) };
+        horizon_events::set_plugin_context(context.clone());
+
+        let mut clock = constructor("clock", vec![], |_| Plugin {});
+        clock.call_rpc = |_, rpc, _| (rpc == "is_daytime").then(|| Box::new(true) as Box<dyn Any + Send + Sync>);
+        let (mut with_clock, mut without_clock) = (PluginManager::new(), PluginManager::new());
+        with_clock.share_context(context.clone(), &router);
+        with_clock.install(construct_in_order(vec![clock, constructor("weather", vec![], |_| Plugin {})]).unwrap());
+        // Built last, as the group without the clock might be
+        without_clock.share_context(context, &router);
+        without_clock.install(construct_in_order(vec![constructor("weather", vec![], |_| Plugin {})]).unwrap());
+
+        assert_eq!(
+            without_clock.call_rpc("clock", "is_daytime", &()).err(),
+            Some(RpcError::UnknownPlugin("clock".to_string()))
+        );
+        let rpc = plugin_context().expect("context is set").rpc;
+        let daytime = rpc.call_rpc("clock", "is_daytime", &()).unwrap();
+        assert_eq!(daytime.downcast_ref::<bool>(), Some(&true));
+        assert_eq!(
+            rpc.call_rpc("clock", "is_night", &()).err(),
+            Some(RpcError::UnknownRpc { plugin: "clock".to_string(), rpc: "is_night".to_string() })
+        );
+        assert_eq!(rpc.call_rpc("missing", "is_daytime", &()).err(), Some(RpcError::UnknownPlugin("missing".to_string())));
+    }
+
+    #[test]
+    fn incompatible_plugins_are_skipped_with_their_dependents() {
+        let mut outdated = constructor("outdated", vec![], |_| Plugin {});
+        outdated.api_version = Version { major: PLUGIN_API_VERSION.major + 1, minor: 0, hotfix: 0 };
+        let dependent = constructor("dependent", vec!["outdated"], |_| Plugin {});
+        let standalone = constructor("standalone", vec![], |_| Plugin {});
+
+        let set = construct_in_order(vec![dependent, outdated, standalone]).unwrap();
+
+        let built: Vec<&str> = set.constructed.iter().map(|plugin| plugin.name.as_str()).collect();
+        assert_eq!(built, vec!["standalone"]);
+        let failed: Vec<&str> = set.failed.iter().map(|plugin| plugin.name.as_str()).collect();
+        assert_eq!(failed, vec!["outdated", "dependent"]);
+        assert!(set.failed[0].reason.contains("plugin API 1.0.0"));
+        assert!(set.failed[1].reason.contains("outdated"));
+    }
+
+    fn names(constructors: &[PluginConstructor], order: &[usize]) -> Vec<&'static str> {
+        order.iter().map(|&i| constructors[i].name).collect()
+    }
+
+    #[test]
+    fn plugins_are_built_after_their_dependencies() {
+        let constructors = vec![
+            constructor("game", vec!["physics", "net"], |_| Plugin {}),
+            constructor("net", vec![], |_| Plugin {}),
+            constructor("physics", vec!["math"], |_| Plugin {}),
+            constructor("math", vec![], |_| Plugin {}),
+        ];
+
+        let order = names(&constructors, &dependency_order(&constructors).unwrap());
+        assert_eq!(order, vec!["math", "physics", "net", "game"]);
+
+        // Each constructor sees exactly the plugins it comes after
+        let set = construct_in_order(vec![
+            constructor("game", vec!["physics", "net"], |available| {
+                assert!(["physics", "net", "math"].iter().all(|name| available.contains_key(*name)));
+                Plugin {}
+            }),
+            constructor("net", vec![], |_| Plugin {}),
+            constructor("physics", vec!["math"], |available| {
+                assert!(available.contains_key("math") && !available.contains_key("game"));
+                Plugin {}
+            }),
+            constructor("math", vec![], |available| {
+                assert!(available.is_empty());
+                Plugin {}
+            }),
+        ])
+        .unwrap();
+        assert!(set.failed.is_empty());
+        let built: Vec<&str> = set.constructed.iter().map(|plugin| plugin.name.as_str()).collect();
+        assert_eq!(built, order);
+    }
+
+    #[test]
+    fn a_dependency_cycle_is_reported_with_its_path() {
+        let constructors = vec![
+            constructor("standalone", vec![], |_| Plugin {}),
+            constructor("crafting", vec!["inventory"], |_| Plugin {}),
+            constructor("inventory", vec!["economy"], |_| Plugin {}),
+            constructor("economy", vec!["crafting"], |_| Plugin {}),
+        ];
+
+        match dependency_order(&constructors) {
+            Err(PluginLoadError::DependencyCycle(path)) => {
+                assert_eq!(path, vec!["crafting", "inventory", "economy", "crafting"]);
+                let message = PluginLoadError::DependencyCycle(path).to_string();
+                assert!(message.contains("crafting -> inventory -> economy -> crafting"), "{}", message);
+            }
+            other => panic!("cycle was not reported: {:?}", other),
+        }
+        assert!(matches!(construct_in_order(constructors), Err(PluginLoadError::DependencyCycle(_))));
+    }
+
+    #[test]
+    fn a_missing_dependency_is_named() {
+        let constructors = vec![
+            constructor("quests", vec!["dialogue"], |_| Plugin {}),
+            constructor("combat", vec![], |_| Plugin {}),
+        ];
+
+        match construct_in_order(constructors) {
+            Err(PluginLoadError::MissingDependency { plugin, dependency }) => {
+                assert_eq!((plugin.as_str(), dependency.as_str()), ("quests", "dialogue"));
+            }
+            Err(other) => panic!("wrong error: {}", other),
+            Ok(_) => panic!("a missing dependency was not reported"),
+        }
+    }
+
+    #[test]
+    fn plugins_built_against_the_host_version_are_compatible() {
+        let host = Version { major: 2, minor: 3, hotfix: 1 };
+
+        assert!(is_compatible(&host, &Version { major: 2, minor: 3, hotfix: 1 }));
+        // Hotfixes never matter, in either direction
+        assert!(is_compatible(&host, &Version { major: 2, minor: 3, hotfix: 9 }));
+    }
+
+    #[test]
+    fn plugins_built_against_an_older_minor_are_compatible() {
+        let host = Version { major: 2, minor: 3, hotfix: 0 };
+
+        assert!(is_compatible(&host, &Version { major: 2, minor: 2, hotfix: 7 }));
+        assert!(is_compatible(&host, &Version { major: 2, minor: 0, hotfix: 0 }));
+    }
+
+    #[test]
+    fn plugins_built_against_a_newer_minor_or_another_major_are_rejected() {
+        let host = Version { major: 2, minor: 3, hotfix: 0 };
+
+        assert!(!is_compatible(&host, &Version { major: 2, minor: 4, hotfix: 0 }));
+        assert!(!is_compatible(&host, &Version { major: 1, minor: 3, hotfix: 0 }));
+        assert!(!is_compatible(&host, &Version { major: 3, minor: 0, hotfix: 0 }));
+    }
+
+    #[test]
+    fn a_panicking_constructor_does_not_stop_the_others() {
+        let set = construct_in_order(vec![
+            constructor("broken", vec![], |_| panic!("data dir is not writable")),
+            constructor("needs_broken", vec!["broken"], |_| Plugin {}),
+            constructor("healthy", vec![], |_| Plugin {}),
+        ])
+        .unwrap();
+
+        let mut manager = PluginManager::new();
+        let loaded = manager.install(set);
+
+        assert_eq!(loaded.keys().collect::<Vec<_>>(), vec!["healthy"]);
+        let failed = manager.failed_plugins();
+        assert_eq!(failed.len(), 2);
+        assert_eq!(failed[0], FailedPlugin {
+            name: "broken".to_string(),
+            reason: "panicked while loading: data dir is not writable".to_string(),
+        });
+        assert_eq!(failed[1].name, "needs_broken");
+        assert_eq!(manager.tick_hooks().len(), 1);
+    }
+
+    #[test]
+    fn disabled_plugins_are_never_constructed() {
+        static DISABLED_BUILT: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+        let enabled = ["game".to_string()];
+
+        let constructors = enabled_constructors(
+            vec![
+                constructor("game", vec!["library"], |_| Plugin {}),
+                constructor("library", vec![], |_| Plugin {}),
+                constructor("disabled", vec![], |_| {
+                    DISABLED_BUILT.store(true, std::sync::atomic::Ordering::SeqCst);
+                    Plugin {}
+                }),
+            ],
+            Some(&enabled),
+        );
+        let set = construct_in_order(constructors).unwrap();
+
+        let built: Vec<&str> = set.constructed.iter().map(|plugin| plugin.name.as_str()).collect();
+        assert_eq!(built, vec!["library", "game"]);
+        assert!(!DISABLED_BUILT.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[test]
+    fn custom_events_reach_only_the_plugins_registered_for_them() {
+        let bus = EventBus::new();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        for (name, event_type) in [("crafting", "craft_item"), ("trading", "open_shop")] {
+            let hook = EventHook { name: name.to_string(), plugin: Plugin {}, on_game_event: |_, _| {}, custom_events: Some(vec![event_type]) };
+            let received = Arc::clone(&received);
+            hook.subscribe_with(&bus, move |event| received.lock().unwrap().push((name, event.clone())));
+        }
+
+        let craft = GameEvent::custom("craft_item", serde_json::json!({ "recipe": "bread" }));
+        let shop = GameEvent::custom("open_shop", serde_json::json!({ "shop": "bakery" }));
+        bus.emit(&craft);
+        bus.emit(&shop);
+        bus.emit(&GameEvent::custom("unregistered", serde_json::Value::Null));
+
+        assert_eq!(*received.lock().unwrap(), vec![("crafting", craft), ("trading", shop)]);
+
+        // Builtin events still reach every plugin
+        let joined = GameEvent::PlayerJoined { player: uuid::Uuid::new_v4() };
+        bus.emit(&joined);
+        assert_eq!(received.lock().unwrap().len(), 4);
+    }
+}
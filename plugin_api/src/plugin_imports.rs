@@ -4,19 +4,32 @@
 use horizon_plugin_api::{Pluginstate, LoadedPlugin, Plugin};
 use std::collections::HashMap;
 
+#[cfg(feature = "chronos_plugin")]
 pub use chronos_plugin;
+#[cfg(feature = "chronos_plugin")]
 pub use chronos_plugin::*;
+#[cfg(feature = "chronos_plugin")]
 pub use chronos_plugin::Plugin as chronos_plugin_plugin;
+#[cfg(feature = "player_lib")]
 pub use player_lib;
+#[cfg(feature = "player_lib")]
 pub use player_lib::*;
+#[cfg(feature = "player_lib")]
 pub use player_lib::Plugin as player_lib_plugin;
+#[cfg(feature = "weather_plugin")]
+pub use weather_plugin;
+#[cfg(feature = "weather_plugin")]
+pub use weather_plugin::*;
+#[cfg(feature = "weather_plugin")]
+pub use weather_plugin::Plugin as weather_plugin_plugin;
 
 
-// Invoke the macro with all discovered plugins
-pub fn load_plugins() -> HashMap<String, (Pluginstate, Plugin)> {
-    let plugins = crate::load_plugins!(
-        chronos_plugin,
-        player_lib
+// Invoke the macro with all discovered plugins, skipping disabled features
+pub fn load_plugins(enabled: Option<&[String]>) -> Result<crate::PluginSet, crate::PluginLoadError> {
+    let plugins = crate::load_plugins!(enabled = enabled;
+        #[cfg(feature = "chronos_plugin")] chronos_plugin,
+        #[cfg(feature = "player_lib")] player_lib,
+        #[cfg(feature = "weather_plugin")] weather_plugin
     );
     plugins
 }
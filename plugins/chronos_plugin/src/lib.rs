@@ -3,17 +3,30 @@ use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
 use horizon_plugin_api::Pluginstate;
 use parking_lot::RwLock;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH, Duration};
 use  std::sync::OnceLock;
 use lazy_static::lazy_static;
+use horizon_events::{global_bus, plugin_context, GameEvent};
+use serde::{Deserialize, Serialize};
 
 // Import the plugin API publicly to allow the API to make calls against this plugin
-pub use horizon_plugin_api::{Plugin, LoadedPlugin};
+pub use horizon_plugin_api::{Plugin, LoadedPlugin, Version};
+
+/// Plugin API version this plugin targets
+const PLUGIN_API_VERSION: Version = Version {
+    major: 0,
+    minor: 1,
+    hotfix: 0
+};
 
 // Time configuration constants
 const MINUTES_PER_HOUR: i32 = 60;
 const HOURS_PER_DAY: i32 = 24;
 
+/// Name of the file in the server's plugin data directory the time of day is saved to
+pub const TIME_FILE: &str = "chronos_time.json";
+
 // Global state using static
 //static mut TIME_STATE: Option<Arc<RwLock<TimeState>>> = None;
 
@@ -32,7 +45,7 @@ fn time_state() -> Arc<RwLock<TimeState>> {
 
 
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
 pub enum TimeMode {
     RealTime(f64),    // Multiplier relative to real time
     Virtual(f64),     // Ticks per second for virtual time
@@ -42,16 +55,59 @@ pub enum TimeMode {
 struct TimeState {
     current_hour: i32,
     current_minute: i32,
+    current_day: u32,
     mode: TimeMode,
     start_time: u64,
 }
 
+/// Clock state written by `save_time` and read back by `load_time`
+#[derive(Serialize, Deserialize)]
+struct SavedTime {
+    hour: i32,
+    minute: i32,
+    day: u32,
+    mode: TimeMode,
+}
+
+impl SavedTime {
+    /// Refuses times the clock can't run from, such as a `Virtual(0.0)` mode
+    fn validate(&self) -> Result<(), String> {
+        if !(0..HOURS_PER_DAY).contains(&self.hour) {
+            return Err(format!("hour {} is not between 0 and {}", self.hour, HOURS_PER_DAY - 1));
+        }
+        if !(0..MINUTES_PER_HOUR).contains(&self.minute) {
+            return Err(format!("minute {} is not between 0 and {}", self.minute, MINUTES_PER_HOUR - 1));
+        }
+        match self.mode {
+            TimeMode::RealTime(rate) | TimeMode::Virtual(rate) if !(rate.is_finite() && rate > 0.0) => {
+                Err(format!("time mode {:?} needs a positive rate", self.mode))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// File the time of day is saved to on shutdown and restored from on construction
+///
+/// `None` when running without a server, in which case the time isn't kept between runs.
+pub fn time_file_path() -> Option<PathBuf> {
+    plugin_context().map(|context| context.server.plugin_data_dir().join(TIME_FILE))
+}
+
+/// Writes a failure to the server log
+fn log_error(message: String) {
+    if let Some(context) = plugin_context() {
+        context.server.log_error("CHRONOS", &message);
+    }
+}
+
 impl TimeState {
     fn new() -> Self {
         println!("Creating new TimeState");
         Self {
             current_hour: 6, // Start at 6 AM
             current_minute: 0,
+            current_day: 0,
             mode: TimeMode::Paused,
             start_time: SystemTime::now()
                 .duration_since(UNIX_EPOCH)
@@ -61,15 +117,114 @@ impl TimeState {
     }
 }
 
+/// Phase of the day an hour falls in, as reported by `get_time_of_day`
+fn phase_of(hour: i32) -> &'static str {
+    match hour {
+        5..=8 => "Dawn",
+        9..=16 => "Day",
+        17..=20 => "Dusk",
+        _ => "Night",
+    }
+}
+
+/// Emits `hour_changed` when the hour moves on, and `time_phase_changed`
+/// when it moves into another phase of the day
+fn announce_time_change(from_hour: i32, to_hour: i32, day: u32) {
+    if from_hour == to_hour {
+        return;
+    }
+    global_bus().emit(&GameEvent::custom("hour_changed", serde_json::json!({
+        "from": from_hour,
+        "to": to_hour,
+        "day": day,
+    })));
+
+    let (from_phase, to_phase) = (phase_of(from_hour), phase_of(to_hour));
+    if from_phase != to_phase {
+        global_bus().emit(&GameEvent::custom("time_phase_changed", serde_json::json!({
+            "from": from_phase,
+            "to": to_phase,
+        })));
+    }
+}
+
 pub trait PluginConstruct {
     fn new(plugins: HashMap<String, (Pluginstate, Plugin)>) -> Plugin;    
+
+    /// Plugins that must be constructed before this one. They are guaranteed
+    /// to be present in the map passed to `new`.
+    fn dependencies() -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    /// Version of the plugin API this plugin was built against. The host
+    /// refuses to load plugins from a different major version or a newer
+    /// minor version than its own.
+    fn api_version() -> Version {
+        PLUGIN_API_VERSION
+    }
+
+    /// Called by the server at a fixed rate with the seconds elapsed since the
+    /// previous tick.
+    fn on_game_tick(&self, delta_time: f64) {}
+
+    /// Order of this plugin's `on_game_tick` among the others: lower
+    /// priorities tick first, and equal priorities tick in plugin name order.
+    fn tick_priority() -> i32 {
+        0
+    }
+
+    /// Called for every game event emitted by the server.
+    ///
+    /// Events are delivered on a thread dedicated to this plugin. Handlers
+    /// must not block: one that runs past the server's timeout is reported
+    /// and misses events until it returns.
+    fn on_game_event(&self, event: &GameEvent) {}
+
+    /// Custom event types this plugin handles in `on_game_event`. Other
+    /// custom events are not delivered to it. `None` receives all of them.
+    fn custom_events() -> Option<Vec<&'static str>> {
+        None
+    }
+
+    /// Called once when the server shuts down, so the plugin can save its state.
+    fn on_shutdown(&self) {}
+
+    /// Handles an RPC issued by another plugin through the plugin manager.
+    /// Returns `None` if this plugin has no RPC named `rpc_name`.
+    fn call_rpc(&self, rpc_name: &str, params: &(dyn std::any::Any + Send + Sync)) -> Option<Box<dyn std::any::Any + Send + Sync>> {
+        None
+    }
 }
 
 // Implement constructor for Plugin
 impl PluginConstruct for Plugin {
     fn new(plugins: HashMap<String, (Pluginstate, Plugin)>) -> Plugin {
+        let plugin = Plugin {};
+        if let Some(path) = time_file_path() {
+            if let Err(e) = plugin.load_time(&path) {
+                log_error(format!("Failed to restore the time of day from {}: {}", path.display(), e));
+            }
+        }
         Self::start_time_server();
-        Plugin {}
+        plugin
+    }
+
+    /// Serves `is_daytime`, answered with a `bool`, to plugins that read the
+    /// clock without depending on this crate
+    fn call_rpc(&self, rpc_name: &str, _params: &(dyn std::any::Any + Send + Sync)) -> Option<Box<dyn std::any::Any + Send + Sync>> {
+        match rpc_name {
+            "is_daytime" => Some(Box::new(self.is_daytime())),
+            _ => None,
+        }
+    }
+
+    fn on_shutdown(&self) {
+        if let Some(path) = time_file_path() {
+            if let Err(e) = self.save_time(&path) {
+                log_error(format!("Failed to save the time of day to {}: {}", path.display(), e));
+            }
+        }
     }
 }
 
@@ -81,49 +236,61 @@ pub trait PluginAPI {
     fn get_time_mode(&self) -> TimeMode;
     fn is_daytime(&self) -> bool;
     fn get_time_of_day(&self) -> String;
+    fn request_day(&self) -> u32;
+    fn save_time(&self, path: &Path) -> std::io::Result<()>;
+    fn load_time(&self, path: &Path) -> std::io::Result<()>;
 }
 
 // Implement the PluginAPI trait for Plugin
 impl PluginAPI for Plugin {
     fn start_time_server() {
-        
-        if let Some(time_state) = TIME_STATE.get() {
-            let time_state = time_state.clone();
-            let thread_running = TIME_THREAD_RUNNING.load(Ordering::SeqCst);
-            
-            std::thread::spawn(move || {
-                while thread_running {
-                    let mut state = time_state.write();
-                    match state.mode {
-                        TimeMode::RealTime(multiplier) => {
-                            let elapsed_real_seconds = SystemTime::now()
-                                .duration_since(UNIX_EPOCH)
-                                .unwrap()
-                                .as_secs() - state.start_time;
-                                
-                            let elapsed_game_seconds = (elapsed_real_seconds as f64 * multiplier) as i32;
-                            let total_minutes = elapsed_game_seconds / 60;
+        // Every thread pool constructs the plugin, but only one clock may run
+        if TIME_THREAD_RUNNING.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        let time_state = time_state();
+
+        std::thread::spawn(move || {
+            let mut last_hour = time_state.read().current_hour;
+            while TIME_THREAD_RUNNING.load(Ordering::SeqCst) {
+                let mut state = time_state.write();
+                let pause = match state.mode {
+                    TimeMode::RealTime(multiplier) => {
+                        let elapsed_real_seconds = SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .unwrap()
+                            .as_secs() - state.start_time;
                             
-                            state.current_minute = total_minutes % MINUTES_PER_HOUR;
-                            state.current_hour = (total_minutes / MINUTES_PER_HOUR) % HOURS_PER_DAY;
-                        },
-                        TimeMode::Virtual(ticks_per_second) => {
-                            state.current_minute += 1;
-                            if state.current_minute >= MINUTES_PER_HOUR {
-                                state.current_minute = 0;
-                                state.current_hour = (state.current_hour + 1) % HOURS_PER_DAY;
+                        let elapsed_game_seconds = (elapsed_real_seconds as f64 * multiplier) as i32;
+                        let total_minutes = elapsed_game_seconds / 60;
+                        
+                        state.current_minute = total_minutes % MINUTES_PER_HOUR;
+                        state.current_hour = (total_minutes / MINUTES_PER_HOUR) % HOURS_PER_DAY;
+                        state.current_day = (total_minutes / (MINUTES_PER_HOUR * HOURS_PER_DAY)) as u32;
+                        Duration::from_millis(100)
+                    },
+                    TimeMode::Virtual(ticks_per_second) => {
+                        state.current_minute += 1;
+                        if state.current_minute >= MINUTES_PER_HOUR {
+                            state.current_minute = 0;
+                            state.current_hour = (state.current_hour + 1) % HOURS_PER_DAY;
+                            if state.current_hour == 0 {
+                                state.current_day += 1;
                             }
-                            drop(state); // Release lock before sleeping
-                            std::thread::sleep(Duration::from_secs_f64(1.0 / ticks_per_second));
-                        },
-                        TimeMode::Paused => {
-                            drop(state); // Release lock before sleeping
-                            std::thread::sleep(Duration::from_millis(100));
                         }
-                    }
-                }
-            });
-        }
+                        Duration::from_secs_f64(1.0 / ticks_per_second)
+                    },
+                    TimeMode::Paused => Duration::from_millis(100),
+                };
+                let (hour, day) = (state.current_hour, state.current_day);
+                drop(state); // Release lock before announcing and sleeping
+
+                // Comparing against the last hour seen also catches jumps made with set_time
+                announce_time_change(last_hour, hour, day);
+                last_hour = hour;
+                std::thread::sleep(pause);
+            }
+        });
     }
 
     fn request_time(&self) -> (i32, i32) {
@@ -168,11 +335,220 @@ impl PluginAPI for Plugin {
 
     fn get_time_of_day(&self) -> String {
         let (hour, _) = self.request_time();
-        match hour {
-            5..=8 => "Dawn".to_string(),
-            9..=16 => "Day".to_string(),
-            17..=20 => "Dusk".to_string(),
-            _ => "Night".to_string(),
+        phase_of(hour).to_string()
+    }
+
+    /// Number of whole in-game days that have passed
+    fn request_day(&self) -> u32 {
+        time_state().read().current_day
+    }
+
+    /// Writes the time of day, day count and time mode to `path`
+    fn save_time(&self, path: &Path) -> std::io::Result<()> {
+        let saved = {
+            let binding = time_state();
+            let state = binding.read();
+            SavedTime {
+                hour: state.current_hour,
+                minute: state.current_minute,
+                day: state.current_day,
+                mode: state.mode,
+            }
+        };
+        let contents = serde_json::to_string_pretty(&saved)?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, contents)
+    }
+
+    /// Restores a time saved by `save_time`, keeping the current time if `path` doesn't exist
+    ///
+    /// A saved hour, minute or time mode the clock can't run with is refused
+    /// as `InvalidData`, and the current time is kept.
+    fn load_time(&self, path: &Path) -> std::io::Result<()> {
+        let contents = match std::fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        let saved: SavedTime = serde_json::from_str(&contents)?;
+        saved.validate().map_err(|reason| std::io::Error::new(std::io::ErrorKind::InvalidData, reason))?;
+
+        let binding = time_state();
+        let mut state = binding.write();
+        state.current_hour = saved.hour;
+        state.current_minute = saved.minute;
+        state.current_day = saved.day;
+        state.mode = saved.mode;
+        if let TimeMode::RealTime(multiplier) = saved.mode {
+            // Real-time clocks are derived from start_time, so move it back to
+            // the moment the clock would have read the saved time
+            let game_minutes = (saved.day as i64 * HOURS_PER_DAY as i64 + saved.hour as i64) * MINUTES_PER_HOUR as i64
+                + saved.minute as i64;
+            let real_seconds = (game_minutes * 60) as f64 / multiplier;
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+            state.start_time = now.saturating_sub(real_seconds as u64);
+        }
+        Ok(())
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    /// The clock is process-wide, so tests that drive it take turns
+    static CLOCK: parking_lot::Mutex<()> = parking_lot::Mutex::new(());
+
+    #[test]
+    fn virtual_clock_announces_a_phase_change_once() {
+        let _clock = CLOCK.lock();
+        let dawns = Arc::new(AtomicUsize::new(0));
+        global_bus().register_custom_event("time_phase_changed", {
+            let dawns = Arc::clone(&dawns);
+            move |event| {
+                if let GameEvent::Custom { data, .. } = event {
+                    if data["from"] == "Night" && data["to"] == "Dawn" {
+                        dawns.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+            }
+        });
+
+        let plugin = Plugin {};
+        plugin.set_time_mode(TimeMode::Paused);
+        plugin.set_time(4, 58);
+        Plugin::start_time_server();
+        plugin.set_time_mode(TimeMode::Virtual(2000.0));
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while plugin.request_time().0 < 7 && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        plugin.set_time_mode(TimeMode::Paused);
+
+        assert!(plugin.request_time().0 >= 7, "clock did not advance");
+        assert_eq!(dawns.load(Ordering::SeqCst), 1);
+    }
+
+    /// Server that keeps plugin data in a directory of the test's own
+    struct TestServer {
+        data_dir: PathBuf,
+        logged: parking_lot::Mutex<Vec<String>>,
+    }
+
+    impl TestServer {
+        /// Installs a server keeping plugin data under a directory named after `test`
+        fn install(test: &str) -> Arc<TestServer> {
+            let data_dir = std::env::temp_dir().join(format!("chronos-{}-{}", test, std::process::id()));
+            let server = Arc::new(TestServer { data_dir, logged: parking_lot::Mutex::new(Vec::new()) });
+            horizon_events::set_plugin_context(horizon_events::PluginContext { server: server.clone(), rpc: Arc::new(NoRpc) });
+            server
+        }
+    }
+
+    impl horizon_events::ServerHandle for TestServer {
+        fn broadcast_message(&self, _message: &str) {}
+
+        fn broadcast_event(&self, _name: &str, _payload: serde_json::Value) {}
+
+        fn broadcast_to_region(&self, _pool_group: &str, _center: [f64; 3], _radius: f64, _name: &str, _payload: serde_json::Value) -> usize {
+            0
+        }
+
+        fn plugin_data_dir(&self) -> PathBuf {
+            self.data_dir.clone()
+        }
+
+        fn log_error(&self, category: &str, message: &str) {
+            self.logged.lock().push(format!("{}: {}", category, message));
+        }
+    }
+
+    struct NoRpc;
+
+    impl horizon_events::RpcRouter for NoRpc {
+        fn call_rpc(
+            &self,
+            plugin_name: &str,
+            _rpc_name: &str,
+            _params: &(dyn std::any::Any + Send + Sync),
+        ) -> Result<Box<dyn std::any::Any + Send + Sync>, horizon_events::RpcError> {
+            Err(horizon_events::RpcError::UnknownPlugin(plugin_name.to_string()))
+        }
+    }
+
+    #[test]
+    fn the_time_saved_on_shutdown_is_restored_on_construction() {
+        let _clock = CLOCK.lock();
+        let server = TestServer::install("restore");
+        let data_dir = server.data_dir.clone();
+
+        let plugin = Plugin {};
+        plugin.set_time_mode(TimeMode::Paused);
+        plugin.set_time(21, 37);
+        time_state().write().current_day = 3;
+        plugin.on_shutdown();
+        assert!(data_dir.join(TIME_FILE).exists());
+
+        plugin.set_time(6, 0);
+        time_state().write().current_day = 0;
+        let restored = <Plugin as PluginConstruct>::new(HashMap::new());
+
+        assert_eq!(restored.request_time(), (21, 37));
+        assert_eq!(restored.request_day(), 3);
+        assert!(matches!(restored.get_time_mode(), TimeMode::Paused));
+        assert!(server.logged.lock().is_empty());
+        let _ = std::fs::remove_dir_all(&data_dir);
+    }
+
+    #[test]
+    fn saved_times_the_clock_cannot_run_from_are_refused() {
+        let _clock = CLOCK.lock();
+        let path = std::env::temp_dir().join(format!("chronos-invalid-{}.json", std::process::id()));
+        let plugin = Plugin {};
+        plugin.set_time_mode(TimeMode::Paused);
+        plugin.set_time(9, 15);
+
+        for saved in [
+            serde_json::json!({ "hour": 24, "minute": 0, "day": 1, "mode": "Paused" }),
+            serde_json::json!({ "hour": -1, "minute": 0, "day": 1, "mode": "Paused" }),
+            serde_json::json!({ "hour": 12, "minute": 60, "day": 1, "mode": "Paused" }),
+            serde_json::json!({ "hour": 12, "minute": 0, "day": 1, "mode": { "Virtual": 0.0 } }),
+            serde_json::json!({ "hour": 12, "minute": 0, "day": 1, "mode": { "RealTime": -2.0 } }),
+        ] {
+            std::fs::write(&path, saved.to_string()).unwrap();
+
+            let error = plugin.load_time(&path).unwrap_err();
+
+            assert_eq!(error.kind(), std::io::ErrorKind::InvalidData, "{} was not refused", saved);
+            assert_eq!(plugin.request_time(), (9, 15));
+            assert!(matches!(plugin.get_time_mode(), TimeMode::Paused));
         }
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn a_corrupt_time_file_is_logged_and_the_clock_keeps_its_time() {
+        let _clock = CLOCK.lock();
+        let server = TestServer::install("corrupt");
+        std::fs::create_dir_all(&server.data_dir).unwrap();
+        let saved = serde_json::json!({ "hour": 12, "minute": 0, "day": 4, "mode": { "Virtual": 0.0 } });
+        std::fs::write(server.data_dir.join(TIME_FILE), saved.to_string()).unwrap();
+        let plugin = Plugin {};
+        plugin.set_time_mode(TimeMode::Paused);
+        plugin.set_time(6, 0);
+        time_state().write().current_day = 0;
+
+        let restored = <Plugin as PluginConstruct>::new(HashMap::new());
+
+        assert_eq!(restored.request_time(), (6, 0));
+        assert_eq!(restored.request_day(), 0);
+        assert!(matches!(restored.get_time_mode(), TimeMode::Paused));
+        let logged = server.logged.lock().clone();
+        assert_eq!(logged.len(), 1);
+        assert!(logged[0].contains("Failed to restore the time of day"), "{}", logged[0]);
+        let _ = std::fs::remove_dir_all(&server.data_dir);
     }
-}
\ No newline at end of file
+}
@@ -1,11 +1,21 @@
 use horizon_data_types::Player;
 use socketioxide::extract::SocketRef;
-pub use horizon_plugin_api::{Plugin, Pluginstate, LoadedPlugin};
+pub use horizon_plugin_api::{Plugin, Pluginstate, LoadedPlugin, Version};
+
+/// Plugin API version this plugin targets
+const PLUGIN_API_VERSION: Version = Version {
+    major: 0,
+    minor: 1,
+    hotfix: 0
+};
 use socketioxide::packet::Str;
 use parking_lot::RwLock;
 use std::sync::Arc;
 use std::collections::HashMap;
 use PebbleVault;
+use horizon_events::GameEvent;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
 pub trait PluginAPI {    
     fn player_joined(&self, socket: SocketRef, player: Arc<RwLock<horizon_data_types::Player>>);   
@@ -15,7 +25,51 @@ pub trait PluginConstruct {
     fn get_structs(&self) -> Vec<&str>;
     // If you want default implementations, mark them with 'default'
     fn new(plugins: HashMap<String, (Pluginstate, Plugin)>) -> Plugin;
-    
+
+    /// Plugins that must be constructed before this one. They are guaranteed
+    /// to be present in the map passed to `new`.
+    fn dependencies() -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    /// Version of the plugin API this plugin was built against. The host
+    /// refuses to load plugins from a different major version or a newer
+    /// minor version than its own.
+    fn api_version() -> Version {
+        PLUGIN_API_VERSION
+    }
+
+    /// Called by the server at a fixed rate with the seconds elapsed since the
+    /// previous tick.
+    fn on_game_tick(&self, delta_time: f64) {}
+
+    /// Order of this plugin's `on_game_tick` among the others: lower
+    /// priorities tick first, and equal priorities tick in plugin name order.
+    fn tick_priority() -> i32 {
+        0
+    }
+
+    /// Called for every game event emitted by the server.
+    ///
+    /// Events are delivered on a thread dedicated to this plugin. Handlers
+    /// must not block: one that runs past the server's timeout is reported
+    /// and misses events until it returns.
+    fn on_game_event(&self, event: &GameEvent) {}
+
+    /// Custom event types this plugin handles in `on_game_event`. Other
+    /// custom events are not delivered to it. `None` receives all of them.
+    fn custom_events() -> Option<Vec<&'static str>> {
+        None
+    }
+
+    /// Called once when the server shuts down, so the plugin can save its state.
+    fn on_shutdown(&self) {}
+
+    /// Handles an RPC issued by another plugin through the plugin manager.
+    /// Returns `None` if this plugin has no RPC named `rpc_name`.
+    fn call_rpc(&self, rpc_name: &str, params: &(dyn std::any::Any + Send + Sync)) -> Option<Box<dyn std::any::Any + Send + Sync>> {
+        None
+    }
 }
 
 impl PluginConstruct for Plugin {
@@ -41,9 +95,39 @@ impl PluginAPI for Plugin {
 
 pub type HorizonPlayer = (Character,Player);
 
+/// Safe access to a player's world position
+///
+/// Players that connect before sending a transform have no location yet;
+/// they are treated as standing at the origin instead of panicking.
+pub trait PlayerPosition {
+    fn position(&self) -> horizon_data_types::Vec3D;
+}
+
+impl PlayerPosition for Player {
+    fn position(&self) -> horizon_data_types::Vec3D {
+        location_or_origin(self.transform.as_ref())
+    }
+}
+
+/// Location held by `transform`, or the origin when there is none
+fn location_or_origin(transform: Option<&horizon_data_types::Transform>) -> horizon_data_types::Vec3D {
+    transform
+        .and_then(|transform| transform.location.clone())
+        .unwrap_or(horizon_data_types::Vec3D { x: 0.0, y: 0.0, z: 0.0 })
+}
+
+/// Most health given to characters saved before `max_health` existed
+pub const DEFAULT_MAX_HEALTH: i64 = 100;
+
+fn default_max_health() -> i64 {
+    DEFAULT_MAX_HEALTH
+}
+
 /// The player character struct
+///  - id: The id of the player controlling the character
 ///  - name: The player character's human-readable name
 ///  - health: The player character's health
+///  - max_health: The most health the player character can have
 ///  - position: The player character's world position
 ///  - rotation: The player character's world rotation
 ///  - scale: The player character's world scale
@@ -52,10 +136,18 @@ pub type HorizonPlayer = (Character,Player);
 ///  - model: The player character's model
 ///  - texture: The player character's texture
 ///  - keybones: The player character's keybones
-
+///
+/// Field names are part of the saved and network format, so renaming a
+/// field breaks existing player data. Fields added later need a serde
+/// default so characters saved without them still load.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Character {
+    #[serde(default)]
+    pub id:            Uuid,             // The id of the player controlling the character
     pub name:          String,           // The player character's human-readable name
     pub health:        i64,              // The player character's health
+    #[serde(default = "default_max_health")]
+    pub max_health:    i64,              // The most health the player character can have
     pub position:      (f64, f64, f64),  // The player character's world position
     pub rotation:      (f64, f64, f64),  // The player character's world rotation
     pub scale:         (f64, f64, f64),  // The player character's world scale
@@ -69,8 +161,10 @@ pub struct Character {
 impl Character {
     /// Create a new player character
     pub fn new(
+        id:            Uuid,
         name:          String,
         health:        i64,
+        max_health:    i64,
         position:      (f64, f64, f64),
         rotation:      (f64, f64, f64),
         scale:         (f64, f64, f64),
@@ -81,8 +175,10 @@ impl Character {
         keybones:      Vec<[f64; 9]>,
     ) -> Self {
         Self {
+            id,
             name,
-            health,
+            health:        health.clamp(0, max_health.max(0)),
+            max_health:    max_health.max(0),
             position,
             rotation,
             scale,
@@ -99,9 +195,46 @@ impl Character {
         self.position = new_position;
     }
 
-    /// Update the player character's health
-    pub fn update_health(&mut self, new_health: i64) {
-        self.health = new_health;
+    /// Update the player character's health, clamped to `0..=max_health`
+    ///
+    /// Returns a `PlayerDied` event when this update takes the character
+    /// from alive to zero health. The caller is responsible for emitting it.
+    pub fn update_health(&mut self, new_health: i64) -> Option<GameEvent> {
+        self.set_health(new_health, None)
+    }
+
+    fn set_health(&mut self, new_health: i64, killer: Option<Uuid>) -> Option<GameEvent> {
+        let was_alive = self.health > 0;
+        self.health = new_health.clamp(0, self.max_health);
+
+        (was_alive && self.health == 0).then(|| GameEvent::PlayerDied { player: self.id, killer })
+    }
+
+    /// Restore up to `amount` health, never exceeding `max_health`
+    pub fn heal(&mut self, amount: i64) -> Option<GameEvent> {
+        self.update_health(self.health.saturating_add(amount.max(0)))
+    }
+
+    /// Remove up to `amount` health, never going below zero
+    ///
+    /// Returns a `PlayerDamaged` event for the health actually lost, followed
+    /// by `PlayerDied` if this damage killed the character. The caller is
+    /// responsible for emitting them.
+    pub fn take_damage(&mut self, amount: i64, source: Option<Uuid>) -> Vec<GameEvent> {
+        let before = self.health;
+        let died = self.set_health(self.health.saturating_sub(amount.max(0)), source);
+
+        let mut events = Vec::new();
+        if self.health < before {
+            events.push(GameEvent::PlayerDamaged { player: self.id, source, amount: before - self.health });
+        }
+        events.extend(died);
+        events
+    }
+
+    /// Whether the character has any health left
+    pub fn is_alive(&self) -> bool {
+        self.health > 0
     }
 
     /// Add an animation to the player character
@@ -141,4 +274,57 @@ impl Character {
 }
 
 fn setup_listeners(socket: SocketRef, player: Arc<RwLock<Player>>) {
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn players_without_a_transform_stand_at_the_origin() {
+        let position = location_or_origin(None);
+
+        assert_eq!((position.x, position.y, position.z), (0.0, 0.0, 0.0));
+    }
+
+    fn character() -> Character {
+        Character::new(
+            Uuid::new_v4(),
+            "Ada".to_string(),
+            80,
+            100,
+            (1.0, 2.0, 3.0),
+            (0.0, 90.0, 0.0),
+            (1.0, 1.0, 1.0),
+            (0.0, 0.0, -9.8),
+            vec!["idle".to_string()],
+            "knight".to_string(),
+            "steel".to_string(),
+            vec![[0.5; 9]],
+        )
+    }
+
+    #[test]
+    fn characters_survive_a_serde_round_trip() {
+        let original = character();
+
+        let json = serde_json::to_string(&original).unwrap();
+        let restored: Character = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(serde_json::to_value(&restored).unwrap(), serde_json::to_value(&original).unwrap());
+        assert!(json.contains("\"max_health\":100"));
+    }
+
+    #[test]
+    fn characters_saved_without_id_or_max_health_still_load() {
+        let mut saved = serde_json::to_value(character()).unwrap();
+        let fields = saved.as_object_mut().unwrap();
+        fields.remove("id");
+        fields.remove("max_health");
+
+        let restored: Character = serde_json::from_value(saved).unwrap();
+
+        assert_eq!(restored.id, Uuid::nil());
+        assert_eq!(restored.max_health, DEFAULT_MAX_HEALTH);
+        assert_eq!(restored.health, 80);
+    }
+}
@@ -0,0 +1,411 @@
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use horizon_events::{global_bus, plugin_context, GameEvent};
+use horizon_plugin_api::Pluginstate;
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use uuid::Uuid;
+
+// Import the plugin API publicly to allow the API to make calls against this plugin
+pub use horizon_plugin_api::{Plugin, LoadedPlugin, Version};
+
+/// Plugin API version this plugin targets
+const PLUGIN_API_VERSION: Version = Version {
+    major: 0,
+    minor: 1,
+    hotfix: 0
+};
+
+/// Environment variable holding the seed for weather transitions
+pub const WEATHER_SEED_ENV: &str = "WEATHER_SEED";
+
+lazy_static! {
+    static ref WEATHER_STATE: RwLock<WeatherState> = RwLock::new(WeatherState::new(WeatherConfig::default()));
+}
+
+/// Custom event the server emits when a PebbleVault region is created or loaded
+const REGION_LOADED_EVENT: &str = "region_loaded";
+
+/// Custom event the server emits when a PebbleVault region is unloaded or deleted
+const REGION_UNLOADED_EVENT: &str = "region_unloaded";
+
+/// Weather in a region
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Weather {
+    Clear,
+    Rain,
+    Storm,
+}
+
+impl Weather {
+    const ALL: [Weather; 3] = [Weather::Clear, Weather::Rain, Weather::Storm];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Weather::Clear => "clear",
+            Weather::Rain => "rain",
+            Weather::Storm => "storm",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// How weather changes over time
+#[derive(Clone, Debug)]
+pub struct WeatherConfig {
+    /// Seconds between weather rolls for every region
+    pub roll_interval_secs: f64,
+    /// Chance of each roll moving from one weather (row) to another (column),
+    /// in `Weather` order. Whatever is left of a row keeps the current weather.
+    pub transitions: [[f64; 3]; 3],
+    /// Multiplier on the chance of a storm starting or carrying on at night
+    pub night_storm_bias: f64,
+    /// Seed for the roll sequence, so the same seed gives the same weather
+    pub seed: u64,
+}
+
+impl Default for WeatherConfig {
+    fn default() -> Self {
+        let seed = std::env::var(WEATHER_SEED_ENV)
+            .ok()
+            .and_then(|seed| seed.parse().ok())
+            .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos() as u64);
+        Self {
+            roll_interval_secs: 300.0,
+            transitions: [
+                [0.0, 0.2, 0.05],
+                [0.3, 0.0, 0.15],
+                [0.1, 0.4, 0.0],
+            ],
+            night_storm_bias: 2.0,
+            seed,
+        }
+    }
+}
+
+/// SplitMix64, small and fully determined by its seed
+struct WeatherRng(u64);
+
+impl WeatherRng {
+    /// Next roll in `0.0..1.0`
+    fn next_roll(&mut self) -> f64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        (z >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+struct WeatherState {
+    config: WeatherConfig,
+    /// Weather of every PebbleVault region currently loaded
+    regions: HashMap<Uuid, Weather>,
+    rng: WeatherRng,
+    since_roll: f64,
+}
+
+impl WeatherState {
+    fn new(config: WeatherConfig) -> Self {
+        Self {
+            rng: WeatherRng(config.seed),
+            config,
+            regions: HashMap::new(),
+            since_roll: 0.0,
+        }
+    }
+}
+
+/// Weather a region moves to from `current` for a roll in `0.0..1.0`
+///
+/// Storms are `night_storm_bias` times as likely at night, both to start and
+/// to continue, since the chance of leaving a storm shrinks by the same factor.
+pub fn next_weather(current: Weather, roll: f64, config: &WeatherConfig, night: bool) -> Weather {
+    let mut chances = config.transitions[current.index()];
+    if night {
+        if current == Weather::Storm {
+            for chance in &mut chances {
+                *chance /= config.night_storm_bias;
+            }
+        } else {
+            chances[Weather::Storm.index()] *= config.night_storm_bias;
+        }
+    }
+
+    let mut cumulative = 0.0;
+    for target in Weather::ALL {
+        if target == current {
+            continue;
+        }
+        cumulative += chances[target.index()];
+        if roll < cumulative {
+            return target;
+        }
+    }
+    current
+}
+
+/// Emits a `weather_changed` custom event for a region
+fn announce_weather(region_id: Uuid, from: Weather, to: Weather) {
+    global_bus().emit(&GameEvent::custom("weather_changed", serde_json::json!({
+        "region": region_id.to_string(),
+        "from": from.name(),
+        "to": to.name(),
+    })));
+}
+
+/// Whether Chronos says it is night
+///
+/// Asks Chronos over RPC, so weather keeps working without it: when Chronos
+/// isn't loaded, or there is no server to route the call, it is never night.
+fn is_night() -> bool {
+    plugin_context()
+        .and_then(|context| context.rpc.call_rpc("chronos_plugin", "is_daytime", &()).ok())
+        .and_then(|daytime| daytime.downcast::<bool>().ok())
+        .map_or(false, |daytime| !*daytime)
+}
+
+/// Region named by a `region_loaded` or `region_unloaded` event
+fn event_region(data: &serde_json::Value) -> Option<Uuid> {
+    data["region"].as_str().and_then(|region| Uuid::parse_str(region).ok())
+}
+
+pub trait PluginConstruct {
+    fn new(plugins: HashMap<String, (Pluginstate, Plugin)>) -> Plugin;
+
+    /// Plugins that must be constructed before this one. They are guaranteed
+    /// to be present in the map passed to `new`.
+    fn dependencies() -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    /// Version of the plugin API this plugin was built against. The host
+    /// refuses to load plugins from a different major version or a newer
+    /// minor version than its own.
+    fn api_version() -> Version {
+        PLUGIN_API_VERSION
+    }
+
+    /// Called by the server at a fixed rate with the seconds elapsed since the
+    /// previous tick.
+    fn on_game_tick(&self, delta_time: f64) {}
+
+    /// Order of this plugin's `on_game_tick` among the others: lower
+    /// priorities tick first, and equal priorities tick in plugin name order.
+    fn tick_priority() -> i32 {
+        0
+    }
+
+    /// Called for every game event emitted by the server.
+    ///
+    /// Events are delivered on a thread dedicated to this plugin. Handlers
+    /// must not block: one that runs past the server's timeout is reported
+    /// and misses events until it returns.
+    fn on_game_event(&self, event: &GameEvent) {}
+
+    /// Custom event types this plugin handles in `on_game_event`. Other
+    /// custom events are not delivered to it. `None` receives all of them.
+    fn custom_events() -> Option<Vec<&'static str>> {
+        None
+    }
+
+    /// Called once when the server shuts down, so the plugin can save its state.
+    fn on_shutdown(&self) {}
+
+    /// Handles an RPC issued by another plugin through the plugin manager.
+    /// Returns `None` if this plugin has no RPC named `rpc_name`.
+    fn call_rpc(&self, rpc_name: &str, params: &(dyn std::any::Any + Send + Sync)) -> Option<Box<dyn std::any::Any + Send + Sync>> {
+        None
+    }
+}
+
+impl PluginConstruct for Plugin {
+    fn new(plugins: HashMap<String, (Pluginstate, Plugin)>) -> Plugin {
+        Plugin {}
+    }
+
+    /// Tracks regions as PebbleVault loads them and forgets them once they're unloaded
+    fn on_game_event(&self, event: &GameEvent) {
+        let GameEvent::Custom { event_type, data } = event else {
+            return;
+        };
+        let Some(region_id) = event_region(data) else {
+            return;
+        };
+        let mut state = WEATHER_STATE.write();
+        match event_type.as_str() {
+            REGION_LOADED_EVENT => {
+                state.regions.entry(region_id).or_insert(Weather::Clear);
+            }
+            REGION_UNLOADED_EVENT => {
+                state.regions.remove(&region_id);
+            }
+            _ => {}
+        }
+    }
+
+    fn custom_events() -> Option<Vec<&'static str>> {
+        Some(vec![REGION_LOADED_EVENT, REGION_UNLOADED_EVENT])
+    }
+
+    /// Rolls every loaded region's weather once per `roll_interval_secs`
+    fn on_game_tick(&self, delta_time: f64) {
+        let night = is_night();
+        let mut changes = Vec::new();
+        {
+            let mut state = WEATHER_STATE.write();
+            state.since_roll += delta_time;
+            if state.since_roll < state.config.roll_interval_secs {
+                return;
+            }
+            state.since_roll = 0.0;
+
+            // Roll in a fixed order so a seed always produces the same weather
+            let mut region_ids: Vec<Uuid> = state.regions.keys().copied().collect();
+            region_ids.sort();
+            for region_id in region_ids {
+                let current = state.regions[&region_id];
+                let roll = state.rng.next_roll();
+                let next = next_weather(current, roll, &state.config, night);
+                if next != current {
+                    state.regions.insert(region_id, next);
+                    changes.push((region_id, current, next));
+                }
+            }
+        }
+
+        for (region_id, from, to) in changes {
+            announce_weather(region_id, from, to);
+        }
+    }
+}
+
+pub trait PluginAPI {
+    fn get_weather(&self, region_id: Uuid) -> Weather;
+    fn set_weather(&self, region_id: Uuid, weather: Weather);
+    fn configure_weather(&self, config: WeatherConfig);
+}
+
+impl PluginAPI for Plugin {
+    /// Current weather of a region
+    ///
+    /// Regions start out clear when PebbleVault loads them and change weather
+    /// while they stay loaded. Regions that aren't loaded are always clear.
+    fn get_weather(&self, region_id: Uuid) -> Weather {
+        WEATHER_STATE.read().regions.get(&region_id).copied().unwrap_or(Weather::Clear)
+    }
+
+    /// Forces a loaded region's weather, e.g. for a scripted storm
+    ///
+    /// The weather keeps changing from here on as usual. Regions that aren't
+    /// loaded are left alone.
+    fn set_weather(&self, region_id: Uuid, weather: Weather) {
+        let previous = match WEATHER_STATE.write().regions.get_mut(&region_id) {
+            Some(current) => std::mem::replace(current, weather),
+            None => return,
+        };
+        if previous != weather {
+            announce_weather(region_id, previous, weather);
+        }
+    }
+
+    /// Replaces the weather settings and restarts the roll sequence from `config.seed`
+    ///
+    /// Regions keep their current weather.
+    fn configure_weather(&self, config: WeatherConfig) {
+        let mut state = WEATHER_STATE.write();
+        state.rng = WeatherRng(config.seed);
+        state.config = config;
+        state.since_roll = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(seed: u64) -> WeatherConfig {
+        WeatherConfig {
+            roll_interval_secs: 300.0,
+            transitions: [
+                [0.0, 0.2, 0.05],
+                [0.3, 0.0, 0.15],
+                [0.1, 0.4, 0.0],
+            ],
+            night_storm_bias: 2.0,
+            seed,
+        }
+    }
+
+    /// Weather of a single region over `rolls` rolls from `seed`
+    fn forecast(seed: u64, rolls: usize) -> Vec<Weather> {
+        let config = config(seed);
+        let mut rng = WeatherRng(config.seed);
+        let mut weather = Weather::Clear;
+        (0..rolls)
+            .map(|roll| {
+                weather = next_weather(weather, rng.next_roll(), &config, roll % 2 == 1);
+                weather
+            })
+            .collect()
+    }
+
+    #[test]
+    fn the_same_seed_rolls_the_same_numbers() {
+        let (mut a, mut b) = (WeatherRng(42), WeatherRng(42));
+        let rolls: Vec<f64> = (0..100).map(|_| a.next_roll()).collect();
+        assert_eq!(rolls, (0..100).map(|_| b.next_roll()).collect::<Vec<_>>());
+        assert!(rolls.iter().all(|roll| (0.0..1.0).contains(roll)));
+
+        let mut other = WeatherRng(43);
+        assert_ne!(rolls, (0..100).map(|_| other.next_roll()).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_weather() {
+        let weather = forecast(7, 200);
+        assert_eq!(weather, forecast(7, 200));
+        assert!(weather.iter().any(|weather| *weather != Weather::Clear), "weather never changed");
+    }
+
+    #[test]
+    fn rolls_walk_the_transition_table() {
+        let config = config(0);
+        assert_eq!(next_weather(Weather::Clear, 0.1, &config, false), Weather::Rain);
+        assert_eq!(next_weather(Weather::Clear, 0.22, &config, false), Weather::Storm);
+        assert_eq!(next_weather(Weather::Clear, 0.5, &config, false), Weather::Clear);
+    }
+
+    #[test]
+    fn storms_are_likelier_at_night() {
+        let config = config(0);
+        // A storm takes 0.05 of a clear day's rolls and 0.1 of a clear night's
+        assert_eq!(next_weather(Weather::Clear, 0.28, &config, false), Weather::Clear);
+        assert_eq!(next_weather(Weather::Clear, 0.28, &config, true), Weather::Storm);
+        // Storms clear up half as often at night, so they last longer
+        assert_eq!(next_weather(Weather::Storm, 0.3, &config, false), Weather::Rain);
+        assert_eq!(next_weather(Weather::Storm, 0.3, &config, true), Weather::Storm);
+    }
+
+    #[test]
+    fn only_loaded_regions_have_weather() {
+        let plugin = Plugin {};
+        let region_id = Uuid::new_v4();
+        let region_event = |event_type: &str| GameEvent::custom(event_type, serde_json::json!({ "region": region_id.to_string() }));
+
+        plugin.set_weather(region_id, Weather::Storm);
+        assert_eq!(plugin.get_weather(region_id), Weather::Clear);
+
+        plugin.on_game_event(&region_event(REGION_LOADED_EVENT));
+        plugin.set_weather(region_id, Weather::Storm);
+        assert_eq!(plugin.get_weather(region_id), Weather::Storm);
+
+        plugin.on_game_event(&region_event(REGION_UNLOADED_EVENT));
+        assert_eq!(plugin.get_weather(region_id), Weather::Clear);
+        assert!(!WEATHER_STATE.read().regions.contains_key(&region_id));
+    }
+}
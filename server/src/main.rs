@@ -25,13 +25,14 @@
 static CTRL_C_HANDLER: Once = Once::new();
 use std::sync::Once;
 use once_cell::sync::Lazy;
-use server::{config::server_config, start};
+use server::config::server_config;
 use splash::splash;
 use anyhow::{Context, Result};
-use horizon_logger::{HorizonLogger, log_info, log_debug, log_warn, log_error, log_critical};
+use horizon_logger::HorizonLogger;
 mod server;
 mod splash;
 mod collision;
+mod utilities;
 
 //------------------------------------------------------------------------------
 // Global Logger Configuration
@@ -44,20 +45,62 @@ pub static LOGGER: Lazy<HorizonLogger> = Lazy::new(|| {
     logger
 });
 
+//------------------------------------------------------------------------------
+// Heap Profiling
+//------------------------------------------------------------------------------
+
+// dhat only tracks allocations when built with the `dhat-heap` feature, so
+// normal builds keep the system allocator
+#[cfg(feature = "dhat-heap")]
+#[global_allocator]
+static ALLOC: dhat::Alloc = dhat::Alloc;
+
+/// Profiler started by `--heap-profile`, writing `dhat-heap.json` when dropped
+#[cfg(feature = "dhat-heap")]
+static HEAP_PROFILER: std::sync::Mutex<Option<dhat::Profiler>> = std::sync::Mutex::new(None);
+
+/// Starts heap profiling if the server was built for it and started with `--heap-profile`
+fn start_heap_profiler() {
+    if !std::env::args().any(|arg| arg == "--heap-profile") {
+        return;
+    }
+    #[cfg(feature = "dhat-heap")]
+    {
+        *HEAP_PROFILER.lock().unwrap() = Some(dhat::Profiler::new_heap());
+        log_info!(LOGGER, "PROFILER", "Heap profiling enabled, the profile is written on shutdown");
+    }
+    #[cfg(not(feature = "dhat-heap"))]
+    log_warn!(LOGGER, "PROFILER", "--heap-profile needs a server built with --features dhat-heap");
+}
+
+/// Writes the heap profile, if one is running
+fn stop_heap_profiler() {
+    #[cfg(feature = "dhat-heap")]
+    drop(HEAP_PROFILER.lock().unwrap().take());
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
-    //collision::main();
-
-    splash();
-    let config_init_time = std::time::Instant::now();
-    //let server_config: std::sync::Arc<server::config::ServerConfig> = server_config().context("Failed to obtain server config")?;
-    log_info!(LOGGER, "INIT", "Server config loaded in {:#?}", config_init_time.elapsed());
+    // Everything below may log, including the startup sequencer itself
+    Lazy::force(&LOGGER);
 
-    let init_time = std::time::Instant::now();
+    // Standalone trigger sphere demo, runs instead of the server
+    if std::env::args().any(|arg| arg == "--demo") {
+        collision::demo();
+        return Ok(());
+    }
 
-    // Start the server
-    server::start().await.context("Failed to start server")?;
+    // Replays a recorded event log against a fresh server instead of serving players
+    let replay_log = std::env::args().skip_while(|arg| arg != "--replay").nth(1);
+    #[cfg(not(feature = "replay"))]
+    if replay_log.is_some() {
+        anyhow::bail!("--replay needs a server built with the replay feature");
+    }
+    // A replay is only served to its own clients, on a port of its own
+    let address = if replay_log.is_some() { "127.0.0.1:0" } else { "0.0.0.0:3000" };
 
+    splash();
+    start_heap_profiler();
 
     let mut terminating: bool = false;
     
@@ -67,7 +110,7 @@ async fn main() -> Result<()> {
             if !terminating {
                 terminating = true;
 
-                println!("Exit");
+                shut_down();
                 std::process::exit(0);
                 
             }
@@ -75,5 +118,63 @@ async fn main() -> Result<()> {
 
     ).expect("Failed to handle Ctrl+C");
     });
-    Ok(())
+
+    // Plugins save their state however the server stops, not only on Ctrl+C
+    let served = run(replay_log, address).await;
+    shut_down();
+    served
+}
+
+/// Lets plugins save their state and writes the heap profile
+fn shut_down() {
+    log_info!(LOGGER, "SERVER", "Shutting down");
+    server::shutdown_plugins();
+    stop_heap_profiler();
+}
+
+/// Starts the server and serves players until it stops
+async fn run(replay_log: Option<String>, address: &'static str) -> Result<()> {
+    // Filled in by the bind step and served once startup has finished
+    let listener = std::sync::Arc::new(parking_lot::Mutex::new(None));
+    let bound = listener.clone();
+
+    utilities::startup::Startup::new()
+        .step("Load config", || async { server_config().map(|_| ()) })
+        .step("Prepare replay", {
+            let replaying = replay_log.is_some();
+            move || async move {
+                if replaying {
+                    let data_dir = std::env::temp_dir().join(format!("horizon-replay-{}", std::process::id()));
+                    server::prepare_replay(data_dir.to_string_lossy().into_owned());
+                }
+                Ok(())
+            }
+        })
+        .step("Open PebbleVault", || async { server::open_vault() })
+        .step("Load plugins", server::spawn_thread_pools)
+        .step("Bind socket", move || async move {
+            *bound.lock() = Some(server::bind(address).await?);
+            Ok(())
+        })
+        .step("Start event recording", || async {
+            match server::live_config().event_log_path {
+                Some(path) => utilities::event_log::start_recording(path),
+                None => Ok(()),
+            }
+        })
+        .run()
+        .await
+        .context("Failed to start server")?;
+
+    let listener = listener.lock().take().context("Socket was not bound")?;
+    #[cfg(feature = "replay")]
+    if let Some(path) = replay_log {
+        let url = format!("http://{}", listener.local_addr().context("Socket has no local address")?);
+        tokio::spawn(async move {
+            if let Err(e) = utilities::event_log::replay(&url, path).await {
+                log_error!(LOGGER, "EVENT LOG", "Replay failed: {:#}", e);
+            }
+        });
+    }
+    server::serve(listener).await.context("Server stopped unexpectedly")
 }
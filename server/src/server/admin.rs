@@ -0,0 +1,389 @@
+//-----------------------------------------------------------------------------
+// Admin Command Channel
+//   - `/admin` Socket.IO namespace for runtime operator commands
+//   - Every command requires the `admin_token` from the server config
+//   - Connections are rate limited per address, and addresses that keep
+//     presenting wrong tokens are locked out for a while
+//   - Commands live in a registry so more can be added without touching
+//     the connection handling
+//-----------------------------------------------------------------------------
+
+use super::live_config;
+use crate::utilities::logging::{LogLevel, Subsystem};
+use crate::LOGGER;
+use crate::{log_info, log_warn};
+use lazy_static::lazy_static;
+use parking_lot::{Mutex, RwLock};
+use socketioxide::extract::{Data, SocketRef};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Namespace operators connect to
+pub const ADMIN_NAMESPACE: &str = "/admin";
+
+/// Wrong tokens an address may present before it is locked out
+const MAX_FAILED_ATTEMPTS: u32 = 5;
+
+/// How long an address stays locked out after too many wrong tokens
+const LOCKOUT: Duration = Duration::from_secs(15 * 60);
+
+/// Runs an admin command with its arguments, returning the result sent back to the operator
+pub type AdminCommand = Arc<dyn Fn(&serde_json::Value) -> Result<serde_json::Value, String> + Send + Sync>;
+
+lazy_static! {
+    static ref COMMANDS: RwLock<HashMap<String, AdminCommand>> = RwLock::new(builtin_commands());
+    static ref FAILED_ATTEMPTS: Mutex<FailedAttempts> = Mutex::new(FailedAttempts::new());
+}
+
+/// Wrong admin tokens presented per address
+struct FailedAttempts {
+    /// Number of wrong tokens and when the last one was presented
+    by_address: HashMap<IpAddr, (u32, Instant)>,
+}
+
+impl FailedAttempts {
+    fn new() -> Self {
+        Self { by_address: HashMap::new() }
+    }
+
+    /// Whether `address` presented `max` wrong tokens, the last one less than `lockout` ago
+    fn locked_out(&self, address: IpAddr, max: u32, lockout: Duration) -> bool {
+        self.by_address
+            .get(&address)
+            .is_some_and(|(count, last)| *count >= max && last.elapsed() < lockout)
+    }
+
+    /// Counts a wrong token from `address`
+    ///
+    /// Attempts older than `lockout` are forgotten first, so a lockout ends
+    /// with a clean slate.
+    fn record_failure(&mut self, address: IpAddr, lockout: Duration) {
+        self.by_address.retain(|_, (_, last)| last.elapsed() < lockout);
+        let (count, last) = self.by_address.entry(address).or_insert((0, Instant::now()));
+        *count += 1;
+        *last = Instant::now();
+    }
+
+    fn clear(&mut self, address: IpAddr) {
+        self.by_address.remove(&address);
+    }
+}
+
+/// Why an admin command was not run
+#[derive(Debug, Clone, PartialEq)]
+pub enum AdminError {
+    /// No admin token is configured, or the one presented does not match
+    Unauthorized,
+    /// No command is registered under this name
+    UnknownCommand(String),
+    /// The command ran and reported an error
+    Failed(String),
+}
+
+impl fmt::Display for AdminError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdminError::Unauthorized => write!(f, "Unauthorized"),
+            AdminError::UnknownCommand(name) => write!(f, "Unknown command {}", name),
+            AdminError::Failed(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl std::error::Error for AdminError {}
+
+/// Adds a command to the registry, replacing any command of the same name
+pub fn register_command(
+    name: impl Into<String>,
+    command: impl Fn(&serde_json::Value) -> Result<serde_json::Value, String> + Send + Sync + 'static,
+) {
+    COMMANDS.write().insert(name.into(), Arc::new(command));
+}
+
+/// Names of every registered command
+pub fn command_names() -> Vec<String> {
+    let mut names: Vec<String> = COMMANDS.read().keys().cloned().collect();
+    names.sort();
+    names
+}
+
+/// Checks `token` against the configured admin token, `expected`
+///
+/// Without an `admin_token` in the config every command is refused.
+fn check_token(expected: Option<&str>, token: Option<&str>) -> Result<(), AdminError> {
+    match (expected, token) {
+        (Some(expected), Some(token)) if constant_time_eq(expected.as_bytes(), token.as_bytes()) => Ok(()),
+        _ => Err(AdminError::Unauthorized),
+    }
+}
+
+/// Runs a registered command
+pub fn dispatch(name: &str, args: &serde_json::Value) -> Result<serde_json::Value, AdminError> {
+    // Clone the handler so commands may register others without deadlocking
+    let command = COMMANDS
+        .read()
+        .get(name)
+        .cloned()
+        .ok_or_else(|| AdminError::UnknownCommand(name.to_string()))?;
+    command(args).map_err(AdminError::Failed)
+}
+
+/// Compares without stopping at the first difference, so timing reveals nothing about the token
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Checks `token` against `expected` for a connection from `address`,
+/// locking the address out after `MAX_FAILED_ATTEMPTS` wrong tokens
+///
+/// A locked out address is refused without its token being looked at.
+fn authorize_from(expected: Option<&str>, address: Option<IpAddr>, token: Option<&str>) -> Result<(), AdminError> {
+    let Some(address) = address else {
+        return check_token(expected, token);
+    };
+    let mut failed = FAILED_ATTEMPTS.lock();
+    if failed.locked_out(address, MAX_FAILED_ATTEMPTS, LOCKOUT) {
+        return Err(AdminError::Unauthorized);
+    }
+    match check_token(expected, token) {
+        Ok(()) => {
+            failed.clear(address);
+            Ok(())
+        }
+        Err(e) => {
+            failed.record_failure(address, LOCKOUT);
+            if failed.locked_out(address, MAX_FAILED_ATTEMPTS, LOCKOUT) {
+                log_warn!(LOGGER, "ADMIN", "Locking out {} after {} wrong admin tokens", address, MAX_FAILED_ATTEMPTS);
+            }
+            Err(e)
+        }
+    }
+}
+
+/// Handles a connection to the admin namespace
+///
+/// Commands arrive as `command` events of the form
+/// `{ "command": "kick", "args": { ... }, "token": "..." }`. The token may be
+/// sent once in the connect payload instead of with every command. Results
+/// are sent back as `admin_result`, failures as `admin_error`. Connections
+/// count against the same per-address limit as player connections.
+pub fn on_connect(socket: SocketRef, Data(data): Data<serde_json::Value>) {
+    log_info!(LOGGER, "ADMIN", "Admin connection from {}", socket.id);
+    let address = super::peer_address(&socket);
+    if let Some(address) = address {
+        let config = live_config();
+        if !super::CONNECTION_LIMITER.lock().try_acquire(address, config.ip_connection_rate_per_sec, config.ip_connection_burst) {
+            log_warn!(LOGGER, "ADMIN", "Rate limiting admin connections from {}", address);
+            let _ = socket.disconnect();
+            return;
+        }
+    }
+    let connect_token = super::auth::token_from(&data).map(str::to_string);
+
+    socket.on("command", move |socket: SocketRef, Data(payload): Data<serde_json::Value>| {
+        let name = payload.get("command").and_then(serde_json::Value::as_str).unwrap_or_default().to_string();
+        let token = super::auth::token_from(&payload).or(connect_token.as_deref());
+        let args = payload.get("args").cloned().unwrap_or(serde_json::Value::Null);
+
+        let outcome = run_command(live_config().admin_token.as_deref(), address, token, &name, &args);
+        match outcome {
+            Ok(result) => {
+                log_info!(LOGGER, "ADMIN", "{} ran {}", socket.id, name);
+                let _ = socket.emit("admin_result", &serde_json::json!({ "command": name, "result": result }));
+            }
+            Err(e) => {
+                log_warn!(LOGGER, "ADMIN", "Refused {} from {}: {}", name, socket.id, e);
+                let _ = socket.emit("admin_error", &serde_json::json!({ "command": name, "error": e.to_string() }));
+            }
+        }
+    });
+}
+
+/// Runs a command sent from `address` if its token matches `expected`
+fn run_command(
+    expected: Option<&str>,
+    address: Option<IpAddr>,
+    token: Option<&str>,
+    name: &str,
+    args: &serde_json::Value,
+) -> Result<serde_json::Value, AdminError> {
+    authorize_from(expected, address, token).and_then(|()| dispatch(name, args))
+}
+
+fn string_arg<'a>(args: &'a serde_json::Value, name: &str) -> Result<&'a str, String> {
+    args.get(name)
+        .and_then(serde_json::Value::as_str)
+        .ok_or_else(|| format!("Missing string argument {}", name))
+}
+
+/// Commands every server starts with
+fn builtin_commands() -> HashMap<String, AdminCommand> {
+    let mut commands: HashMap<String, AdminCommand> = HashMap::new();
+
+    commands.insert("kick".to_string(), Arc::new(|args| {
+        let player_id = Uuid::parse_str(string_arg(args, "player_id")?).map_err(|e| format!("Invalid player_id: {}", e))?;
+        let reason = args.get("reason").and_then(serde_json::Value::as_str).unwrap_or("Kicked by an operator");
+        if !super::kick_player(player_id, reason) {
+            return Err(format!("Player {} is not connected", player_id));
+        }
+        Ok(serde_json::json!({ "kicked": player_id }))
+    }));
+
+    commands.insert("broadcast".to_string(), Arc::new(|args| {
+        let message = string_arg(args, "message")?;
+        // An optional pool_group limits the broadcast to one game mode
+        let recipients = match args.get("pool_group").and_then(serde_json::Value::as_str) {
+            Some(pool_group) => Some(super::group_players(pool_group)),
+            None => None,
+        };
+        let delivered = super::emit_to_players(recipients.as_ref(), "server_message", &serde_json::Value::String(message.to_string()));
+        Ok(serde_json::json!({ "delivered": delivered }))
+    }));
+
+    commands.insert("list_players".to_string(), Arc::new(|_args| {
+        let players: Vec<String> = super::connected_players().iter().map(Uuid::to_string).collect();
+        let per_pool = super::players_per_pool();
+        Ok(serde_json::json!({ "players": players, "total": per_pool.iter().sum::<usize>(), "per_pool": per_pool }))
+    }));
+
+    commands.insert("overlapping".to_string(), Arc::new(|args| {
+        let player_id = Uuid::parse_str(string_arg(args, "player_id")?).map_err(|e| format!("Invalid player_id: {}", e))?;
+        let pool_group = super::player_group(player_id).ok_or_else(|| format!("Player {} is not in the world", player_id))?;
+        let actors = super::player_actors(&pool_group);
+        let player = actors
+            .iter()
+            .find(|actor| actor.uuid == player_id)
+            .ok_or_else(|| format!("Player {} is not in the world", player_id))?;
+        let overlapping: Vec<String> = super::event_rep::get_overlapping_collisions(player, &actors)
+            .iter()
+            .map(Uuid::to_string)
+            .collect();
+        Ok(serde_json::json!({ "player_id": player_id, "overlapping": overlapping }))
+    }));
+
+    // Applies to every message the server logs, optionally for one category only
+    commands.insert("set_log_level".to_string(), Arc::new(|args| {
+        let level: LogLevel = string_arg(args, "level")?.parse()?;
+        match args.get("subsystem").and_then(serde_json::Value::as_str) {
+            Some(subsystem) => Subsystem::set_level(subsystem, level),
+            None => Subsystem::set_default_level(level),
+        }
+        Ok(serde_json::json!({ "level": format!("{:?}", level) }))
+    }));
+
+    // Plugins are compiled into the server, so there is no new build of one to load
+    commands.insert("reload_plugin".to_string(), Arc::new(|args| {
+        let plugin = string_arg(args, "plugin")?;
+        Err(format!("Reloading {} is not supported: plugins are compiled into the server, restart it to load a new version", plugin))
+    }));
+
+    commands.insert("drain_thread".to_string(), Arc::new(|args| {
+        let index = args
+            .get("index")
+            .and_then(serde_json::Value::as_u64)
+            .ok_or_else(|| "Missing integer argument index".to_string())? as usize;
+        // Draining waits on every pool involved, so it finishes in the background
+        tokio::spawn(async move {
+            match super::drain_thread(index).await {
+                Ok(moved) => log_info!(LOGGER, "ADMIN", "Drained thread {}, moved {} players", index, moved),
+                Err(e) => log_warn!(LOGGER, "ADMIN", "Failed to drain thread {}: {:#}", index, e),
+            }
+        });
+        Ok(serde_json::json!({ "draining": index }))
+    }));
+
+    commands
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const OPERATOR: IpAddr = IpAddr::V4(Ipv4Addr::new(198, 51, 100, 1));
+    const OTHER: IpAddr = IpAddr::V4(Ipv4Addr::new(198, 51, 100, 2));
+
+    #[test]
+    fn repeated_wrong_tokens_lock_the_address_out() {
+        let mut failed = FailedAttempts::new();
+        for _ in 0..MAX_FAILED_ATTEMPTS - 1 {
+            failed.record_failure(OPERATOR, LOCKOUT);
+        }
+        assert!(!failed.locked_out(OPERATOR, MAX_FAILED_ATTEMPTS, LOCKOUT));
+
+        failed.record_failure(OPERATOR, LOCKOUT);
+        assert!(failed.locked_out(OPERATOR, MAX_FAILED_ATTEMPTS, LOCKOUT));
+        assert!(!failed.locked_out(OTHER, MAX_FAILED_ATTEMPTS, LOCKOUT));
+    }
+
+    #[test]
+    fn a_lockout_ends_with_a_clean_slate() {
+        let lockout = Duration::from_millis(10);
+        let mut failed = FailedAttempts::new();
+        failed.record_failure(OPERATOR, lockout);
+        failed.record_failure(OPERATOR, lockout);
+        assert!(failed.locked_out(OPERATOR, 2, lockout));
+
+        std::thread::sleep(lockout * 2);
+
+        assert!(!failed.locked_out(OPERATOR, 2, lockout));
+        failed.record_failure(OPERATOR, lockout);
+        assert!(!failed.locked_out(OPERATOR, 2, lockout));
+    }
+
+    #[test]
+    fn tokens_only_match_exactly() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret2"));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn an_authorized_kick_disconnects_the_player() {
+        let url = crate::server::test_support::serve_players().await;
+        let player = crate::server::test_support::TestPlayer::connect(&url, "default").await;
+
+        let args = serde_json::json!({ "player_id": player.id.to_string(), "reason": "testing" });
+        let result = run_command(Some("secret"), Some(OPERATOR), Some("secret"), "kick", &args).unwrap();
+
+        assert_eq!(result, serde_json::json!({ "kicked": player.id }));
+        assert_eq!(player.wait_for("kicked").await, vec![serde_json::json!({ "reason": "testing" })]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn unauthorized_commands_are_not_run() {
+        let url = crate::server::test_support::serve_players().await;
+        let player = crate::server::test_support::TestPlayer::connect(&url, "default").await;
+        let args = serde_json::json!({ "player_id": player.id.to_string() });
+
+        assert_eq!(run_command(Some("secret"), Some(OTHER), Some("wrong"), "kick", &args), Err(AdminError::Unauthorized));
+        assert_eq!(run_command(Some("secret"), Some(OTHER), None, "kick", &args), Err(AdminError::Unauthorized));
+        // Without a configured token nothing is accepted, not even an empty one
+        assert_eq!(run_command(None, Some(OTHER), Some(""), "kick", &args), Err(AdminError::Unauthorized));
+        FAILED_ATTEMPTS.lock().clear(OTHER);
+
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(player.received("kicked").is_empty());
+        assert!(crate::server::connected_players().contains(&player.id));
+    }
+
+    #[test]
+    fn reloading_a_plugin_is_refused_as_unsupported() {
+        match dispatch("reload_plugin", &serde_json::json!({ "plugin": "chronos_plugin" })) {
+            Err(AdminError::Failed(reason)) => assert!(reason.contains("not supported"), "{}", reason),
+            other => panic!("reload_plugin was not refused: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn set_log_level_changes_the_level_messages_are_checked_against() {
+        dispatch("set_log_level", &serde_json::json!({ "level": "error", "subsystem": "ADMIN TEST" })).unwrap();
+
+        assert!(!Subsystem::enabled("ADMIN TEST", LogLevel::Warn));
+        assert!(Subsystem::enabled("ADMIN TEST", LogLevel::Error));
+    }
+}
@@ -0,0 +1,158 @@
+//-----------------------------------------------------------------------------
+// Connection Authentication
+//   - Pluggable check of the token a client presents when connecting
+//   - Players are only created once their token has been accepted
+//-----------------------------------------------------------------------------
+
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// How long a client has to send its `auth` message after connecting
+pub const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+lazy_static! {
+    static ref AUTHENTICATOR: RwLock<Arc<dyn Authenticator>> = RwLock::new(Arc::new(NoAuthentication));
+}
+
+/// Who a verified token belongs to
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlayerIdentity {
+    /// Persistent id of the player, or None to give the connection a fresh id
+    pub player_id: Option<Uuid>,
+    /// Display name attached to the token, if any; never used to pick the player id
+    pub name: Option<String>,
+}
+
+impl PlayerIdentity {
+    /// Player id this identity always maps to, if it identifies an account
+    ///
+    /// Only the id the authenticator verified counts. Names are not turned into
+    /// ids, since a name is easy to claim and would hand its account, and the
+    /// data saved for it, to whoever sent it. Anonymous identities have no
+    /// stable id.
+    pub fn stable_player_id(&self) -> Option<Uuid> {
+        self.player_id
+    }
+}
+
+/// Why a connection was not authenticated
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuthError {
+    /// The client did not send a token in time
+    MissingToken,
+    /// The token was not accepted, with a reason safe to show the client
+    Rejected(String),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AuthError::MissingToken => write!(f, "No authentication token was provided"),
+            AuthError::Rejected(reason) => write!(f, "Authentication failed: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+/// Verifies the tokens clients present when connecting
+pub trait Authenticator: Send + Sync {
+    fn verify(&self, token: &str) -> Result<PlayerIdentity, AuthError>;
+
+    /// Whether clients must present a token at all
+    ///
+    /// When false, clients that connect without a token are admitted as
+    /// anonymous players instead of waiting for an `auth` message.
+    fn requires_token(&self) -> bool {
+        true
+    }
+}
+
+/// Default authenticator that admits everyone as an anonymous player
+pub struct NoAuthentication;
+
+impl Authenticator for NoAuthentication {
+    fn verify(&self, _token: &str) -> Result<PlayerIdentity, AuthError> {
+        Ok(PlayerIdentity::default())
+    }
+
+    fn requires_token(&self) -> bool {
+        false
+    }
+}
+
+/// Replaces the authenticator used for new connections
+pub fn set_authenticator(authenticator: impl Authenticator + 'static) {
+    *AUTHENTICATOR.write() = Arc::new(authenticator);
+}
+
+/// Authenticator currently in effect
+pub fn authenticator() -> Arc<dyn Authenticator> {
+    Arc::clone(&AUTHENTICATOR.read())
+}
+
+/// Token carried by an auth payload of the form `{ "token": "..." }`
+pub fn token_from(payload: &serde_json::Value) -> Option<&str> {
+    payload.get("token").and_then(serde_json::Value::as_str)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Accepts one fixed token for one fixed player
+    struct SingleToken(Uuid);
+
+    impl Authenticator for SingleToken {
+        fn verify(&self, token: &str) -> Result<PlayerIdentity, AuthError> {
+            if token == "letmein" {
+                Ok(PlayerIdentity { player_id: Some(self.0), name: None })
+            } else {
+                Err(AuthError::Rejected("unknown token".to_string()))
+            }
+        }
+    }
+
+    #[test]
+    fn a_valid_token_is_accepted() {
+        let player_id = Uuid::new_v4();
+        let payload = serde_json::json!({ "token": "letmein" });
+
+        let identity = SingleToken(player_id).verify(token_from(&payload).unwrap()).unwrap();
+
+        assert_eq!(identity.player_id, Some(player_id));
+    }
+
+    #[test]
+    fn an_invalid_token_is_rejected() {
+        let payload = serde_json::json!({ "token": "guess" });
+
+        let error = SingleToken(Uuid::new_v4()).verify(token_from(&payload).unwrap()).unwrap_err();
+
+        assert_eq!(error, AuthError::Rejected("unknown token".to_string()));
+        assert_eq!(error.to_string(), "Authentication failed: unknown token");
+        assert!(SingleToken(Uuid::new_v4()).requires_token());
+    }
+
+    #[test]
+    fn only_a_verified_id_is_stable() {
+        let player_id = Uuid::new_v4();
+        let verified = PlayerIdentity { player_id: Some(player_id), name: Some("alice".to_string()) };
+        let named = PlayerIdentity { player_id: None, name: Some("alice".to_string()) };
+
+        assert_eq!(verified.stable_player_id(), Some(player_id));
+        assert_eq!(named.stable_player_id(), None);
+        assert_eq!(PlayerIdentity::default().stable_player_id(), None);
+    }
+
+    #[test]
+    fn the_default_admits_anonymous_players_without_a_token() {
+        assert!(!NoAuthentication.requires_token());
+        assert_eq!(NoAuthentication.verify("anything"), Ok(PlayerIdentity::default()));
+        assert_eq!(token_from(&serde_json::json!({})), None);
+    }
+}
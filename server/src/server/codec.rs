@@ -0,0 +1,139 @@
+//-----------------------------------------------------------------------------
+// Binary Player State Codec
+//   - Compact fixed-width layout for high-frequency position updates
+//   - 1-byte version header followed by little-endian f32 fields
+//   - Used by clients that negotiate binary instead of JSON payloads
+//-----------------------------------------------------------------------------
+
+use std::fmt;
+
+/// Version written in the first byte of every encoded state
+pub const STATE_CODEC_VERSION: u8 = 1;
+
+/// Size in bytes of an encoded state: version header plus 7 f32 fields
+pub const ENCODED_STATE_LEN: usize = 1 + 7 * 4;
+
+/// Position and rotation of a player as carried on the binary channel
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlayerState {
+    /// World position [x, y, z]
+    pub position: [f32; 3],
+    /// Rotation quaternion [x, y, z, w]
+    pub rotation: [f32; 4],
+}
+
+/// Errors that can occur while decoding a binary player state
+#[derive(Debug, Clone, PartialEq)]
+pub enum CodecError {
+    /// The buffer ended before all fields were read
+    Truncated { expected: usize, actual: usize },
+    /// The version header is not one this server understands
+    UnsupportedVersion(u8),
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::Truncated { expected, actual } => {
+                write!(f, "Player state truncated: expected {} bytes, got {}", expected, actual)
+            }
+            CodecError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported player state version {}", version)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+/// Encodes a player state as `[version, x, y, z, rx, ry, rz, rw]`
+///
+/// Every field after the version byte is a little-endian f32, so the
+/// layout is the same regardless of the host's byte order.
+pub fn encode_player_state(state: &PlayerState) -> Vec<u8> {
+    let mut buffer = Vec::with_capacity(ENCODED_STATE_LEN);
+    buffer.push(STATE_CODEC_VERSION);
+    for value in state.position.iter().chain(state.rotation.iter()) {
+        buffer.extend_from_slice(&value.to_le_bytes());
+    }
+    buffer
+}
+
+/// Decodes a buffer produced by `encode_player_state`
+///
+/// Trailing bytes beyond the fixed layout are ignored so later versions can
+/// append fields without breaking older decoders.
+pub fn decode_player_state(buffer: &[u8]) -> Result<PlayerState, CodecError> {
+    let Some((&version, fields)) = buffer.split_first() else {
+        return Err(CodecError::Truncated { expected: ENCODED_STATE_LEN, actual: 0 });
+    };
+    if version != STATE_CODEC_VERSION {
+        return Err(CodecError::UnsupportedVersion(version));
+    }
+    if buffer.len() < ENCODED_STATE_LEN {
+        return Err(CodecError::Truncated { expected: ENCODED_STATE_LEN, actual: buffer.len() });
+    }
+
+    let mut values = fields
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]));
+    let mut next = || values.next().unwrap_or_default();
+
+    Ok(PlayerState {
+        position: [next(), next(), next()],
+        rotation: [next(), next(), next(), next()],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> PlayerState {
+        PlayerState {
+            position: [1.5, -20.25, 300.0],
+            rotation: [0.0, 0.70710677, 0.0, 0.70710677],
+        }
+    }
+
+    #[test]
+    fn encoded_state_round_trips() {
+        let encoded = encode_player_state(&sample());
+        assert_eq!(encoded.len(), ENCODED_STATE_LEN);
+        assert_eq!(decode_player_state(&encoded), Ok(sample()));
+    }
+
+    #[test]
+    fn fields_are_little_endian_after_the_version() {
+        let encoded = encode_player_state(&sample());
+        assert_eq!(encoded[0], STATE_CODEC_VERSION);
+        assert_eq!(&encoded[1..5], &1.5f32.to_le_bytes());
+    }
+
+    #[test]
+    fn trailing_bytes_are_ignored() {
+        let mut encoded = encode_player_state(&sample());
+        encoded.extend_from_slice(&[0xff; 8]);
+        assert_eq!(decode_player_state(&encoded), Ok(sample()));
+    }
+
+    #[test]
+    fn truncated_buffers_are_rejected() {
+        let encoded = encode_player_state(&sample());
+        assert_eq!(
+            decode_player_state(&encoded[..ENCODED_STATE_LEN - 1]),
+            Err(CodecError::Truncated { expected: ENCODED_STATE_LEN, actual: ENCODED_STATE_LEN - 1 })
+        );
+        assert_eq!(
+            decode_player_state(&[]),
+            Err(CodecError::Truncated { expected: ENCODED_STATE_LEN, actual: 0 })
+        );
+    }
+
+    #[test]
+    fn unknown_versions_are_rejected() {
+        let mut encoded = encode_player_state(&sample());
+        encoded[0] = STATE_CODEC_VERSION + 1;
+        assert_eq!(decode_player_state(&encoded), Err(CodecError::UnsupportedVersion(STATE_CODEC_VERSION + 1)));
+    }
+}
@@ -1,46 +1,605 @@
 use anyhow::{Context, Result};
 use env_logger::Logger;
-use horizon_logger::log_warn;
+use crate::{log_error, log_info, log_warn};
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 use std::{
     fs,
+    path::{Path, PathBuf},
     sync::{Arc, OnceLock},
+    time::{Duration, SystemTime},
 };
 
 use crate::LOGGER;
+use uuid::Uuid;
 
 lazy_static! {
-    pub static ref SERVER_CONFIG: OnceLock<Arc<ServerConfig>> = OnceLock::new();
+    /// Config as first loaded from disk
+    ///
+    /// Hot reloads do not update this, so the running server reads its config
+    /// through `live_config()` instead.
+    static ref SERVER_CONFIG: OnceLock<Arc<ServerConfig>> = OnceLock::new();
+}
+
+/// How often the hot-reload watcher checks the config file for changes
+const CONFIG_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Path of the config file, overridable with `SERVER_CONFIG_PATH`
+pub fn config_path() -> PathBuf {
+    std::env::var("SERVER_CONFIG_PATH")
+        .unwrap_or_else(|_| "./server_config.json".to_string())
+        .into()
 }
 
 pub fn server_config() -> Result<Arc<ServerConfig>> {
-    let config_path = std::env::var("SERVER_CONFIG_PATH").unwrap_or_else(|_| "./server_config.json".to_string());
+    let config_path = config_path();
     let config = match fs::read_to_string(&config_path) {
-        Ok(config_str) => serde_json::from_str(&config_str).with_context(|| format!("Failed to parse {}", config_path))?,
+        Ok(config_str) => serde_json::from_str(&config_str).with_context(|| format!("Failed to parse {}", config_path.display()))?,
         Err(e) => {
-            log_warn!(LOGGER, "SERVER", "Failed to read {}: {}", config_path, e);
+            log_warn!(LOGGER, "SERVER", "Failed to read {}: {}", config_path.display(), e);
             ServerConfig::new()
         }
     };
+    config.validate()?;
     Ok(SERVER_CONFIG.get_or_init(|| Arc::new(config)).clone())
 }
 
-#[derive(Debug, Serialize, Deserialize, Copy, Clone)]
+/// Reads, parses and validates a config file
+fn load_from(path: &Path) -> Result<ServerConfig> {
+    let config_str = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let config: ServerConfig = serde_json::from_str(&config_str).with_context(|| format!("Failed to parse {}", path.display()))?;
+    config.validate()?;
+    Ok(config)
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ServerConfig {
     pub players_per_pool: u32,
+    #[serde(default = "default_thread_pools")]
     pub num_thread_pools: u32,
+    /// Name shown on the built-in status page
+    #[serde(default = "default_server_name")]
+    pub server_name: String,
+    /// When set, browser visitors to `/` are redirected here instead of the status page
+    #[serde(default)]
+    pub status_redirect: Option<String>,
+    /// Fastest a player may legitimately move, in world units per second
+    #[serde(default = "default_max_player_speed")]
+    pub max_player_speed: f64,
+    /// Extra distance tolerated per move to absorb network jitter
+    #[serde(default = "default_movement_tolerance")]
+    pub movement_tolerance: f64,
+    /// Where players without a saved position enter the world
+    #[serde(default)]
+    pub spawn_position: [f64; 3],
+    /// When false, new connections are refused while existing players stay connected
+    #[serde(default = "default_accept_connections")]
+    pub accept_connections: bool,
+    /// What to do with new connections when every thread pool is full
+    #[serde(default)]
+    pub full_server_policy: FullServerPolicy,
+    /// Maximum number of connections waiting for a slot under the queue policy
+    #[serde(default = "default_connection_queue_length")]
+    pub connection_queue_length: usize,
+    /// Seconds a queued connection waits for a slot before being refused
+    #[serde(default = "default_connection_queue_timeout_secs")]
+    pub connection_queue_timeout_secs: u64,
+    /// Seconds a dropped player's state is held for them to reconnect
+    #[serde(default = "default_reconnect_grace_secs")]
+    pub reconnect_grace_secs: u64,
+    /// Seconds between application-level pings sent to each player
+    #[serde(default = "default_heartbeat_interval_secs")]
+    pub heartbeat_interval_secs: u64,
+    /// Seconds without any inbound event before a player is evicted
+    #[serde(default = "default_heartbeat_timeout_secs")]
+    pub heartbeat_timeout_secs: u64,
+    /// Number of state updates sent to a player between full keyframes
+    #[serde(default = "default_state_keyframe_interval")]
+    pub state_keyframe_interval: u32,
+    /// Distance within which players receive each other's updates
+    #[serde(default = "default_view_radius")]
+    pub view_radius: f64,
+    /// Chat messages each player may send per second once its burst is spent
+    #[serde(default = "default_chat_rate_per_sec")]
+    pub chat_rate_per_sec: f64,
+    /// Chat messages a player may send back to back
+    #[serde(default = "default_chat_burst")]
+    pub chat_burst: f64,
+    /// Longest chat message accepted, in characters
+    #[serde(default = "default_chat_max_length")]
+    pub chat_max_length: usize,
+    /// Distance local chat messages travel from the sender
+    #[serde(default = "default_chat_local_radius")]
+    pub chat_local_radius: f64,
+    /// Game ticks per second delivered to plugins through `on_game_tick`
+    #[serde(default = "default_tick_rate_hz")]
+    pub tick_rate_hz: u32,
+    /// Milliseconds of movement history kept per player for lag compensation
+    #[serde(default = "default_position_history_ms")]
+    pub position_history_ms: u64,
+    /// Furthest back in time, in milliseconds, a shot may be rewound for hit validation
+    #[serde(default = "default_max_rewind_ms")]
+    pub max_rewind_ms: u64,
+    /// Health a confirmed hit takes from its target
+    #[serde(default = "default_shot_damage")]
+    pub shot_damage: i32,
+    /// Box every object in the world must stay within, unbounded when unset
+    #[serde(default)]
+    pub world_bounds: Option<super::vault_lib::WorldBounds>,
+    /// Seconds between automatic saves of the PebbleVault data, 0 disables autosave
+    #[serde(default = "default_vault_autosave_interval_secs")]
+    pub vault_autosave_interval_secs: u64,
+    /// Directory PebbleVault stores its data in, read once when the vault opens
+    #[serde(default = "default_vault_data_dir")]
+    pub vault_data_dir: String,
+    /// Directory saved player data is kept in, one JSON file per player
+    #[serde(default = "default_player_data_dir")]
+    pub player_data_dir: String,
+    /// Directory plugins save their state in between runs
+    #[serde(default = "default_plugin_data_dir")]
+    pub plugin_data_dir: String,
+    /// Key session tokens are signed with; a random key is used when unset
+    #[serde(default)]
+    pub session_secret: Option<String>,
+    /// Seconds a session token handed to a client stays valid
+    #[serde(default = "default_session_token_ttl_secs")]
+    pub session_token_ttl_secs: u64,
+    /// Whether new connections must present a session token signed with `session_secret`
+    #[serde(default)]
+    pub require_session_token: bool,
+    /// New connections each client address may open per second once its burst is spent
+    #[serde(default = "default_ip_connection_rate_per_sec")]
+    pub ip_connection_rate_per_sec: f64,
+    /// New connections a client address may open back to back
+    #[serde(default = "default_ip_connection_burst")]
+    pub ip_connection_burst: f64,
+    /// Largest inbound packet accepted from a client, in bytes
+    #[serde(default = "default_max_message_bytes")]
+    pub max_message_bytes: u64,
+    /// Events each connection may send per second once its burst is spent
+    #[serde(default = "default_event_rate_per_sec")]
+    pub event_rate_per_sec: f64,
+    /// Events a connection may send back to back
+    #[serde(default = "default_event_burst")]
+    pub event_burst: f64,
+    /// Consecutive throttled events after which a connection is dropped
+    #[serde(default = "default_max_dropped_events")]
+    pub max_dropped_events: u32,
+    /// Milliseconds a plugin may spend handling one game event before it is reported as hung
+    #[serde(default = "default_plugin_event_timeout_ms")]
+    pub plugin_event_timeout_ms: u64,
+    /// Socket.IO namespaces clients may connect to and the pool group serving each
+    #[serde(default = "default_namespaces")]
+    pub namespaces: Vec<NamespaceConfig>,
+    /// Token operators present to use the admin namespace; admin commands are refused when unset
+    #[serde(default)]
+    pub admin_token: Option<String>,
+    /// File every inbound client event is appended to for later replay; recording is off when unset
+    #[serde(default)]
+    pub event_log_path: Option<String>,
+    /// PebbleVault regions loaded at startup so the first players to enter them don't wait on disk
+    #[serde(default)]
+    pub preload_regions: Vec<Uuid>,
+}
+
+/// A namespace clients may connect to
+///
+/// Players are placed in thread pools of their namespace's `pool_group` and
+/// only see players of the same group, so game modes with their own group
+/// never share state. Namespaces listing the same group share its pools.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct NamespaceConfig {
+    /// Namespace path, e.g. `"/deathmatch"`
+    pub path: String,
+    #[serde(default = "default_pool_group")]
+    pub pool_group: String,
+    /// Plugins loaded for this group along with their dependencies, or every plugin when unset
+    #[serde(default)]
+    pub plugins: Option<Vec<String>>,
+}
+
+/// Handling of new connections once every thread pool is at capacity
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum FullServerPolicy {
+    /// Send `server_full` and disconnect immediately
+    #[default]
+    Reject,
+    /// Hold the connection until a slot opens or the queue timeout expires
+    Queue,
+}
+
+/// Falls back to the machine's available parallelism when no pool count is configured
+fn default_thread_pools() -> u32 {
+    std::thread::available_parallelism()
+        .map(|threads| threads.get() as u32)
+        .unwrap_or(1)
+}
+
+fn default_server_name() -> String {
+    String::from("Horizon Server")
+}
+
+fn default_max_player_speed() -> f64 {
+    10.0
+}
+
+fn default_movement_tolerance() -> f64 {
+    super::movement::DEFAULT_MOVEMENT_TOLERANCE
+}
+
+fn default_accept_connections() -> bool {
+    true
+}
+
+fn default_connection_queue_length() -> usize {
+    100
+}
+
+fn default_connection_queue_timeout_secs() -> u64 {
+    30
+}
+
+fn default_reconnect_grace_secs() -> u64 {
+    30
+}
+
+fn default_vault_autosave_interval_secs() -> u64 {
+    super::vault_lib::DEFAULT_AUTOSAVE_INTERVAL_SECS
+}
+
+fn default_vault_data_dir() -> String {
+    super::vault_lib::vault_data_dir_from_env()
+}
+
+fn default_player_data_dir() -> String {
+    crate::utilities::player_data::DEFAULT_PLAYER_DATA_DIR.to_string()
+}
+
+fn default_plugin_data_dir() -> String {
+    "./plugin-data".to_string()
+}
+
+fn default_session_token_ttl_secs() -> u64 {
+    super::session::DEFAULT_SESSION_TOKEN_TTL_SECS
+}
+
+fn default_heartbeat_interval_secs() -> u64 {
+    5
+}
+
+fn default_heartbeat_timeout_secs() -> u64 {
+    30
+}
+
+fn default_state_keyframe_interval() -> u32 {
+    super::delta::DEFAULT_KEYFRAME_INTERVAL
+}
+
+fn default_view_radius() -> f64 {
+    100.0
+}
+
+fn default_chat_rate_per_sec() -> f64 {
+    1.0
+}
+
+fn default_chat_burst() -> f64 {
+    5.0
+}
+
+fn default_chat_max_length() -> usize {
+    256
+}
+
+fn default_chat_local_radius() -> f64 {
+    50.0
+}
+
+fn default_tick_rate_hz() -> u32 {
+    crate::utilities::game_logic::DEFAULT_TICK_RATE_HZ
+}
+
+fn default_position_history_ms() -> u64 {
+    super::history::DEFAULT_POSITION_HISTORY_MS
+}
+
+fn default_max_rewind_ms() -> u64 {
+    super::hit_validation::DEFAULT_MAX_REWIND_MS
+}
+
+fn default_shot_damage() -> i32 {
+    super::hit_validation::DEFAULT_SHOT_DAMAGE
+}
+
+fn default_ip_connection_rate_per_sec() -> f64 {
+    1.0
+}
+
+fn default_ip_connection_burst() -> f64 {
+    10.0
+}
+
+fn default_max_message_bytes() -> u64 {
+    64 * 1024
+}
+
+fn default_event_rate_per_sec() -> f64 {
+    60.0
+}
+
+fn default_event_burst() -> f64 {
+    120.0
+}
+
+fn default_max_dropped_events() -> u32 {
+    100
+}
+
+fn default_pool_group() -> String {
+    String::from("default")
+}
+
+fn default_namespaces() -> Vec<NamespaceConfig> {
+    ["/", "/custom"]
+        .into_iter()
+        .map(|path| NamespaceConfig { path: path.to_string(), pool_group: default_pool_group(), plugins: None })
+        .collect()
+}
+
+fn default_plugin_event_timeout_ms() -> u64 {
+    crate::utilities::event_dispatch::DEFAULT_PLUGIN_EVENT_TIMEOUT_MS
 }
 
 impl ServerConfig {
     fn new() -> Self {
         Self {
             players_per_pool: 5000,
-            num_thread_pools: 60,
+            num_thread_pools: default_thread_pools(),
+            server_name: default_server_name(),
+            status_redirect: None,
+            max_player_speed: default_max_player_speed(),
+            movement_tolerance: default_movement_tolerance(),
+            spawn_position: [0.0; 3],
+            accept_connections: default_accept_connections(),
+            full_server_policy: FullServerPolicy::default(),
+            connection_queue_length: default_connection_queue_length(),
+            connection_queue_timeout_secs: default_connection_queue_timeout_secs(),
+            reconnect_grace_secs: default_reconnect_grace_secs(),
+            heartbeat_interval_secs: default_heartbeat_interval_secs(),
+            heartbeat_timeout_secs: default_heartbeat_timeout_secs(),
+            state_keyframe_interval: default_state_keyframe_interval(),
+            view_radius: default_view_radius(),
+            chat_rate_per_sec: default_chat_rate_per_sec(),
+            chat_burst: default_chat_burst(),
+            chat_max_length: default_chat_max_length(),
+            chat_local_radius: default_chat_local_radius(),
+            tick_rate_hz: default_tick_rate_hz(),
+            position_history_ms: default_position_history_ms(),
+            max_rewind_ms: default_max_rewind_ms(),
+            shot_damage: default_shot_damage(),
+            world_bounds: None,
+            vault_autosave_interval_secs: default_vault_autosave_interval_secs(),
+            vault_data_dir: default_vault_data_dir(),
+            player_data_dir: default_player_data_dir(),
+            plugin_data_dir: default_plugin_data_dir(),
+            session_secret: None,
+            session_token_ttl_secs: default_session_token_ttl_secs(),
+            require_session_token: false,
+            ip_connection_rate_per_sec: default_ip_connection_rate_per_sec(),
+            ip_connection_burst: default_ip_connection_burst(),
+            max_message_bytes: default_max_message_bytes(),
+            event_rate_per_sec: default_event_rate_per_sec(),
+            event_burst: default_event_burst(),
+            max_dropped_events: default_max_dropped_events(),
+            plugin_event_timeout_ms: default_plugin_event_timeout_ms(),
+            namespaces: default_namespaces(),
+            admin_token: None,
+            event_log_path: None,
+            preload_regions: Vec::new(),
+        }
+    }
+
+    /// Distinct pool groups and the plugins each loads, in order of first appearance
+    pub fn pool_groups(&self) -> Vec<(String, Option<Vec<String>>)> {
+        let mut groups: Vec<(String, Option<Vec<String>>)> = Vec::new();
+        for namespace in &self.namespaces {
+            if !groups.iter().any(|(group, _)| *group == namespace.pool_group) {
+                groups.push((namespace.pool_group.clone(), namespace.plugins.clone()));
+            }
+        }
+        groups
+    }
+
+    /// Names of fields that differ from `running` but only take effect after a restart
+    fn restart_required_changes(&self, running: &ServerConfig) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        if self.num_thread_pools != running.num_thread_pools {
+            changed.push("num_thread_pools");
+        }
+        if self.players_per_pool != running.players_per_pool {
+            changed.push("players_per_pool");
+        }
+        if self.tick_rate_hz != running.tick_rate_hz {
+            changed.push("tick_rate_hz");
+        }
+        if self.max_message_bytes != running.max_message_bytes {
+            changed.push("max_message_bytes");
+        }
+        if self.namespaces != running.namespaces {
+            changed.push("namespaces");
+        }
+        if self.vault_data_dir != running.vault_data_dir {
+            changed.push("vault_data_dir");
+        }
+        if self.event_log_path != running.event_log_path {
+            changed.push("event_log_path");
         }
+        if self.preload_regions != running.preload_regions {
+            changed.push("preload_regions");
+        }
+        if self.plugin_event_timeout_ms != running.plugin_event_timeout_ms {
+            changed.push("plugin_event_timeout_ms");
+        }
+        changed
+    }
+
+    /// Carries over the fields that cannot change while the server is running
+    fn keep_restart_fields(mut self, running: &ServerConfig) -> Self {
+        self.num_thread_pools = running.num_thread_pools;
+        self.players_per_pool = running.players_per_pool;
+        self.tick_rate_hz = running.tick_rate_hz;
+        self.max_message_bytes = running.max_message_bytes;
+        self.namespaces = running.namespaces.clone();
+        self.vault_data_dir = running.vault_data_dir.clone();
+        self.event_log_path = running.event_log_path.clone();
+        self.preload_regions = running.preload_regions.clone();
+        self.plugin_event_timeout_ms = running.plugin_event_timeout_ms;
+        self
+    }
+
+    /// Watches the config file at `path` and hands every valid change to `on_change`
+    ///
+    /// The file is polled for modification, re-parsed and validated. Invalid
+    /// files are rejected and the previous config stays in effect. Fields that
+    /// need a restart keep their running values and log a warning instead.
+    pub fn watch(path: PathBuf, on_change: impl Fn(&ServerConfig) + Send + 'static) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut current = super::live_config();
+            let mut last_modified = modified_time(&path);
+            let mut interval = tokio::time::interval(CONFIG_POLL_INTERVAL);
+
+            loop {
+                interval.tick().await;
+
+                let modified = modified_time(&path);
+                if modified.is_none() || modified == last_modified {
+                    continue;
+                }
+                last_modified = modified;
+
+                match load_from(&path) {
+                    Ok(reloaded) => {
+                        for field in reloaded.restart_required_changes(&current) {
+                            log_warn!(LOGGER, "SERVER", "{} changed in {}, restart the server to apply it", field, path.display());
+                        }
+                        let reloaded = reloaded.keep_restart_fields(&current);
+                        on_change(&reloaded);
+                        current = reloaded;
+                        log_info!(LOGGER, "SERVER", "Reloaded {}", path.display());
+                    }
+                    Err(e) => {
+                        log_error!(LOGGER, "SERVER", "Rejected config reload, keeping previous config: {:#}", e);
+                    }
+                }
+            }
+        })
+    }
+
+    /// Rejects configurations the server cannot start with
+    fn validate(&self) -> Result<()> {
+        if self.num_thread_pools == 0 {
+            anyhow::bail!("num_thread_pools must be at least 1, the server cannot accept connections without a thread pool");
+        }
+        if self.players_per_pool == 0 {
+            anyhow::bail!("players_per_pool must be at least 1, a thread pool without room admits no one");
+        }
+        let rates = [
+            ("chat_rate_per_sec", self.chat_rate_per_sec),
+            ("chat_burst", self.chat_burst),
+            ("ip_connection_rate_per_sec", self.ip_connection_rate_per_sec),
+            ("ip_connection_burst", self.ip_connection_burst),
+            ("event_rate_per_sec", self.event_rate_per_sec),
+            ("event_burst", self.event_burst),
+        ];
+        for (field, rate) in rates {
+            if rate.is_nan() || rate <= 0.0 {
+                anyhow::bail!("{} must be greater than 0, got {}", field, rate);
+            }
+        }
+        if self.tick_rate_hz == 0 {
+            anyhow::bail!("tick_rate_hz must be at least 1");
+        }
+        if self.heartbeat_interval_secs == 0 {
+            anyhow::bail!("heartbeat_interval_secs must be at least 1");
+        }
+        if let Some(bounds) = &self.world_bounds {
+            // NaN compares false both ways, so it is rejected here too
+            if (0..3).any(|axis| bounds.min[axis].is_nan() || bounds.max[axis].is_nan() || bounds.min[axis] > bounds.max[axis]) {
+                anyhow::bail!("world_bounds min must not exceed max on any axis");
+            }
+        }
+        if self.namespaces.is_empty() {
+            anyhow::bail!("namespaces must list at least one namespace");
+        }
+        for (index, namespace) in self.namespaces.iter().enumerate() {
+            if !namespace.path.starts_with('/') {
+                anyhow::bail!("namespace {} must start with '/'", namespace.path);
+            }
+            if namespace.path == super::admin::ADMIN_NAMESPACE {
+                anyhow::bail!("namespace {} is reserved for admin commands", namespace.path);
+            }
+            for other in &self.namespaces[..index] {
+                if other.path == namespace.path {
+                    anyhow::bail!("namespace {} is listed more than once", namespace.path);
+                }
+                if other.pool_group == namespace.pool_group && other.plugins != namespace.plugins {
+                    anyhow::bail!("namespaces {} and {} share pool group {} but list different plugins", other.path, namespace.path, namespace.pool_group);
+                }
+            }
+        }
+        Ok(())
     }
     fn log_level() -> String {
         String::from("info")
     }
     
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn startup_only_fields_are_reported_and_kept_on_reload() {
+        let running = ServerConfig::new();
+        let mut reloaded = ServerConfig::new();
+        reloaded.vault_data_dir = "./elsewhere".to_string();
+        reloaded.event_log_path = Some("./events.log".to_string());
+        reloaded.preload_regions = vec![Uuid::new_v4()];
+        reloaded.plugin_event_timeout_ms = running.plugin_event_timeout_ms + 1;
+        reloaded.chat_burst = running.chat_burst + 1.0;
+
+        assert_eq!(
+            reloaded.restart_required_changes(&running),
+            vec!["vault_data_dir", "event_log_path", "preload_regions", "plugin_event_timeout_ms"]
+        );
+
+        let applied = reloaded.keep_restart_fields(&running);
+        assert!(applied.restart_required_changes(&running).is_empty());
+        // Fields that apply live still take the reloaded value
+        assert_eq!(applied.chat_burst, running.chat_burst + 1.0);
+    }
+
+    #[test]
+    fn empty_pools_and_rates_that_are_not_positive_are_rejected() {
+        assert!(ServerConfig::new().validate().is_ok());
+
+        let mut config = ServerConfig::new();
+        config.players_per_pool = 0;
+        assert!(config.validate().unwrap_err().to_string().contains("players_per_pool"));
+
+        for rate in [0.0, -1.0, f64::NAN] {
+            let mut config = ServerConfig::new();
+            config.event_rate_per_sec = rate;
+            assert!(config.validate().unwrap_err().to_string().contains("event_rate_per_sec"));
+            let mut config = ServerConfig::new();
+            config.chat_burst = rate;
+            assert!(config.validate().unwrap_err().to_string().contains("chat_burst"));
+        }
+    }
 }
\ No newline at end of file
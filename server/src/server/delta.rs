@@ -0,0 +1,182 @@
+//-----------------------------------------------------------------------------
+// Delta Compression for Entity State
+//   - Diffs each entity against the last state sent to a given receiver
+//   - Only changed fields are serialized
+//   - Periodic keyframes resynchronize receivers after packet loss
+//-----------------------------------------------------------------------------
+
+use serde::Serialize;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Default number of updates sent to a receiver between full keyframes
+pub const DEFAULT_KEYFRAME_INTERVAL: u32 = 30;
+
+/// Full replicated state of one entity
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct EntitySnapshot {
+    pub position: [f32; 3],
+    pub rotation: [f32; 4],
+    pub health: f32,
+}
+
+/// Fields of an entity that changed since the previous snapshot
+///
+/// A keyframe carries every field, so it can be applied to any base state.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize)]
+pub struct StateDelta {
+    pub keyframe: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position: Option<[f32; 3]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rotation: Option<[f32; 4]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub health: Option<f32>,
+}
+
+impl StateDelta {
+    /// A delta that resets the receiver to `snapshot`
+    pub fn keyframe(snapshot: &EntitySnapshot) -> Self {
+        Self {
+            keyframe: true,
+            position: Some(snapshot.position),
+            rotation: Some(snapshot.rotation),
+            health: Some(snapshot.health),
+        }
+    }
+
+    /// Whether applying this delta would change anything
+    pub fn is_empty(&self) -> bool {
+        !self.keyframe && self.position.is_none() && self.rotation.is_none() && self.health.is_none()
+    }
+}
+
+/// Computes the fields of `cur` that differ from `prev`
+pub fn compute_delta(prev: &EntitySnapshot, cur: &EntitySnapshot) -> StateDelta {
+    StateDelta {
+        keyframe: false,
+        position: (prev.position != cur.position).then_some(cur.position),
+        rotation: (prev.rotation != cur.rotation).then_some(cur.rotation),
+        health: (prev.health != cur.health).then_some(cur.health),
+    }
+}
+
+/// Applies `delta` on top of `base`, returning the reconstructed state
+pub fn apply_delta(base: &EntitySnapshot, delta: &StateDelta) -> EntitySnapshot {
+    EntitySnapshot {
+        position: delta.position.unwrap_or(base.position),
+        rotation: delta.rotation.unwrap_or(base.rotation),
+        health: delta.health.unwrap_or(base.health),
+    }
+}
+
+/// Last state sent to one receiver for each entity it knows about
+pub struct DeltaTracker {
+    last_sent: HashMap<Uuid, EntitySnapshot>,
+    updates_since_keyframe: HashMap<Uuid, u32>,
+    keyframe_interval: u32,
+}
+
+impl DeltaTracker {
+    pub fn new(keyframe_interval: u32) -> Self {
+        Self {
+            last_sent: HashMap::new(),
+            updates_since_keyframe: HashMap::new(),
+            keyframe_interval: keyframe_interval.max(1),
+        }
+    }
+
+    /// Produces the delta to send for `entity_id` and records `snapshot` as sent
+    ///
+    /// Returns `None` when nothing changed. The first update for an entity and
+    /// every `keyframe_interval`-th update after it are sent as keyframes.
+    pub fn next(&mut self, entity_id: Uuid, snapshot: EntitySnapshot) -> Option<StateDelta> {
+        let updates = self.updates_since_keyframe.entry(entity_id).or_insert(0);
+        let delta = match self.last_sent.get(&entity_id) {
+            Some(prev) if *updates < self.keyframe_interval => compute_delta(prev, &snapshot),
+            _ => StateDelta::keyframe(&snapshot),
+        };
+        if delta.is_empty() {
+            return None;
+        }
+
+        *updates = if delta.keyframe { 1 } else { *updates + 1 };
+        self.last_sent.insert(entity_id, snapshot);
+        Some(delta)
+    }
+
+    /// Forgets an entity so the next update for it is sent as a keyframe
+    pub fn forget(&mut self, entity_id: Uuid) {
+        self.last_sent.remove(&entity_id);
+        self.updates_since_keyframe.remove(&entity_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A snapshot that differs from its neighbors in position, and in health and rotation every few steps
+    fn snapshot(step: u32) -> EntitySnapshot {
+        EntitySnapshot {
+            position: [step as f32, 0.5 * step as f32, 0.0],
+            rotation: if step % 3 == 0 { [0.0, 0.0, 0.0, 1.0] } else { [0.0, 1.0, 0.0, 0.0] },
+            health: 100.0 - (step / 4) as f32,
+        }
+    }
+
+    #[test]
+    fn applying_every_delta_to_a_keyframe_rebuilds_the_current_state() {
+        let keyframe = StateDelta::keyframe(&snapshot(0));
+        let mut received = apply_delta(&snapshot(7), &keyframe);
+
+        for step in 1..20 {
+            received = apply_delta(&received, &compute_delta(&snapshot(step - 1), &snapshot(step)));
+            assert_eq!(received, snapshot(step));
+        }
+    }
+
+    #[test]
+    fn unchanged_fields_are_left_out_of_a_delta() {
+        let mut moved = snapshot(0);
+        moved.position[0] += 1.0;
+
+        assert_eq!(compute_delta(&snapshot(0), &moved), StateDelta { position: Some(moved.position), ..StateDelta::default() });
+        assert!(compute_delta(&moved, &moved).is_empty());
+    }
+
+    #[test]
+    fn a_keyframe_is_sent_every_interval() {
+        let mut tracker = DeltaTracker::new(3);
+        let entity_id = Uuid::new_v4();
+
+        let keyframes: Vec<bool> = (0..7).map(|step| tracker.next(entity_id, snapshot(step)).unwrap().keyframe).collect();
+
+        assert_eq!(keyframes, [true, false, false, true, false, false, true]);
+    }
+
+    #[test]
+    fn nothing_is_sent_when_nothing_changed() {
+        let mut tracker = DeltaTracker::new(3);
+        let entity_id = Uuid::new_v4();
+        tracker.next(entity_id, snapshot(0)).unwrap();
+
+        assert_eq!(tracker.next(entity_id, snapshot(0)), None);
+        assert_eq!(tracker.next(entity_id, snapshot(0)), None);
+        // Updates that were not sent don't count towards the next keyframe
+        assert!(!tracker.next(entity_id, snapshot(1)).unwrap().keyframe);
+        assert!(!tracker.next(entity_id, snapshot(2)).unwrap().keyframe);
+        assert!(tracker.next(entity_id, snapshot(3)).unwrap().keyframe);
+    }
+
+    #[test]
+    fn a_forgotten_entity_gets_a_keyframe() {
+        let mut tracker = DeltaTracker::new(DEFAULT_KEYFRAME_INTERVAL);
+        let entity_id = Uuid::new_v4();
+        tracker.next(entity_id, snapshot(0)).unwrap();
+
+        tracker.forget(entity_id);
+
+        assert_eq!(tracker.next(entity_id, snapshot(0)), Some(StateDelta::keyframe(&snapshot(0))));
+    }
+}
@@ -1,70 +1,298 @@
-use uuid::Uuid;
-use rayon::prelude::*;
-use horizon_data_types::Vec3D;
-
-
-mod structs;
-
-struct Actor {
-    name: String,
-    location: Vec3D,
-    uuid: Uuid,
-    has_collision: bool,
-    replication: bool,
-    replication_distance: f64,
-}
-
-impl Actor {
-    fn new(name: &str, has_collision: bool) -> Self {
-        Self {
-            name: name.to_string(),
-            location: Vec3D { x: 0.0, y: 0.0, z: 0.0 },
-            uuid: Uuid::new_v4(),
-            has_collision,
-            replication: false,
-            replication_distance: 0.0,
-        }
-    }
-
-    fn check_collision(&self, other_actor: &Actor) -> bool {
-        // Check collision between self and other
-        // Return true if collision detected, false otherwise
-        let dx = self.location.x - other_actor.location.x;
-        let dy = self.location.y - other_actor.location.y;
-        let dz = self.location.z - other_actor.location.z;
-        let distance = (dx * dx + dy * dy + dz * dz).sqrt();
-        return distance < other_actor.replication_distance;
-    }
-}
-
-fn main() {
-    let actors = vec![
-        Actor::new("Actor1", true),
-        Actor::new("Actor2", false),
-        Actor::new("Actor3", true),
-        // Add more actors as needed
-    ];
-
-    let collidable_actors: Vec<&Actor> = actors.iter().filter(|actor| actor.has_collision).collect();
-
-    collidable_actors.par_iter().enumerate().for_each(|(i, actor1)| {
-        collidable_actors.iter().skip(i + 1).for_each(|actor2| {
-            if actor1.check_collision(actor2) {
-                println!("Collision detected between {} and {}", actor1.name, actor2.name);
-            }
-        });
-    });
-}
-
-
-fn get_overlapping_colissions(main_actor: Actor, actors: Vec<Actor>) -> Vec<Uuid> {
-    let mut overlapping_collisions = Vec::new();
-
-    //for other_actor in actors {
-    //    if actor.check_collision(&other_actor) {
-    //        overlapping_collisions.push(other_actor.uuid);
-    //    }
-    //}
-
-    overlapping_collisions
-}
\ No newline at end of file
+use uuid::Uuid;
+use rayon::prelude::*;
+use horizon_data_types::Vec3D;
+use std::collections::HashMap;
+
+
+mod structs;
+
+/// Something in the world that can collide or be replicated to players
+pub struct Actor {
+    pub name: String,
+    pub location: Vec3D,
+    pub uuid: Uuid,
+    pub has_collision: bool,
+    pub collision_radius: f64,
+    pub replication: bool,
+    pub replication_distance: f64,
+}
+
+impl Actor {
+    pub fn new(name: &str, has_collision: bool) -> Self {
+        Self {
+            name: name.to_string(),
+            location: Vec3D { x: 0.0, y: 0.0, z: 0.0 },
+            uuid: Uuid::new_v4(),
+            has_collision,
+            collision_radius: 0.0,
+            replication: false,
+            replication_distance: 0.0,
+        }
+    }
+
+    fn check_collision(&self, other_actor: &Actor) -> bool {
+        // Sphere-sphere test: the actors touch once their centers are no further
+        // apart than the sum of both collision radii
+        let dx = self.location.x - other_actor.location.x;
+        let dy = self.location.y - other_actor.location.y;
+        let dz = self.location.z - other_actor.location.z;
+        let distance = (dx * dx + dy * dy + dz * dz).sqrt();
+        return distance <= self.collision_radius + other_actor.collision_radius;
+    }
+}
+
+/// Uniform spatial hash over actor positions
+///
+/// Actors are bucketed into cubic cells no smaller than the largest collision
+/// reach, so any colliding pair is guaranteed to sit in the same or an
+/// adjacent cell. Only those 27 cells need to be tested per actor.
+struct SpatialGrid {
+    cell_size: f64,
+    cells: HashMap<(i32, i32, i32), Vec<usize>>,
+}
+
+impl SpatialGrid {
+    fn new<'a>(actors: impl Iterator<Item = &'a Actor> + Clone, min_cell_size: f64) -> Self {
+        // Cells must span the largest possible pair reach (two of the biggest
+        // radii) to keep the neighbor search exact
+        let cell_size = actors
+            .clone()
+            .map(|actor| actor.collision_radius * 2.0)
+            .fold(min_cell_size.max(1.0), f64::max);
+
+        let mut grid = Self {
+            cell_size,
+            cells: HashMap::new(),
+        };
+
+        for (index, actor) in actors.enumerate() {
+            let cell = grid.cell_of(&actor.location);
+            grid.cells.entry(cell).or_default().push(index);
+        }
+
+        grid
+    }
+
+    fn cell_of(&self, location: &Vec3D) -> (i32, i32, i32) {
+        (
+            (location.x / self.cell_size).floor() as i32,
+            (location.y / self.cell_size).floor() as i32,
+            (location.z / self.cell_size).floor() as i32,
+        )
+    }
+
+    /// Indices of every actor in the cell containing `location` and its neighbors
+    fn nearby(&self, location: &Vec3D) -> Vec<usize> {
+        let (cx, cy, cz) = self.cell_of(location);
+        let mut indices = Vec::new();
+
+        for x in cx - 1..=cx + 1 {
+            for y in cy - 1..=cy + 1 {
+                for z in cz - 1..=cz + 1 {
+                    if let Some(cell) = self.cells.get(&(x, y, z)) {
+                        indices.extend_from_slice(cell);
+                    }
+                }
+            }
+        }
+
+        indices
+    }
+}
+
+/// Returns every pair of collidable actors that overlap
+///
+/// Each pair is reported once, with the actor that comes first in `actors` on
+/// the left. Pairs are narrowed with a spatial grid and tested in parallel.
+pub fn colliding_pairs(actors: &[Actor]) -> Vec<(Uuid, Uuid)> {
+    let collidable_actors: Vec<&Actor> = actors.iter().filter(|actor| actor.has_collision).collect();
+    let grid = SpatialGrid::new(collidable_actors.iter().copied(), 1.0);
+
+    let mut pairs: Vec<(usize, usize)> = collidable_actors
+        .par_iter()
+        .enumerate()
+        .flat_map_iter(|(i, actor1)| {
+            let collidable_actors = &collidable_actors;
+            grid.nearby(&actor1.location)
+                .into_iter()
+                .filter(move |&j| j > i && actor1.check_collision(collidable_actors[j]))
+                .map(move |j| (i, j))
+        })
+        .collect();
+    pairs.sort_unstable();
+    pairs
+        .into_iter()
+        .map(|(i, j)| (collidable_actors[i].uuid, collidable_actors[j].uuid))
+        .collect()
+}
+
+
+/// Returns the UUIDs of every actor overlapping `main_actor`
+///
+/// Candidates are narrowed with a spatial grid, so only actors in neighboring
+/// cells are tested instead of the whole list.
+pub fn get_overlapping_collisions(main_actor: &Actor, actors: &[Actor]) -> Vec<Uuid> {
+    let grid = SpatialGrid::new(actors.iter(), main_actor.collision_radius * 2.0);
+
+    grid.nearby(&main_actor.location)
+        .into_iter()
+        .map(|index| &actors[index])
+        .filter(|other_actor| other_actor.has_collision && other_actor.uuid != main_actor.uuid && main_actor.check_collision(other_actor))
+        .map(|other_actor| other_actor.uuid)
+        .collect()
+}
+
+/// Returns the UUIDs of every replicated actor a viewer at `viewer_pos` should receive
+///
+/// An actor is relevant when it has replication enabled and the viewer is within
+/// that actor's `replication_distance`. This is the core network culling step.
+pub fn relevant_actors(viewer_pos: Vec3D, actors: &[Actor]) -> Vec<Uuid> {
+    actors
+        .par_iter()
+        .filter(|actor| actor.replication)
+        .filter(|actor| {
+            let dx = actor.location.x - viewer_pos.x;
+            let dy = actor.location.y - viewer_pos.y;
+            let dz = actor.location.z - viewer_pos.z;
+            (dx * dx + dy * dy + dz * dz).sqrt() <= actor.replication_distance
+        })
+        .map(|actor| actor.uuid)
+        .collect()
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn actor_at(x: f64, y: f64, z: f64, radius: f64) -> Actor {
+        let mut actor = Actor::new("actor", true);
+        actor.location = Vec3D { x, y, z };
+        actor.collision_radius = radius;
+        actor
+    }
+
+    /// Every overlapping pair found by testing each actor against every other
+    fn naive_pairs(actors: &[Actor]) -> Vec<(Uuid, Uuid)> {
+        let mut pairs = Vec::new();
+        for (i, a) in actors.iter().enumerate() {
+            for b in &actors[i + 1..] {
+                if a.has_collision && b.has_collision && a.check_collision(b) {
+                    pairs.push((a.uuid, b.uuid));
+                }
+            }
+        }
+        pairs
+    }
+
+    /// Deterministic scatter of actors with mixed radii, some without collision
+    fn scattered_actors(count: usize) -> Vec<Actor> {
+        let mut seed: u64 = 0x2545_F491_4F6C_DD1D;
+        let mut next = move || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            (seed % 10_000) as f64 / 100.0
+        };
+        (0..count)
+            .map(|i| {
+                let mut actor = actor_at(next(), next(), next() / 10.0, 0.5 + next() / 50.0);
+                actor.has_collision = i % 7 != 0;
+                actor
+            })
+            .collect()
+    }
+
+    #[test]
+    fn grid_pairs_match_the_naive_search() {
+        let actors = scattered_actors(2_000);
+
+        let naive = naive_pairs(&actors);
+        let grid = colliding_pairs(&actors);
+
+        assert!(!naive.is_empty());
+        assert_eq!(grid, naive);
+    }
+
+    #[test]
+    fn overlapping_collisions_match_the_naive_search() {
+        let actors = scattered_actors(500);
+        for main_actor in actors.iter().filter(|actor| actor.has_collision).take(50) {
+            let mut expected: Vec<Uuid> = actors
+                .iter()
+                .filter(|other| other.has_collision && other.uuid != main_actor.uuid && main_actor.check_collision(other))
+                .map(|other| other.uuid)
+                .collect();
+            let mut found = get_overlapping_collisions(main_actor, &actors);
+            expected.sort();
+            found.sort();
+            assert_eq!(found, expected);
+        }
+    }
+
+    #[test]
+    fn actors_without_collision_never_collide() {
+        let mut ghost = actor_at(0.0, 0.0, 0.0, 1.0);
+        ghost.has_collision = false;
+        let actors = vec![ghost, actor_at(0.5, 0.0, 0.0, 1.0)];
+
+        assert!(colliding_pairs(&actors).is_empty());
+        assert!(get_overlapping_collisions(&actors[1], &actors).is_empty());
+    }
+
+    #[test]
+    fn touching_actors_collide() {
+        let a = actor_at(0.0, 0.0, 0.0, 1.0);
+        let b = actor_at(3.0, 0.0, 0.0, 2.0);
+        assert!(a.check_collision(&b));
+        assert!(b.check_collision(&a));
+    }
+
+    #[test]
+    fn overlapping_actors_collide() {
+        let a = actor_at(0.0, 0.0, 0.0, 1.0);
+        let b = actor_at(1.0, 1.0, 0.0, 0.5);
+        assert!(a.check_collision(&b));
+        assert_eq!(get_overlapping_collisions(&a, &[actor_at(50.0, 0.0, 0.0, 1.0), b]).len(), 1);
+    }
+
+    #[test]
+    fn two_overlapping_actors_make_exactly_one_pair() {
+        let actors = vec![actor_at(0.0, 0.0, 0.0, 1.0), actor_at(1.5, 0.0, 0.0, 1.0)];
+        assert_eq!(colliding_pairs(&actors), vec![(actors[0].uuid, actors[1].uuid)]);
+    }
+
+    #[test]
+    fn separated_actors_do_not_collide() {
+        let mut a = actor_at(0.0, 0.0, 0.0, 1.0);
+        // A large replication distance must not count as a collision
+        a.replication_distance = 100.0;
+        let b = actor_at(10.0, 0.0, 0.0, 1.0);
+        assert!(!a.check_collision(&b));
+        assert!(!b.check_collision(&a));
+    }
+
+    fn replicated_at(x: f64, replication_distance: f64) -> Actor {
+        let mut actor = actor_at(x, 0.0, 0.0, 0.5);
+        actor.replication = true;
+        actor.replication_distance = replication_distance;
+        actor
+    }
+
+    #[test]
+    fn relevant_actors_respect_each_replication_distance() {
+        let viewer = Vec3D { x: 0.0, y: 0.0, z: 0.0 };
+        let inside = replicated_at(9.99, 10.0);
+        let edge = replicated_at(10.0, 10.0);
+        let just_outside = replicated_at(10.01, 10.0);
+        let far_but_large = replicated_at(40.0, 50.0);
+        let mut not_replicated = replicated_at(1.0, 10.0);
+        not_replicated.replication = false;
+
+        let expected = vec![inside.uuid, edge.uuid, far_but_large.uuid];
+        let actors = [inside, edge, just_outside, far_but_large, not_replicated];
+        let mut relevant = relevant_actors(viewer, &actors);
+        relevant.sort();
+        let mut expected = expected;
+        expected.sort();
+        assert_eq!(relevant, expected);
+    }
+}
@@ -0,0 +1,175 @@
+//-----------------------------------------------------------------------------
+// Player Position History
+//   - Keeps a short, timestamped trail of each player's accepted positions
+//   - Interpolates where a player was at a past instant, for lag compensation
+//-----------------------------------------------------------------------------
+
+use horizon_data_types::Vec3D;
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Default length of history kept per player, in milliseconds
+pub const DEFAULT_POSITION_HISTORY_MS: u64 = 1000;
+
+lazy_static! {
+    static ref HISTORIES: RwLock<HashMap<Uuid, PositionHistory>> = RwLock::new(HashMap::new());
+}
+
+/// Recent positions of one player, oldest first
+#[derive(Debug, Clone)]
+pub struct PositionHistory {
+    samples: VecDeque<(Instant, Vec3D)>,
+    max_age: Duration,
+}
+
+impl PositionHistory {
+    /// Creates an empty history that keeps samples for `max_age`
+    pub fn new(max_age: Duration) -> Self {
+        Self { samples: VecDeque::new(), max_age }
+    }
+
+    /// Records a position, dropping samples older than `max_age` before `at`
+    ///
+    /// Samples must be pushed in time order; one older than the newest sample
+    /// is ignored.
+    pub fn push(&mut self, at: Instant, position: Vec3D) {
+        if self.samples.back().is_some_and(|(newest, _)| at < *newest) {
+            return;
+        }
+        self.samples.push_back((at, position));
+        while self.samples.front().is_some_and(|(oldest, _)| at.duration_since(*oldest) > self.max_age) {
+            self.samples.pop_front();
+        }
+    }
+
+    /// Position at `t`, linearly interpolated between the samples around it
+    ///
+    /// Returns `None` when `t` is before the oldest or after the newest sample.
+    pub fn position_at(&self, t: Instant) -> Option<Vec3D> {
+        let (first, _) = self.samples.front()?;
+        let (last, _) = self.samples.back()?;
+        if t < *first || t > *last {
+            return None;
+        }
+
+        // Index of the first sample at or after t; there is one since t <= last
+        let after = self.samples.partition_point(|(at, _)| *at < t);
+        let (after_time, after_position) = &self.samples[after];
+        if *after_time == t || after == 0 {
+            return Some(copy(after_position));
+        }
+
+        let (before_time, before_position) = &self.samples[after - 1];
+        let span = after_time.duration_since(*before_time).as_secs_f64();
+        let progress = t.duration_since(*before_time).as_secs_f64() / span;
+        Some(Vec3D {
+            x: before_position.x + (after_position.x - before_position.x) * progress,
+            y: before_position.y + (after_position.y - before_position.y) * progress,
+            z: before_position.z + (after_position.z - before_position.z) * progress,
+        })
+    }
+}
+
+fn copy(position: &Vec3D) -> Vec3D {
+    Vec3D { x: position.x, y: position.y, z: position.z }
+}
+
+/// Records a player's accepted position at the current time
+pub fn record(player_id: Uuid, position: Vec3D, max_age: Duration) {
+    HISTORIES
+        .write()
+        .entry(player_id)
+        .or_insert_with(|| PositionHistory::new(max_age))
+        .push(Instant::now(), position);
+}
+
+/// Where a player was at `t`, or its newest recorded position if `t` is later
+///
+/// A player that stops moving records no new samples, so its last sample
+/// remains its position until it moves again.
+pub fn rewound_position(player_id: Uuid, t: Instant) -> Option<Vec3D> {
+    let histories = HISTORIES.read();
+    let history = histories.get(&player_id)?;
+    match history.samples.back() {
+        Some((newest, position)) if t >= *newest => Some(copy(position)),
+        _ => history.position_at(t),
+    }
+}
+
+/// Drops a player's history, e.g. when it leaves the server
+pub fn forget(player_id: Uuid) {
+    HISTORIES.write().remove(&player_id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: f64) -> Vec3D {
+        Vec3D { x, y: 0.0, z: 0.0 }
+    }
+
+    /// A history with samples at x = 0, 10 and 20, 100ms apart, and its first timestamp
+    fn walking() -> (PositionHistory, Instant) {
+        let start = Instant::now();
+        let mut history = PositionHistory::new(Duration::from_secs(1));
+        for step in 0..3u32 {
+            history.push(start + Duration::from_millis(100) * step, at(10.0 * f64::from(step)));
+        }
+        (history, start)
+    }
+
+    #[test]
+    fn exact_samples_are_returned_as_recorded() {
+        let (history, start) = walking();
+
+        assert_eq!(history.position_at(start).unwrap().x, 0.0);
+        assert_eq!(history.position_at(start + Duration::from_millis(100)).unwrap().x, 10.0);
+        assert_eq!(history.position_at(start + Duration::from_millis(200)).unwrap().x, 20.0);
+    }
+
+    #[test]
+    fn positions_between_samples_are_interpolated() {
+        let (history, start) = walking();
+
+        let position = history.position_at(start + Duration::from_millis(125)).unwrap();
+
+        assert!((position.x - 12.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn times_outside_the_history_have_no_position() {
+        let (history, start) = walking();
+
+        assert!(history.position_at(start - Duration::from_millis(1)).is_none());
+        assert!(history.position_at(start + Duration::from_millis(201)).is_none());
+        assert!(PositionHistory::new(Duration::from_secs(1)).position_at(start).is_none());
+    }
+
+    #[test]
+    fn samples_older_than_the_max_age_are_dropped() {
+        let start = Instant::now();
+        let mut history = PositionHistory::new(Duration::from_millis(150));
+        history.push(start, at(0.0));
+        history.push(start + Duration::from_millis(100), at(1.0));
+        history.push(start + Duration::from_millis(200), at(2.0));
+
+        assert_eq!(history.samples.len(), 2);
+        assert!(history.position_at(start).is_none());
+    }
+
+    #[test]
+    fn a_player_that_stopped_moving_stays_at_its_last_sample() {
+        let player_id = Uuid::new_v4();
+        record(player_id, at(7.0), Duration::from_secs(1));
+
+        let later = Instant::now() + Duration::from_millis(50);
+
+        assert_eq!(rewound_position(player_id, later).unwrap().x, 7.0);
+        forget(player_id);
+        assert!(rewound_position(player_id, later).is_none());
+    }
+}
@@ -0,0 +1,168 @@
+//-----------------------------------------------------------------------------
+// Lag-Compensated Hit Validation
+//   - Rewinds a target to where it was when the shot was fired
+//   - Tests the shot ray against the target's collision sphere
+//   - Refuses shots older than the configured rewind limit
+//   - Turns confirmed hits into damage and death events
+//-----------------------------------------------------------------------------
+
+use super::{history, live_config};
+use crate::collision::PLAYER_COLLISION_RADIUS;
+use horizon_data_types::Vec3D;
+use plugin_api::GameEvent;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Default furthest a shot may be rewound, in milliseconds
+pub const DEFAULT_MAX_REWIND_MS: u64 = 300;
+
+/// Default health a confirmed hit takes from its target
+pub const DEFAULT_SHOT_DAMAGE: i32 = 10;
+
+/// Whether a shot fired by `shooter` at `shot_time` along `ray` hit `target`
+///
+/// `ray` is an origin and a direction, and the shot travels from the origin
+/// along the direction without limit. The target is rewound to its
+/// interpolated position at `shot_time`. Shots older than `max_rewind_ms`,
+/// from the future, at oneself or at a target without history never hit.
+pub fn validate_hit(shooter: Uuid, target: Uuid, shot_time: Instant, ray: (Vec3D, Vec3D)) -> bool {
+    if shooter == target {
+        return false;
+    }
+
+    let max_rewind = Duration::from_millis(live_config().max_rewind_ms);
+    let now = Instant::now();
+    if shot_time > now || now.duration_since(shot_time) > max_rewind {
+        return false;
+    }
+
+    let Some(position) = history::rewound_position(target, shot_time) else {
+        return false;
+    };
+    ray_hits_sphere(&ray.0, &ray.1, &position, PLAYER_COLLISION_RADIUS)
+}
+
+/// Takes up to `amount` from a target's `health`, never going below zero
+///
+/// Returns the target's new health and the events to emit: `PlayerDamaged`
+/// for the health actually lost, followed by `PlayerDied` if this hit took
+/// the target from alive to zero.
+pub fn apply_damage(target: Uuid, shooter: Uuid, health: i32, amount: i32) -> (i32, Vec<GameEvent>) {
+    let remaining = health.saturating_sub(amount.max(0)).max(0);
+
+    let mut events = Vec::new();
+    if remaining < health {
+        events.push(GameEvent::PlayerDamaged { player: target, source: Some(shooter), amount: i64::from(health - remaining) });
+    }
+    if health > 0 && remaining == 0 {
+        events.push(GameEvent::PlayerDied { player: target, killer: Some(shooter) });
+    }
+    (remaining, events)
+}
+
+/// Whether the ray from `origin` along `direction` passes within `radius` of `center`
+fn ray_hits_sphere(origin: &Vec3D, direction: &Vec3D, center: &Vec3D, radius: f64) -> bool {
+    let length_squared = direction.x * direction.x + direction.y * direction.y + direction.z * direction.z;
+    if !length_squared.is_normal() {
+        return false;
+    }
+
+    let to_center = [center.x - origin.x, center.y - origin.y, center.z - origin.z];
+    let along = (to_center[0] * direction.x + to_center[1] * direction.y + to_center[2] * direction.z) / length_squared;
+    // Spheres behind the shooter are only hit if the origin is inside them
+    let along = along.max(0.0);
+
+    let closest = [
+        origin.x + direction.x * along - center.x,
+        origin.y + direction.y * along - center.y,
+        origin.z + direction.z * along - center.z,
+    ];
+    closest[0] * closest[0] + closest[1] * closest[1] + closest[2] * closest[2] <= radius * radius
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: f64) -> Vec3D {
+        Vec3D { x, y: 0.0, z: 0.0 }
+    }
+
+    /// A ray fired from 10 units in front of `x`, straight back along the x axis
+    fn ray_at(x: f64) -> (Vec3D, Vec3D) {
+        (Vec3D { x, y: 0.0, z: 10.0 }, Vec3D { x: 0.0, y: 0.0, z: -1.0 })
+    }
+
+    #[test]
+    fn a_shot_at_where_the_target_was_hits_after_rewind() {
+        let (shooter, target) = (Uuid::new_v4(), Uuid::new_v4());
+        history::record(target, at(0.0), Duration::from_secs(1));
+        let shot_time = Instant::now();
+        std::thread::sleep(Duration::from_millis(20));
+        history::record(target, at(100.0), Duration::from_secs(1));
+
+        // The target has moved on, so the shot misses where it stands now
+        assert!(!ray_hits_sphere(&ray_at(0.0).0, &ray_at(0.0).1, &at(100.0), PLAYER_COLLISION_RADIUS));
+        assert!(validate_hit(shooter, target, shot_time, ray_at(0.0)));
+        assert!(!validate_hit(shooter, target, shot_time, ray_at(100.0)));
+        history::forget(target);
+    }
+
+    #[test]
+    fn shots_older_than_the_rewind_limit_are_rejected() {
+        let (shooter, target) = (Uuid::new_v4(), Uuid::new_v4());
+        history::record(target, at(0.0), Duration::from_secs(60));
+        let shot_time = Instant::now();
+        assert!(validate_hit(shooter, target, shot_time, ray_at(0.0)));
+
+        std::thread::sleep(Duration::from_millis(live_config().max_rewind_ms + 100));
+
+        // The target is still where the shot was aimed, but the shot is too old to rewind
+        assert!(history::rewound_position(target, shot_time).is_some());
+        assert!(!validate_hit(shooter, target, shot_time, ray_at(0.0)));
+        history::forget(target);
+    }
+
+    #[test]
+    fn players_cannot_shoot_themselves() {
+        let player = Uuid::new_v4();
+        history::record(player, at(0.0), Duration::from_secs(1));
+
+        assert!(!validate_hit(player, player, Instant::now(), ray_at(0.0)));
+        history::forget(player);
+    }
+
+    #[test]
+    fn a_hit_damages_the_target() {
+        let (shooter, target) = (Uuid::new_v4(), Uuid::new_v4());
+
+        let (health, events) = apply_damage(target, shooter, 100, 30);
+
+        assert_eq!(health, 70);
+        assert_eq!(events, vec![GameEvent::PlayerDamaged { player: target, source: Some(shooter), amount: 30 }]);
+    }
+
+    #[test]
+    fn a_killing_hit_reports_the_health_lost_and_the_death() {
+        let (shooter, target) = (Uuid::new_v4(), Uuid::new_v4());
+
+        let (health, events) = apply_damage(target, shooter, 20, 50);
+
+        assert_eq!(health, 0);
+        assert_eq!(
+            events,
+            vec![
+                GameEvent::PlayerDamaged { player: target, source: Some(shooter), amount: 20 },
+                GameEvent::PlayerDied { player: target, killer: Some(shooter) },
+            ]
+        );
+    }
+
+    #[test]
+    fn the_dead_cannot_die_again() {
+        let (health, events) = apply_damage(Uuid::new_v4(), Uuid::new_v4(), 0, 50);
+
+        assert_eq!(health, 0);
+        assert!(events.is_empty());
+    }
+}
@@ -0,0 +1,136 @@
+//-----------------------------------------------------------------------------
+// Area of Interest
+//   - Tracks which entities each player is subscribed to
+//   - Subscriptions only change when something moves, never on a tick
+//-----------------------------------------------------------------------------
+
+use std::collections::{HashMap, HashSet};
+use uuid::Uuid;
+
+/// How a receiver's view of a moved entity changed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Visibility {
+    /// The entity came into view; the receiver gets `entity_enter` and then its state
+    Entered,
+    /// The entity was already in view; the receiver gets its state
+    Visible,
+    /// The entity went out of view; the receiver gets `entity_leave`
+    Left,
+    /// The entity was and still is out of view; the receiver gets nothing
+    Hidden,
+}
+
+impl Visibility {
+    /// Whether the receiver is sent the entity's state
+    pub fn receives_state(self) -> bool {
+        matches!(self, Visibility::Entered | Visibility::Visible)
+    }
+}
+
+/// Updates one receiver's subscriptions after `entity` moved
+pub fn observe(subscriptions: &mut HashSet<Uuid>, entity: Uuid, in_range: bool) -> Visibility {
+    match (in_range, subscriptions.contains(&entity)) {
+        (true, true) => Visibility::Visible,
+        (true, false) => {
+            subscriptions.insert(entity);
+            Visibility::Entered
+        }
+        (false, true) => {
+            subscriptions.remove(&entity);
+            Visibility::Left
+        }
+        (false, false) => Visibility::Hidden,
+    }
+}
+
+/// Replaces a mover's own subscriptions with the entities now in its view
+///
+/// Returns the entities that entered its view and the ones that left it.
+pub fn resubscribe<P>(subscriptions: &mut HashSet<Uuid>, nearby: &HashMap<Uuid, P>) -> (Vec<Uuid>, Vec<Uuid>) {
+    let left: Vec<Uuid> = subscriptions.iter().filter(|entity| !nearby.contains_key(entity)).copied().collect();
+    for entity in &left {
+        subscriptions.remove(entity);
+    }
+    let entered: Vec<Uuid> = nearby.keys().filter(|entity| subscriptions.insert(**entity)).copied().collect();
+    (entered, left)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{event_rep, player_actor};
+    use horizon_data_types::Vec3D;
+
+    const VIEW_RADIUS: f64 = 50.0;
+
+    struct World {
+        positions: HashMap<Uuid, f64>,
+        subscriptions: HashMap<Uuid, HashSet<Uuid>>,
+        /// Number of state updates each player received
+        received: HashMap<Uuid, usize>,
+    }
+
+    impl World {
+        fn new(players: &[(Uuid, f64)]) -> Self {
+            Self {
+                positions: players.iter().copied().collect(),
+                subscriptions: players.iter().map(|(id, _)| (*id, HashSet::new())).collect(),
+                received: players.iter().map(|(id, _)| (*id, 0)).collect(),
+            }
+        }
+
+        /// Moves a player and delivers its state the way the server does
+        fn step(&mut self, mover: Uuid, x: f64) {
+            self.positions.insert(mover, x);
+            let candidates: Vec<event_rep::Actor> = self
+                .positions
+                .iter()
+                .filter(|(id, _)| **id != mover)
+                .map(|(id, x)| player_actor(*id, [*x, 0.0, 0.0], VIEW_RADIUS))
+                .collect();
+            let nearby: HashMap<Uuid, ()> = event_rep::relevant_actors(Vec3D { x, y: 0.0, z: 0.0 }, &candidates)
+                .into_iter()
+                .map(|id| (id, ()))
+                .collect();
+
+            resubscribe(self.subscriptions.get_mut(&mover).unwrap(), &nearby);
+            for (receiver, subscriptions) in self.subscriptions.iter_mut().filter(|(id, _)| **id != mover) {
+                if observe(subscriptions, mover, nearby.contains_key(receiver)).receives_state() {
+                    *self.received.get_mut(receiver).unwrap() += 1;
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn far_player_receives_nothing_from_the_close_pair() {
+        let (a, b, far) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        let mut world = World::new(&[(a, 0.0), (b, 10.0), (far, 1000.0)]);
+
+        world.step(a, 1.0);
+        world.step(b, 11.0);
+        world.step(a, 2.0);
+
+        assert_eq!(world.received[&far], 0);
+        assert_eq!(world.received[&a], 1);
+        assert_eq!(world.received[&b], 2);
+        assert!(world.subscriptions[&far].is_empty());
+        assert_eq!(world.subscriptions[&a], HashSet::from([b]));
+    }
+
+    #[test]
+    fn leaving_the_view_radius_unsubscribes() {
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let mut subscriptions = HashSet::new();
+
+        assert_eq!(observe(&mut subscriptions, a, true), Visibility::Entered);
+        assert_eq!(observe(&mut subscriptions, a, true), Visibility::Visible);
+        assert_eq!(observe(&mut subscriptions, a, false), Visibility::Left);
+        assert_eq!(observe(&mut subscriptions, a, false), Visibility::Hidden);
+
+        let (entered, left) = resubscribe(&mut subscriptions, &HashMap::from([(b, ())]));
+        assert_eq!((entered, left), (vec![b], vec![]));
+        let (entered, left) = resubscribe(&mut subscriptions, &HashMap::<Uuid, ()>::new());
+        assert_eq!((entered, left), (vec![], vec![b]));
+    }
+}
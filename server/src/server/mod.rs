@@ -16,31 +16,95 @@
 
 use crate::LOGGER;
 use anyhow::{Context, Result};
-use axum::{routing::get, serve, Router};
-use config::ServerConfig;
-use horizon_data_types::Player;
-use horizon_logger::{log_critical, log_debug, log_error, log_info, log_warn};
+use axum::{
+    response::{Html, IntoResponse, Redirect, Response},
+    routing::get,
+    Router,
+};
+use config::{FullServerPolicy, ServerConfig};
+use horizon_data_types::{Player, Vec3D};
+use crate::{log_critical, log_debug, log_error, log_info, log_warn};
 use horizon_plugin_api::LoadedPlugin;
 use parking_lot::RwLock;
-use plugin_api::{Plugin, Pluginstate};
+use plugin_api::{GameEvent, Plugin, Pluginstate};
 use socketioxide::{
     extract::{AckSender, Data, SocketRef},
     SocketIo,
 };
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{
+    atomic::{AtomicU64, AtomicUsize, Ordering},
+    Arc, OnceLock,
+};
 use tokio::sync::Mutex;
 use uuid::Uuid;
+pub mod admin;
+pub mod auth;
+mod codec;
 pub mod config;
-mod event_rep;
+mod delta;
+pub mod event_rep;
+mod history;
+pub mod hit_validation;
+mod interest;
+mod movement;
+mod pool;
+mod session;
+pub mod token;
+pub mod vault_lib;
 use lazy_static::lazy_static;
 use plugin_api::plugin_imports::*;
+use codec::PlayerState;
+use delta::{DeltaTracker, EntitySnapshot};
+use movement::MovementVerdict;
+use pool::PoolPlayers;
+use vault_lib::{PebbleVaultCustomData, PluginAPI as VaultAPI};
+use crate::utilities::{chat, event_dispatch, event_log, game_logic, notifications, player_data};
+use crate::utilities::rate_limit::TokenBuckets;
 
 
 lazy_static! {
     static ref SERVER: Server = Server::new().unwrap();
+    /// New connections allowed per client address
+    static ref CONNECTION_LIMITER: parking_lot::Mutex<TokenBuckets<std::net::IpAddr>> =
+        parking_lot::Mutex::new(TokenBuckets::new());
 }
 
+/// Live Socket.IO instance, set once the layer is created in `spawn_thread_pools`
+static SOCKET_IO: OnceLock<SocketIo> = OnceLock::new();
+
+/// Set by `prepare_replay`; replayed clients all share one address, so they skip the per-address limit
+static REPLAYING: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+/// Tower layer for the Socket.IO instance, mounted on the router in `serve`
+static SOCKET_LAYER: OnceLock<socketioxide::layer::SocketIoLayer> = OnceLock::new();
+
+/// One `on_shutdown` hook per loaded plugin, set in `bind` and run by `shutdown_plugins`
+static SHUTDOWN_HOOKS: OnceLock<Vec<plugin_api::ShutdownHook>> = OnceLock::new();
+
+/// Context every thread pool's plugins share, installed once by `shared_plugin_context`
+static PLUGIN_CONTEXT: OnceLock<plugin_api::PluginContext> = OnceLock::new();
+
+/// Routes plugin RPCs across the plugins of every pool group
+static PLUGIN_RPC: once_cell::sync::Lazy<plugin_api::SharedRpcRouter> = once_cell::sync::Lazy::new(plugin_api::SharedRpcRouter::new);
+
+/// Region players are placed in when they first report a position
+const WORLD_REGION_CENTER: [f64; 3] = [0.0, 0.0, 0.0];
+const WORLD_REGION_RADIUS: f64 = 100_000.0;
+
+/// Health of a player entering the world for the first time
+pub const DEFAULT_PLAYER_HEALTH: i32 = 100;
+
+/// How often queued connections check for a free pool slot
+const CONNECTION_QUEUE_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(250);
+
+/// How often expired reconnect sessions are swept
+const SESSION_SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// How often idle per-address connection buckets are dropped, and how long
+/// a bucket must be unused to go
+const CONNECTION_LIMITER_SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
 // Server state management
 
 //-----------------------------------------------------------------------------
@@ -49,6 +113,18 @@ lazy_static! {
 pub struct HorizonServer {
     config: ServerConfig,
     threads: RwLock<Vec<Arc<HorizonThread>>>,
+    player_sockets: RwLock<HashMap<Uuid, SocketRef>>,
+    started_at: std::time::Instant,
+    rejected_connections: AtomicU64,
+    queued_connections: AtomicUsize,
+    /// Disconnected players whose state is held for a possible reconnect
+    pending_sessions: session::PendingSessions,
+    /// Players being admitted, from their duplicate check until they are in `player_sockets`
+    admitting: parking_lot::Mutex<HashSet<Uuid>>,
+    /// Pool group of every player in the world, from joining until it leaves
+    player_groups: RwLock<HashMap<Uuid, String>>,
+    /// Plugins loaded by each pool group
+    group_plugins: RwLock<HashMap<String, HashSet<String>>>,
 }
 
 struct Server {
@@ -69,13 +145,162 @@ impl Server {
 impl HorizonServer {
     fn new() -> Result<Self> {
         Ok(Self {
-            config: *config::server_config()?,
+            config: config::server_config()?.as_ref().clone(),
             threads: RwLock::new(Vec::new()),
+            player_sockets: RwLock::new(HashMap::new()),
+            started_at: std::time::Instant::now(),
+            rejected_connections: AtomicU64::new(0),
+            queued_connections: AtomicUsize::new(0),
+            pending_sessions: session::PendingSessions::new(),
+            admitting: parking_lot::Mutex::new(HashSet::new()),
+            player_groups: RwLock::new(HashMap::new()),
+            group_plugins: RwLock::new(HashMap::new()),
         })
     }
 
-    fn spawn_thread(&self) -> Result<usize> {
-        let thread = HorizonThread::new();
+    /// Reserves `player_id` for a connection that is being admitted
+    ///
+    /// Fails if the player is already connected or another connection is
+    /// being admitted as it. Both are checked under the reservation lock, and
+    /// a reservation is only released once the player is in `player_sockets`
+    /// or its admission is abandoned, so two logins racing for the same
+    /// player can never both get through.
+    fn reserve_player(&self, player_id: Uuid) -> bool {
+        let mut admitting = self.admitting.lock();
+        !self.player_sockets.read().contains_key(&player_id) && admitting.insert(player_id)
+    }
+
+    /// Ends the reservation taken by `reserve_player`
+    fn release_player(&self, player_id: Uuid) {
+        self.admitting.lock().remove(&player_id);
+    }
+
+    /// Claims the player a session token was issued for, if it disconnected
+    /// within the reconnect grace period
+    ///
+    /// Tokens are checked for a valid signature and expiry first. A claimed
+    /// player is no longer held, so no other connection can resume it.
+    fn take_pending_session(&self, session_token: &str) -> Option<Uuid> {
+        let claims = match token::verify_token(session_token) {
+            Ok(claims) => claims,
+            Err(e) => {
+                log_debug!(LOGGER, "SOCKET NET", "Ignoring session token: {}", e);
+                return None;
+            }
+        };
+        let grace = std::time::Duration::from_secs(self.config.reconnect_grace_secs);
+        self.pending_sessions.take(claims.player_id, grace).then_some(claims.player_id)
+    }
+
+    /// Drops sessions whose grace period has passed and returns their player ids
+    fn evict_expired_sessions(&self) -> Vec<Uuid> {
+        let grace = std::time::Duration::from_secs(self.config.reconnect_grace_secs);
+        self.pending_sessions.evict_expired(grace)
+    }
+
+    /// Least-loaded thread pool of `pool_group` that still has room for another player
+    fn available_thread(&self, pool_group: &str) -> Option<Arc<HorizonThread>> {
+        let capacity = self.config.players_per_pool as usize;
+        self.threads
+            .read()
+            .iter()
+            .filter(|thread| thread.pool_group == pool_group && !thread.draining.load(Ordering::SeqCst))
+            .map(|thread| (thread.player_count(), thread))
+            .filter(|(load, _)| *load < capacity)
+            .min_by_key(|(load, _)| *load)
+            .map(|(_, thread)| Arc::clone(thread))
+    }
+
+    /// Number of players held by all thread pools
+    pub fn total_players(&self) -> usize {
+        self.players_per_pool().into_iter().sum()
+    }
+
+    /// Number of players held by each thread pool, in thread order
+    ///
+    /// Read from each pool's player counter, so busy pools are counted without
+    /// waiting on their locks. Players whose connection has closed are counted
+    /// until they are removed.
+    pub fn players_per_pool(&self) -> Vec<usize> {
+        self.threads.read().iter().map(|thread| thread.player_count()).collect()
+    }
+
+    /// Players in the same pool group as `player_id`, including itself
+    ///
+    /// Empty when the player is not in the world.
+    fn group_members(&self, player_id: Uuid) -> HashSet<Uuid> {
+        let Some(pool_group) = self.player_groups.read().get(&player_id).cloned() else {
+            return HashSet::new();
+        };
+        self.group_players(&pool_group)
+    }
+
+    /// Players in the world in `pool_group`
+    fn group_players(&self, pool_group: &str) -> HashSet<Uuid> {
+        self.player_groups
+            .read()
+            .iter()
+            .filter(|(_, group)| *group == pool_group)
+            .map(|(member, _)| *member)
+            .collect()
+    }
+
+    /// Players of `pool_group` among `nearby`
+    fn region_recipients(&self, pool_group: &str, nearby: Vec<Uuid>) -> HashSet<Uuid> {
+        let members = self.group_players(pool_group);
+        nearby.into_iter().filter(|player_id| members.contains(player_id)).collect()
+    }
+
+    /// Pool group an event belongs to, or `None` for events about no one in particular
+    ///
+    /// Player events belong to their player's group. Custom events belong to
+    /// the group named by a `pool_group` field, or else to the group of the
+    /// player named by a `player` field.
+    fn event_group(&self, event: &GameEvent) -> Option<String> {
+        let player_id = match event {
+            GameEvent::PlayerJoined { player }
+            | GameEvent::PlayerLeft { player }
+            | GameEvent::PlayerMoved { player, .. }
+            | GameEvent::ChatMessage { player, .. }
+            | GameEvent::PlayerDamaged { player, .. }
+            | GameEvent::PlayerDied { player, .. } => *player,
+            GameEvent::Custom { data, .. } => {
+                if let Some(pool_group) = data.get("pool_group").and_then(serde_json::Value::as_str) {
+                    return Some(pool_group.to_string());
+                }
+                data.get("player").and_then(serde_json::Value::as_str).and_then(|id| Uuid::parse_str(id).ok())?
+            }
+        };
+        self.player_groups.read().get(&player_id).cloned()
+    }
+
+    /// Whether `event` should be delivered to `plugin`
+    ///
+    /// Events belonging to a pool group only reach the plugins that group loads.
+    fn event_reaches(&self, plugin: &str, event: &GameEvent) -> bool {
+        match self.event_group(event) {
+            Some(pool_group) => self.group_plugins.read().get(&pool_group).is_some_and(|plugins| plugins.contains(plugin)),
+            None => true,
+        }
+    }
+
+    /// Threads serving `pool_group`
+    fn group_threads(&self, pool_group: &str) -> Vec<Arc<HorizonThread>> {
+        self.threads
+            .read()
+            .iter()
+            .filter(|thread| thread.pool_group == pool_group)
+            .cloned()
+            .collect()
+    }
+
+    fn spawn_thread(&self, pool_group: &str, plugins: Option<&[String]>) -> Result<usize> {
+        let thread = HorizonThread::new(pool_group, plugins)?;
+        self.group_plugins
+            .write()
+            .entry(pool_group.to_string())
+            .or_default()
+            .extend(thread.plugins.keys().cloned());
         let thread_id = {
             let mut threads = self.threads.write();
             threads.push(thread.into());
@@ -91,52 +316,290 @@ impl HorizonServer {
 // Horizon Thread Structhorizon_plugin_api::Plugin
 //-----------------------------------------------------------------------------
 struct HorizonThread {
-    players: Mutex<Vec<Player>>,
+    /// Group of pools this thread belongs to; players only meet players of the same group
+    pool_group: String,
+    players: Arc<Mutex<PoolPlayers<PooledPlayer>>>,
+    /// Number of players in `players`, kept in step by `PoolPlayers`
+    player_count: Arc<AtomicUsize>,
     plugins: HashMap<String, LoadedPlugin>,
+    plugin_manager: plugin_api::PluginManager,
     handle: tokio::task::JoinHandle<()>,
+    heartbeat: tokio::task::JoinHandle<()>,
+    /// Set once the thread starts handing its players to other threads
+    draining: std::sync::atomic::AtomicBool,
 }
 
-impl HorizonThread {
+/// A player held by a thread pool, along with its server-side connection state
+struct PooledPlayer {
+    player: Player,
+    socket: SocketRef,
+    heartbeat: Heartbeat,
+    /// Entity state last sent to this player, used to send only what changed
+    sent_state: DeltaTracker,
+    /// Entities within this player's view radius, the only ones it receives updates for
+    subscriptions: HashSet<Uuid>,
+}
+
+/// Time of the last inbound event from a player, shared with its socket handlers
+#[derive(Clone)]
+struct Heartbeat(Arc<parking_lot::Mutex<std::time::Instant>>);
+
+impl Heartbeat {
+    fn new() -> Self {
+        Self(Arc::new(parking_lot::Mutex::new(std::time::Instant::now())))
+    }
+
+    /// Records that the player was just heard from
+    fn touch(&self) {
+        *self.0.lock() = std::time::Instant::now();
+    }
+
+    fn elapsed(&self) -> std::time::Duration {
+        self.0.lock().elapsed()
+    }
+}
+
+/// Per-connection throttle on inbound events, shared by every handler of a socket
+///
+/// Each admitted event also counts as a sign of life for the idle sweep.
+/// Events over the configured rate are dropped, and a client that keeps
+/// flooding for `max_dropped_events` events in a row is disconnected.
+#[derive(Clone)]
+struct InboundGuard {
+    player_id: Uuid,
+    socket: SocketRef,
+    heartbeat: Heartbeat,
+    state: Arc<parking_lot::Mutex<InboundState>>,
+}
+
+struct InboundState {
+    bucket: TokenBuckets<()>,
+    /// Events dropped since the last admitted one
+    dropped: u32,
+}
+
+/// What happens to an inbound event
+#[derive(Debug, PartialEq)]
+enum Inbound {
+    Admit,
+    Drop,
+    /// Dropped, and the client has now flooded for long enough to be disconnected
+    Disconnect,
+}
+
+impl InboundState {
     fn new() -> Self {
+        Self { bucket: TokenBuckets::new(), dropped: 0 }
+    }
+
+    /// Takes a token for one event, counting the events dropped in a row
+    fn check(&mut self, rate: f64, burst: f64, max_dropped: u32) -> Inbound {
+        if self.bucket.try_acquire((), rate, burst) {
+            self.dropped = 0;
+            return Inbound::Admit;
+        }
+        self.dropped += 1;
+        if self.dropped == max_dropped {
+            Inbound::Disconnect
+        } else {
+            Inbound::Drop
+        }
+    }
+}
+
+impl InboundGuard {
+    fn new(player_id: Uuid, socket: SocketRef, heartbeat: Heartbeat) -> Self {
+        Self {
+            player_id,
+            socket,
+            heartbeat,
+            state: Arc::new(parking_lot::Mutex::new(InboundState::new())),
+        }
+    }
+
+    /// Returns whether the event may be handled
+    ///
+    /// Only admitted events are recorded, so a replay sends exactly the events
+    /// that reached a handler. `payload` is only called for those while inbound
+    /// events are being recorded.
+    fn admit(&self, event: &str, payload: impl FnOnce() -> serde_json::Value) -> bool {
+        self.heartbeat.touch();
+        let (rate, burst, max_dropped) = {
+            let server_instance = SERVER.get_instance();
+            let server = server_instance.read();
+            (server.config.event_rate_per_sec, server.config.event_burst, server.config.max_dropped_events)
+        };
+
+        let mut state = self.state.lock();
+        let verdict = state.check(rate, burst, max_dropped);
+        if verdict != Inbound::Admit && state.dropped == 1 {
+            log_warn!(LOGGER, "SOCKET NET", "Throttling events from {}, over {} per second", self.player_id, rate);
+        }
+        drop(state);
+        match verdict {
+            Inbound::Admit => {
+                if event_log::is_recording() {
+                    event_log::record(self.player_id, self.socket.ns(), event, payload());
+                }
+                true
+            }
+            Inbound::Drop => false,
+            Inbound::Disconnect => {
+                log_warn!(LOGGER, "SOCKET NET", "Disconnecting {} for flooding", self.player_id);
+                let _ = self.socket.clone().disconnect();
+                false
+            }
+        }
+    }
+}
+
+impl HorizonThread {
+    fn new(pool_group: &str, enabled_plugins: Option<&[String]>) -> Result<Self> {
         let mut plugin_manager = plugin_api::PluginManager::new();
-        let plugins = plugin_manager.load_all();
+        if let Some(io) = SOCKET_IO.get() {
+            plugin_manager.share_context(shared_plugin_context(io), &PLUGIN_RPC);
+        }
+        let plugins = plugin_manager.load_enabled(enabled_plugins).context("Failed to load plugins")?;
 
         plugins.iter().for_each(|(name, plugin)| {
             log_info!(LOGGER, "PLUGIN", "Loaded plugin: {}", name);
         });
-        Self {
-            players: Mutex::new(Vec::new()),
+        for failed in plugin_manager.failed_plugins() {
+            log_error!(LOGGER, "PLUGIN", "Plugin {} failed to load and is disabled: {}", failed.name, failed.reason);
+        }
+        let players = PoolPlayers::new();
+        let player_count = players.counter();
+        let players = Arc::new(Mutex::new(players));
+        Ok(Self {
+            pool_group: pool_group.to_string(),
+            heartbeat: tokio::spawn(sweep_idle_players(Arc::clone(&players))),
+            players,
+            player_count,
             plugins,
+            plugin_manager,
+            draining: std::sync::atomic::AtomicBool::new(false),
             handle: tokio::spawn(async move {
                 loop {
                     tokio::time::sleep(std::time::Duration::from_millis(10)).await;
                 }
             }),
-        }
+        })
     }
 
-    fn id(&self) -> usize {
-        self.players
-            .try_lock()
-            .map(|players| players.len())
-            .unwrap_or(usize::MAX)
+    /// Number of players this thread holds, without waiting for its lock
+    fn player_count(&self) -> usize {
+        self.player_count.load(Ordering::Acquire)
     }
 
-    async fn add_player(&self, player: Player) -> Result<()> {
+    async fn add_player(&self, player_id: Uuid, player: PooledPlayer) -> Result<()> {
+        let capacity = SERVER.get_instance().read().config.players_per_pool as usize;
         let mut players = self.players.lock().await;
-        players.push(player);
+        if players.len() >= capacity {
+            anyhow::bail!("Thread pool is full ({} players)", capacity);
+        }
+        players.insert(player_id, player);
         Ok(())
     }
 
-    // async fn remove_player(&self, player_id: &str) -> Result<bool> {
-    //     let mut players = self.players.lock().await;
-    //     if let Some(pos) = players.iter().position(|p| p.id == player_id) {
-    //         players.remove(pos);
-    //         Ok(true)
-    //     } else {
-    //         Ok(false)
-    //     }
-    // }
+    async fn remove_player(&self, player_id: Uuid) -> bool {
+        let mut players = self.players.lock().await;
+        players.remove(&player_id).is_some()
+    }
+
+    /// Moves every player to the least-loaded of `others`, then stops this thread
+    ///
+    /// Players keep their socket and connection state, and socket handlers
+    /// look players up by id rather than by thread, so their events keep
+    /// routing after the move. No new players are assigned here while the
+    /// drain runs. If it fails, the thread takes new players again and keeps
+    /// running with whichever players are left.
+    async fn drain_to(&self, others: &[Arc<HorizonThread>]) -> Result<usize> {
+        if self.draining.swap(true, Ordering::SeqCst) {
+            anyhow::bail!("The thread is already being drained");
+        }
+        let result = self.move_players_to(others).await;
+        if result.is_err() {
+            self.draining.store(false, Ordering::SeqCst);
+        }
+        result
+    }
+
+    /// Moves this thread's players to `others` and stops the thread, for `drain_to`
+    ///
+    /// Refuses to start when no other thread is left to take the players, so
+    /// a pool group always keeps a thread. If `others` run out of room, the
+    /// players left over stay here and an error is returned.
+    async fn move_players_to(&self, others: &[Arc<HorizonThread>]) -> Result<usize> {
+        let capacity = SERVER.get_instance().read().config.players_per_pool as usize;
+        let targets: Vec<&Arc<HorizonThread>> = others
+            .iter()
+            .filter(|other| !std::ptr::eq(other.as_ref(), self) && !other.draining.load(Ordering::SeqCst))
+            .collect();
+        if targets.is_empty() {
+            anyhow::bail!("This is the last thread of pool group {}", self.pool_group);
+        }
+
+        // Take the players out first so no two pool locks are ever held at once
+        let mut remaining: Vec<(Uuid, PooledPlayer)> = self.players.lock().await.drain();
+        let mut moved = 0;
+        while let Some((player_id, pooled)) = remaining.pop() {
+            let mut least_loaded = None;
+            for target in &targets {
+                let load = target.player_count();
+                if load < capacity && least_loaded.map_or(true, |(least, _)| load < least) {
+                    least_loaded = Some((load, *target));
+                }
+            }
+
+            let Some((_, target)) = least_loaded else {
+                remaining.push((player_id, pooled));
+                break;
+            };
+            target.players.lock().await.insert(player_id, pooled);
+            moved += 1;
+        }
+
+        if !remaining.is_empty() {
+            let left = remaining.len();
+            self.players.lock().await.extend(remaining);
+            anyhow::bail!("Moved {} players but no other thread had room for the remaining {}", moved, left);
+        }
+
+        self.handle.abort();
+        self.heartbeat.abort();
+        log_info!(LOGGER, "SERVER", "Drained {} players from a {} thread", moved, self.pool_group);
+        Ok(moved)
+    }
+}
+
+/// Pings every player in a pool and disconnects those that stopped responding
+///
+/// Eviction goes through the socket's disconnect handler, so idle players are
+/// cleaned up exactly like a clean disconnect. Interval and timeout are read
+/// from the live config on every pass.
+async fn sweep_idle_players(players: Arc<Mutex<PoolPlayers<PooledPlayer>>>) {
+    loop {
+        let (interval, timeout) = {
+            let server_instance = SERVER.get_instance();
+            let server = server_instance.read();
+            (
+                std::time::Duration::from_secs(server.config.heartbeat_interval_secs),
+                std::time::Duration::from_secs(server.config.heartbeat_timeout_secs),
+            )
+        };
+        tokio::time::sleep(interval).await;
+
+        let players = players.lock().await;
+        for (player_id, pooled) in players.iter() {
+            let idle = pooled.heartbeat.elapsed();
+            if idle > timeout {
+                log_warn!(LOGGER, "HEARTBEAT", "Evicting {} after {:?} without activity", player_id, idle);
+                let _ = pooled.socket.clone().disconnect();
+            } else if let Err(e) = pooled.socket.emit("ping", &()) {
+                log_debug!(LOGGER, "HEARTBEAT", "Failed to ping {}: {}", player_id, e);
+            }
+        }
+    }
 }
 
 //-----------------------------------------------------------------------------
@@ -156,103 +619,1647 @@ async fn handle_socket_ack(Data(data): Data<serde_json::Value>, ack: AckSender)
     }
 }
 
-fn on_connect(socket: SocketRef, Data(data): Data<serde_json::Value>) {
+/// Emits a `region_changed` custom event if a move carried a player into another region
+///
+/// The vault moves objects across region boundaries itself when they are updated.
+fn announce_region_change(player_id: Uuid, from: Option<Uuid>, to: Option<Uuid>) {
+    let (Some(from), Some(to)) = (from, to) else {
+        return;
+    };
+    if from == to {
+        return;
+    }
+    emit_event(GameEvent::custom("region_changed", serde_json::json!({
+        "player": player_id.to_string(),
+        "from": from.to_string(),
+        "to": to.to_string(),
+    })));
+}
+
+/// Handles a `chat` event of the form `{ "channel": "global", "text": "..." }`
+///
+/// Refused messages are answered with a `chat_error` event.
+fn handle_chat(socket: SocketRef, player_id: Uuid, data: serde_json::Value) {
+    let channel = data.get("channel").and_then(serde_json::Value::as_str).unwrap_or("global");
+    let text = data.get("text").and_then(serde_json::Value::as_str).unwrap_or_default();
+
+    let result = match chat::ChatChannel::parse(channel) {
+        Some(channel) => chat::send_message(player_id, channel, text).map_err(|e| e.to_string()),
+        None => Err(format!("Unknown chat channel {}", channel)),
+    };
+    if let Err(reason) = result {
+        log_debug!(LOGGER, "CHAT", "Refused message from {}: {}", player_id, reason);
+        let _ = socket.emit("chat_error", &serde_json::json!({ "reason": reason }));
+    }
+}
+
+/// Handles a `party_invite` event of the form `{ "party": ..., "player": ... }`
+///
+/// The invited player is sent a `party_invite` event naming the party and the
+/// leader, and can accept it with `party_join`.
+fn handle_party_invite(socket: SocketRef, player_id: Uuid, data: serde_json::Value) {
+    let party = data.get("party").and_then(serde_json::Value::as_str).unwrap_or_default();
+    let invitee = data.get("player").and_then(serde_json::Value::as_str).and_then(|id| Uuid::parse_str(id).ok());
+
+    let result = match invitee {
+        Some(invitee) if !group_members(player_id).contains(&invitee) => Err(format!("Player {} is not in your game", invitee)),
+        Some(invitee) => chat::invite_to_party(player_id, party, invitee).map(|()| invitee).map_err(|e| e.to_string()),
+        None => Err("Invalid player id".to_string()),
+    };
+    match result {
+        Ok(invitee) => {
+            let invite = serde_json::json!({ "party": party, "leader": player_id.to_string() });
+            emit_to_players(Some(&HashSet::from([invitee])), "party_invite", &invite);
+        }
+        Err(reason) => {
+            let _ = socket.emit("party_error", &serde_json::json!({ "reason": reason }));
+        }
+    }
+}
+
+/// Handles a `shot` event of the form
+/// `{ "target": ..., "age_ms": ..., "origin": [x, y, z], "direction": [x, y, z] }`
+///
+/// `age_ms` is how long ago the client fired, so the target is rewound to
+/// where the shooter saw it. Hits are confirmed to the shooter with a
+/// `hit_confirmed` event; misses and malformed shots get no answer.
+fn handle_shot(socket: SocketRef, player_id: Uuid, data: serde_json::Value) {
+    let target = data.get("target").and_then(serde_json::Value::as_str).and_then(|id| Uuid::parse_str(id).ok());
+    let age = data.get("age_ms").and_then(serde_json::Value::as_u64).map(std::time::Duration::from_millis);
+    let vector = |key: &str| {
+        let [x, y, z] = serde_json::from_value::<[f64; 3]>(data.get(key)?.clone()).ok()?;
+        Some(Vec3D { x, y, z })
+    };
+    let (Some(target), Some(age), Some(origin), Some(direction)) = (target, age, vector("origin"), vector("direction")) else {
+        log_warn!(LOGGER, "COMBAT", "Ignoring malformed shot from {}", player_id);
+        return;
+    };
+    let Some(shot_time) = std::time::Instant::now().checked_sub(age) else {
+        return;
+    };
+    // Players in another pool group are in another game and can't be hit
+    if !group_members(player_id).contains(&target) {
+        return;
+    }
+
+    if hit_validation::validate_hit(player_id, target, shot_time, (origin, direction)) {
+        let _ = socket.emit("hit_confirmed", &serde_json::json!({ "target": target.to_string() }));
+        if let Err(e) = damage_player(target, player_id, live_config().shot_damage) {
+            log_error!(LOGGER, "COMBAT", "Failed to damage {}: {}", target, e);
+        }
+    }
+}
+
+/// Takes `amount` health from a player in the world and emits the resulting events
+fn damage_player(target: Uuid, shooter: Uuid, amount: i32) -> Result<()> {
+    let vault = Plugin {};
+    let Some(mut object) = vault.get_object(target).map_err(anyhow::Error::msg)? else {
+        return Ok(());
+    };
+    let health = object.custom_data.health.unwrap_or_default();
+    let (remaining, events) = hit_validation::apply_damage(target, shooter, health, amount);
+
+    object.custom_data.health = Some(remaining);
+    vault.update_object(&object).map_err(anyhow::Error::msg)?;
+    for event in events {
+        emit_event(event);
+    }
+    Ok(())
+}
+
+/// Per-connection state shared between a player's socket handlers
+struct ConnectionState {
+    /// When the last move was accepted, or when the player connected before its first one
+    last_move: parking_lot::Mutex<std::time::Instant>,
+    /// Last rotation reported by the client, as a quaternion [x, y, z, w]
+    rotation: parking_lot::Mutex<[f32; 4]>,
+    /// Whether the client negotiated the binary state protocol
+    binary: std::sync::atomic::AtomicBool,
+    /// Pool group of the namespace the client connected to
+    pool_group: String,
+    /// Latest accepted state of the player, picked up by its `forward_entity_state` task
+    state_updates: tokio::sync::watch::Sender<Option<EntitySnapshot>>,
+    /// Held while a move is applied, so a player's moves apply in the order they arrived
+    moving: Mutex<()>,
+}
+
+impl ConnectionState {
+    /// Creates the state of a new connection and starts forwarding its state updates
+    fn new(player_id: Uuid, pool_group: String) -> Self {
+        let (state_updates, receiver) = tokio::sync::watch::channel(None);
+        tokio::spawn(forward_entity_state(player_id, pool_group.clone(), receiver));
+        Self {
+            pool_group,
+            last_move: parking_lot::Mutex::new(std::time::Instant::now()),
+            rotation: parking_lot::Mutex::new([0.0, 0.0, 0.0, 1.0]),
+            binary: std::sync::atomic::AtomicBool::new(false),
+            state_updates,
+            moving: Mutex::new(()),
+        }
+    }
+
+    fn binary(&self) -> bool {
+        self.binary.load(Ordering::Relaxed)
+    }
+}
+
+/// Sends a player state using the binary codec if the client negotiated it, or JSON otherwise
+fn emit_player_state(socket: &SocketRef, binary: bool, event: &'static str, state: &PlayerState) {
+    let result = if binary {
+        socket.emit(event, &bytes::Bytes::from(codec::encode_player_state(state)))
+    } else {
+        socket.emit(event, &serde_json::json!({
+            "x": state.position[0],
+            "y": state.position[1],
+            "z": state.position[2],
+            "rotation": state.rotation,
+        }))
+    };
+    if let Err(e) = result {
+        log_error!(LOGGER, "SOCKET EVENT", "Failed to send {}: {}", event, e);
+    }
+}
+
+/// Handles a JSON `player_move` event
+async fn handle_player_move(socket: SocketRef, player_id: Uuid, connection: &ConnectionState, data: serde_json::Value) {
+    let coordinate = |axis: &str| data.get(axis).and_then(serde_json::Value::as_f64);
+    let (Some(x), Some(y), Some(z)) = (coordinate("x"), coordinate("y"), coordinate("z")) else {
+        log_warn!(LOGGER, "MOVEMENT", "Ignoring malformed player_move from {}", player_id);
+        return;
+    };
+    if let Some(rotation) = data.get("rotation").and_then(|r| serde_json::from_value::<[f32; 4]>(r.clone()).ok()) {
+        *connection.rotation.lock() = rotation;
+    }
+    apply_player_move(socket, player_id, connection, x, y, z).await;
+}
+
+/// Handles a binary `state_binary` event from a client that negotiated the codec
+async fn handle_state_binary(socket: SocketRef, player_id: Uuid, connection: &ConnectionState, data: bytes::Bytes) {
+    if !connection.binary() {
+        log_warn!(LOGGER, "MOVEMENT", "Ignoring state_binary from {}, binary protocol was not negotiated", player_id);
+        return;
+    }
+    match codec::decode_player_state(&data) {
+        Ok(state) => {
+            let [x, y, z] = state.position.map(f64::from);
+            *connection.rotation.lock() = state.rotation;
+            apply_player_move(socket, player_id, connection, x, y, z).await;
+        }
+        Err(e) => log_warn!(LOGGER, "MOVEMENT", "Ignoring malformed state_binary from {}: {}", player_id, e),
+    }
+}
+
+/// Validates a client-reported move and applies it to PebbleVault if plausible
+///
+/// Rejected moves leave the authoritative position untouched and send the
+/// client a `position_correction` event so it can snap back. The vault is
+/// only touched on the blocking pool, so a busy region never stalls a
+/// runtime worker.
+async fn apply_player_move(socket: SocketRef, player_id: Uuid, connection: &ConnectionState, x: f64, y: f64, z: f64) {
+    let _moving = connection.moving.lock().await;
+    let now = std::time::Instant::now();
+    let dt = now.duration_since(*connection.last_move.lock()).as_secs_f64().min(movement::MAX_MOVE_INTERVAL_SECS);
+
+    match vault_lib::nonblocking::get_object(player_id).await {
+        Ok(Some(mut object)) => {
+            let old_position = Vec3D { x: object.point[0], y: object.point[1], z: object.point[2] };
+            let (max_speed, tolerance) = {
+                let server_instance = SERVER.get_instance();
+                let server = server_instance.read();
+                (server.config.max_player_speed, server.config.movement_tolerance)
+            };
+
+            match movement::validate_movement(old_position, Vec3D { x, y, z }, dt, max_speed, tolerance) {
+                MovementVerdict::Accept => {
+                    *connection.last_move.lock() = now;
+                    let from = object.point;
+                    let from_region = vault_lib::nonblocking::region_of(player_id).await.ok().flatten();
+                    object.point = [x, y, z];
+                    if dt > 0.0 {
+                        object.custom_data.velocity = [0, 1, 2].map(|axis| (object.point[axis] - from[axis]) / dt);
+                    }
+                    let health = object.custom_data.health.unwrap_or_default();
+                    if let Err(e) = vault_lib::nonblocking::update_object(object).await {
+                        log_error!(LOGGER, "MOVEMENT", "Failed to update position of {}: {}", player_id, e);
+                        return;
+                    }
+                    let history_length = std::time::Duration::from_millis(live_config().position_history_ms);
+                    history::record(player_id, Vec3D { x, y, z }, history_length);
+                    emit_event(GameEvent::PlayerMoved { player: player_id, from, to: [x, y, z] });
+                    let to_region = vault_lib::nonblocking::region_of(player_id).await.ok().flatten();
+                    announce_region_change(player_id, from_region, to_region);
+                    let snapshot = EntitySnapshot {
+                        position: [x as f32, y as f32, z as f32],
+                        rotation: *connection.rotation.lock(),
+                        health: health as f32,
+                    };
+                    connection.state_updates.send_replace(Some(snapshot));
+                }
+                MovementVerdict::Reject { corrected_position } => {
+                    log_warn!(LOGGER, "MOVEMENT", "Rejected move of {} to ({}, {}, {})", player_id, x, y, z);
+                    let correction = PlayerState {
+                        position: [corrected_position.x as f32, corrected_position.y as f32, corrected_position.z as f32],
+                        rotation: [0.0, 0.0, 0.0, 1.0],
+                    };
+                    emit_player_state(&socket, connection.binary(), "position_correction", &correction);
+                }
+            }
+        }
+        Ok(None) => {
+            // Players are placed at their saved position or the spawn point on
+            // admission, never where the client first claims to be
+            log_debug!(LOGGER, "MOVEMENT", "Ignoring move from {} before it was placed in the world", player_id);
+        }
+        Err(e) => log_error!(LOGGER, "MOVEMENT", "Failed to look up {}: {}", player_id, e),
+    }
+}
+
+/// Handles a new connection to a namespace served by `pool_group`
+fn on_connect(socket: SocketRef, Data(data): Data<serde_json::Value>, pool_group: String) {
     //socket.on("connect", |socket: SocketRef, _| {
-    log_info!(LOGGER, "SOCKET NET", "New connection from {}", socket.id);
+    log_info!(LOGGER, "SOCKET NET", "New connection from {} on {}", socket.id, socket.ns());
     //});
 
+    if let Some(address) = peer_address(&socket).filter(|_| !REPLAYING.load(Ordering::Relaxed)) {
+        let (rate, burst) = {
+            let server_instance = SERVER.get_instance();
+            let server = server_instance.read();
+            (server.config.ip_connection_rate_per_sec, server.config.ip_connection_burst)
+        };
+        if !CONNECTION_LIMITER.lock().try_acquire(address, rate, burst) {
+            log_warn!(LOGGER, "SOCKET NET", "Rate limiting connections from {}", address);
+            let _ = socket.emit("rate_limited", &serde_json::json!({ "reason": "Too many connections from your address" }));
+            let _ = socket.disconnect();
+            return;
+        }
+    }
+
     if let Err(e) = socket.emit("auth", &data) {
         log_error!(LOGGER, "SOCKET NET", "Failed to send auth: {}", e);
         return;
     }
 
-    // TODO: Implement proper thread management via round robin
-    let threadid = 0;
-
-    let server_instance = SERVER.get_instance();
-    let server_instance_read = server_instance.read();
-    let threads = server_instance_read.threads.read();
+    // A token in the connect payload is checked right away; otherwise the
+    // client has AUTH_TIMEOUT to send one in an `auth` message
+    let authenticator = auth::authenticator();
+    if let Some(token) = auth::token_from(&data) {
+        let result = authenticator.verify(token);
+        finish_authentication(socket, data, pool_group, result);
+        return;
+    }
+    if !authenticator.requires_token() {
+        accept_connection(socket, data, pool_group, auth::PlayerIdentity::default());
+        return;
+    }
 
-    socket.on("message", handle_socket_message);
-    socket.on("message-with-ack", handle_socket_ack);
+    let authenticated = Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let timeout_group = pool_group.clone();
+    socket.on("auth", {
+        let authenticated = Arc::clone(&authenticated);
+        move |socket: SocketRef, Data(payload): Data<serde_json::Value>| {
+            if authenticated.swap(true, Ordering::AcqRel) {
+                return;
+            }
+            let result = match auth::token_from(&payload) {
+                Some(token) => auth::authenticator().verify(token),
+                None => Err(auth::AuthError::MissingToken),
+            };
+            finish_authentication(socket, data.clone(), pool_group.clone(), result);
+        }
+    });
 
-    let player = horizon_data_types::Player::new(socket.clone(), Uuid::new_v4());
+    let pending = socket.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(auth::AUTH_TIMEOUT).await;
+        if !authenticated.swap(true, Ordering::AcqRel) {
+            finish_authentication(pending, serde_json::Value::Null, timeout_group, Err(auth::AuthError::MissingToken));
+        }
+    });
+}
 
-    let target_thread = Arc::clone(&threads[threadid]);
-    target_thread.add_player(player.clone());
+/// Address of the client behind a socket, if the transport recorded it
+fn peer_address(socket: &SocketRef) -> Option<std::net::IpAddr> {
+    socket
+        .req_parts()
+        .extensions
+        .get::<axum::extract::ConnectInfo<std::net::SocketAddr>>()
+        .map(|info| info.0.ip())
+}
 
-    let player_arc: Arc<horizon_data_types::Player> = Arc::new(player);
+/// Admits an authenticated connection, or sends `auth_failed` and disconnects it
+fn finish_authentication(socket: SocketRef, data: serde_json::Value, pool_group: String, result: Result<auth::PlayerIdentity, auth::AuthError>) {
+    match result {
+        Ok(identity) => accept_connection(socket, data, pool_group, identity),
+        Err(e) => {
+            log_warn!(LOGGER, "SOCKET NET", "Rejecting connection from {}: {}", socket.id, e);
+            let _ = socket.emit("auth_failed", &serde_json::json!({ "reason": e.to_string() }));
+            let _ = socket.disconnect();
+        }
+    }
+}
 
-    //let casted_struct = plugin_api::get_plugin!(unreal_adapter_horizon, target_thread.plugins);
+/// Places an authenticated connection in a thread pool, queues it or refuses it
+fn accept_connection(socket: SocketRef, data: serde_json::Value, pool_group: String, identity: auth::PlayerIdentity) {
+    // Clients resuming a dropped session send back the token they were issued
+    let session_token = data.get("session").and_then(serde_json::Value::as_str);
 
-    //casted_struct.player_joined(socket, player_arc);
-}
+    let (accept_connections, available_thread, policy) = {
+        let server_instance = SERVER.get_instance();
+        let server = server_instance.read();
+        (server.config.accept_connections, server.available_thread(&pool_group), server.config.full_server_policy)
+    };
 
-//-----------------------------------------------------------------------------
-// Server startup
-//-----------------------------------------------------------------------------
-pub async fn start() -> anyhow::Result<()> {
-    let start_time = std::time::Instant::now();
+    if !accept_connections {
+        log_info!(LOGGER, "SOCKET NET", "Refusing connection from {}, new connections are disabled", socket.id);
+        let _ = socket.emit("connection_refused", &serde_json::json!({ "reason": "Server is not accepting new connections" }));
+        let _ = socket.disconnect();
+        return;
+    }
 
-    let (layer, io) = SocketIo::new_layer();
-    // Initialize server state so we can spawn threads
+    // Authenticated accounts keep their id across sessions; anonymous players get a fresh one
+    let resumed_player = session_token.and_then(|token| SERVER.get_instance().read().take_pending_session(token));
+    let resumed = resumed_player.is_some();
+    let stable_player = identity.stable_player_id();
+    let player_id = resumed_player.or(stable_player).unwrap_or_else(Uuid::new_v4);
 
-    let thread_count = config::SERVER_CONFIG
-        .get()
-        .map(|config| config.num_thread_pools)
-        .unwrap_or_default();
+    if !SERVER.get_instance().read().reserve_player(player_id) {
+        // The reservation belongs to the other connection, so only the resumed session is given back
+        if resumed {
+            SERVER.get_instance().read().pending_sessions.hold(player_id);
+        }
+        log_warn!(LOGGER, "SOCKET NET", "Refusing connection from {}, player {} is already connected", socket.id, player_id);
+        let _ = socket.emit("connection_refused", &serde_json::json!({ "reason": "This player is already connected" }));
+        let _ = socket.disconnect();
+        return;
+    }
 
-    let thread_count = 32;
+    // Only ids that come back in a later session are worth saving
+    if !resumed && stable_player.is_some() {
+        player_data::mark_persistent(player_id);
+    }
+    if event_log::is_recording() {
+        event_log::record(player_id, socket.ns(), event_log::CONNECT_EVENT, data.clone());
+    }
 
-    println!("Preparing to start {} threads", thread_count);
-    // Start 10 threads initially for handling player connections
+    match (available_thread, policy) {
+        (Some(thread), _) => admit_player(socket, thread, player_id, resumed),
+        (None, FullServerPolicy::Reject) => {
+            abandon_admission(player_id, resumed);
+            refuse_full(socket, "All thread pools are full");
+        }
+        (None, FullServerPolicy::Queue) => queue_connection(socket, pool_group, player_id, resumed),
+    }
+}
 
-    //let handles = Vec::new();
+/// Undoes what `accept_connection` claimed for a connection that is not admitted after all
+///
+/// The player's reservation is released. A resumed player is held for a
+/// reconnect again, with its grace period starting over, so the refused
+/// client can still retry. A new player is no longer tracked for saving.
+fn abandon_admission(player_id: Uuid, resumed: bool) {
+    let server_instance = SERVER.get_instance();
+    let server = server_instance.read();
+    server.release_player(player_id);
+    if resumed {
+        server.pending_sessions.hold(player_id);
+    } else {
+        player_data::forget(player_id);
+    }
+}
 
-    let handles = Arc::new(Mutex::new(Vec::new()));
-    let server_instance = &SERVER.get_instance();
-    let spawn_futures: Vec<_> = (0..thread_count)
-        .map(|_| {
-            println!("Spawning thread");
+/// Creates the player for an accepted connection and places it in `target_thread`
+///
+/// When `resumed` is set the connection reclaims a player that dropped within
+/// the reconnect grace period, keeping its id and therefore its state. The
+/// session only starts once the pool has taken the player: the client is
+/// sent a fresh signed session token, and only then does a disconnect hold
+/// the player for a reconnect.
+fn admit_player(socket: SocketRef, target_thread: Arc<HorizonThread>, player_id: Uuid, resumed: bool) {
+    // Every inbound event passes the flood guard, which also feeds the idle sweep
+    let heartbeat = Heartbeat::new();
+    let guard = InboundGuard::new(player_id, socket.clone(), heartbeat.clone());
 
-            let handles = handles.clone();
+    socket.on("message", {
+        let guard = guard.clone();
+        move |socket: SocketRef, data: Data<serde_json::Value>| {
+            let admitted = guard.admit("message", || data.0.clone());
             async move {
-                if let Ok(thread_id) = server_instance.read().spawn_thread() {
-                    println!("Attempting to obtain handles lock");
-                    handles.lock().await.push(thread_id);
-                    println!("Handle lock obtained");
-
-                    println!("Thread spawned: {}", thread_id);
-                } else {
-                    println!("Failed to spawn thread");
+                if admitted {
+                    handle_socket_message(socket, data).await;
                 }
             }
-        })
-        .collect();
+        }
+    });
+    socket.on("message-with-ack", {
+        let guard = guard.clone();
+        move |data: Data<serde_json::Value>, ack: AckSender| {
+            let admitted = guard.admit("message-with-ack", || data.0.clone());
+            async move {
+                if admitted {
+                    handle_socket_ack(data, ack).await;
+                }
+            }
+        }
+    });
+    socket.on("chat", {
+        let guard = guard.clone();
+        move |socket: SocketRef, Data(data): Data<serde_json::Value>| {
+            if !guard.admit("chat", || data.clone()) {
+                return;
+            }
+            handle_chat(socket, player_id, data);
+        }
+    });
+    socket.on("party_join", {
+        let guard = guard.clone();
+        move |socket: SocketRef, Data(party): Data<String>| {
+            if !guard.admit("party_join", || serde_json::json!(party)) {
+                return;
+            }
+            if let Err(e) = chat::join_party(player_id, &party) {
+                let _ = socket.emit("party_error", &serde_json::json!({ "reason": e.to_string() }));
+            }
+        }
+    });
+    socket.on("party_invite", {
+        let guard = guard.clone();
+        move |socket: SocketRef, Data(data): Data<serde_json::Value>| {
+            if !guard.admit("party_invite", || data.clone()) {
+                return;
+            }
+            handle_party_invite(socket, player_id, data);
+        }
+    });
+    socket.on("party_leave", {
+        let guard = guard.clone();
+        move |Data(party): Data<String>| {
+            if !guard.admit("party_leave", || serde_json::json!(party)) {
+                return;
+            }
+            chat::leave_party(player_id, &party);
+        }
+    });
+    socket.on("shot", {
+        let guard = guard.clone();
+        move |socket: SocketRef, Data(data): Data<serde_json::Value>| {
+            if !guard.admit("shot", || data.clone()) {
+                return;
+            }
+            handle_shot(socket, player_id, data);
+        }
+    });
+    socket.on("beginplay", {
+        let guard = guard.clone();
+        move || {
+            if !guard.admit("beginplay", || serde_json::Value::Null) {
+                return;
+            }
+            notifications::flush(player_id);
+        }
+    });
+    socket.on("pong", {
+        let guard = guard.clone();
+        move || {
+            guard.admit("pong", || serde_json::Value::Null);
+        }
+    });
+    socket.on("ping", {
+        let guard = guard.clone();
+        move |socket: SocketRef| {
+            if !guard.admit("ping", || serde_json::Value::Null) {
+                return;
+            }
+            let _ = socket.emit("pong", &());
+        }
+    });
 
-    // Configure socket namespaces
-    io.ns("/", on_connect);
-    io.ns("/custom", on_connect);
-    println!("Accepting socket connections");
-    // Build the application with routes
-    let app = Router::new()
-        .route("/", get(|| async { "Horizon Server Running" }))
-        .layer(layer);
-    // Start the server
-    let address = "0.0.0.0:3000";
-    log_info!(LOGGER, "SOCKET NET", "Starting server on {}", address);
+    let player = horizon_data_types::Player::new(socket.clone(), player_id);
 
-    futures::future::join_all(spawn_futures).await;
+    let connection = Arc::new(ConnectionState::new(player_id, target_thread.pool_group.clone()));
+    socket.on("player_move", {
+        let guard = guard.clone();
+        let connection = Arc::clone(&connection);
+        move |socket: SocketRef, Data(data): Data<serde_json::Value>| {
+            let admitted = guard.admit("player_move", || data.clone());
+            let connection = Arc::clone(&connection);
+            async move {
+                if admitted {
+                    handle_player_move(socket, player_id, &connection, data).await;
+                }
+            }
+        }
+    });
 
-    log_info!(LOGGER, "SERVER", "Spawned {} threads", thread_count);
-    let elapsed = start_time.elapsed();
-    log_info!(LOGGER, "SERVER", "Server initialization took {:?}", elapsed);
+    // Clients opt in to the binary state channel; everyone else stays on JSON
+    socket.on("negotiate_binary", {
+        let guard = guard.clone();
+        let connection = Arc::clone(&connection);
+        move |Data(version): Data<u8>, ack: AckSender| {
+            if !guard.admit("negotiate_binary", || serde_json::json!(version)) {
+                return;
+            }
+            let accepted = version == codec::STATE_CODEC_VERSION;
+            connection.binary.store(accepted, Ordering::Relaxed);
+            let _ = ack.send(&serde_json::json!({ "accepted": accepted, "version": codec::STATE_CODEC_VERSION }));
+        }
+    });
+    socket.on("state_binary", {
+        let guard = guard.clone();
+        let connection = Arc::clone(&connection);
+        move |socket: SocketRef, Data(data): Data<bytes::Bytes>| {
+            let admitted = guard.admit("state_binary", || event_log::binary_payload(&data));
+            let connection = Arc::clone(&connection);
+            async move {
+                if admitted {
+                    handle_state_binary(socket, player_id, &connection, data).await;
+                }
+            }
+        }
+    });
 
-    let listener = tokio::net::TcpListener::bind(&address)
-        .await
-        .context(format!("Failed to bind to {}", address))?;
-    axum::serve(listener, app)
+    let keyframe_interval = SERVER.get_instance().read().config.state_keyframe_interval;
+    let pooled_player = PooledPlayer {
+        player: player.clone(),
+        socket: socket.clone(),
+        heartbeat,
+        sent_state: DeltaTracker::new(keyframe_interval),
+        subscriptions: HashSet::new(),
+    };
+    let pooled_socket = socket.clone();
+    tokio::spawn(async move {
+        if let Err(e) = target_thread.add_player(player_id, pooled_player).await {
+            log_error!(LOGGER, "SOCKET NET", "Failed to add player to thread pool: {}", e);
+            abandon_admission(player_id, resumed);
+            refuse_full(pooled_socket, "All thread pools are full");
+            return;
+        }
+        start_session(pooled_socket, player_id, target_thread.pool_group.clone(), resumed);
+    });
+
+    let player_arc: Arc<horizon_data_types::Player> = Arc::new(player);
+
+    //let casted_struct = plugin_api::get_plugin!(unreal_adapter_horizon, target_thread.plugins);
+
+    //casted_struct.player_joined(socket, player_arc);
+}
+
+/// Starts the session of a player its pool has just taken
+///
+/// The client is sent a signed session token to resume the player with after
+/// a drop, and the player's saved data is restored.
+fn start_session(socket: SocketRef, player_id: Uuid, pool_group: String, resumed: bool) {
+    let ttl = std::time::Duration::from_secs(SERVER.get_instance().read().config.session_token_ttl_secs);
+    let session = serde_json::json!({ "token": token::issue_token(player_id, ttl), "player_id": player_id.to_string() });
+    if let Err(e) = socket.emit("session", &session) {
+        log_error!(LOGGER, "SOCKET NET", "Failed to send session token: {}", e);
+    }
+
+    if resumed {
+        log_info!(LOGGER, "SOCKET NET", "Player {} reconnected", player_id);
+    }
+    notifications::begin_connecting(player_id);
+    SERVER.get_instance().read().player_groups.write().insert(player_id, pool_group);
+    emit_event(GameEvent::PlayerJoined { player: player_id });
+    {
+        let server_instance = SERVER.get_instance();
+        let server = server_instance.read();
+        server.player_sockets.write().insert(player_id, socket.clone());
+        server.release_player(player_id);
+    }
+    socket.on_disconnect(move |socket: SocketRef| end_session(&socket, player_id));
+    // The client may have left while it was being placed, before the handler existed
+    if !socket.connected() {
+        end_session(&socket, player_id);
+    }
+
+    // Returning players pick up where they left off
+    tokio::task::spawn_blocking(move || {
+        if let Err(e) = player_data::restore_player(player_id) {
+            log_error!(LOGGER, "PLAYER DATA", "Failed to load {}: {:#}", player_id, e);
+        }
+    });
+}
+
+/// Saves a disconnected player and holds it for a reconnect
+fn end_session(socket: &SocketRef, player_id: Uuid) {
+    let server_instance = SERVER.get_instance();
+    let server = server_instance.read();
+    // Only the connection that owns the player may end its session
+    {
+        let mut player_sockets = server.player_sockets.write();
+        if player_sockets.get(&player_id).map_or(true, |owner| owner.id != socket.id) {
+            return;
+        }
+        player_sockets.remove(&player_id);
+    }
+
+    event_log::record(player_id, socket.ns(), event_log::DISCONNECT_EVENT, serde_json::Value::Null);
+    server.pending_sessions.hold(player_id);
+    tokio::spawn(async move {
+        match tokio::task::spawn_blocking(move || player_data::persist_player(player_id)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => log_error!(LOGGER, "PLAYER DATA", "Failed to save {}: {:#}", player_id, e),
+            Err(e) => log_error!(LOGGER, "PLAYER DATA", "Failed to save {}: {}", player_id, e),
+        }
+        remove_player(player_id).await;
+    });
+}
+
+/// Broadcasts a player's state updates one at a time, in the order they were accepted
+///
+/// Updates that arrive while a broadcast is still running are coalesced into
+/// the newest one, so a slow broadcast never builds a backlog and receivers
+/// never see an older state after a newer one. Ends once the connection's
+/// state is dropped.
+async fn forward_entity_state(
+    player_id: Uuid,
+    pool_group: String,
+    mut updates: tokio::sync::watch::Receiver<Option<EntitySnapshot>>,
+) {
+    while updates.changed().await.is_ok() {
+        let Some(snapshot) = *updates.borrow_and_update() else {
+            continue;
+        };
+        broadcast_entity_state(player_id, snapshot, pool_group.clone()).await;
+    }
+}
+
+/// Updates area-of-interest subscriptions for a moved entity and forwards its state
+///
+/// Subscriptions are only recomputed when something moves: the mover's own set
+/// is rebuilt from a PebbleVault sphere query, and every other player gains or
+/// loses the mover depending on whether it is now within `view_radius`. Since
+/// the radius is symmetric, this keeps stationary players up to date as well.
+///
+/// Subscribers receive a `state_delta` event with only the fields that changed
+/// since the state they were last sent, with a full keyframe every
+/// `state_keyframe_interval` updates. Only players in the mover's pool group
+/// take part, so namespaces in different groups never see each other.
+async fn broadcast_entity_state(entity_id: Uuid, snapshot: EntitySnapshot, pool_group: String) {
+    let view_radius = SERVER.get_instance().read().config.view_radius;
+    let position = snapshot.position.map(f64::from);
+
+    let threads: Vec<Arc<HorizonThread>> = SERVER.get_instance().read().group_threads(&pool_group);
+    let mut group_players = HashSet::new();
+    for thread in &threads {
+        group_players.extend(thread.players.lock().await.keys().copied());
+    }
+
+    // PebbleVault narrows the candidates, and event_rep's replication rules decide who is relevant
+    let candidates: Vec<event_rep::Actor> = match vault_lib::nonblocking::query_sphere(position, view_radius).await {
+        Ok(objects) => objects
+            .into_iter()
+            .filter(|object| object.object_type == "player" && object.uuid != entity_id && group_players.contains(&object.uuid))
+            .map(|object| player_actor(object.uuid, object.point, view_radius))
+            .collect(),
+        Err(e) => {
+            log_error!(LOGGER, "STATE", "Failed to query players around {}: {}", entity_id, e);
+            return;
+        }
+    };
+    let [x, y, z] = position;
+    let relevant: HashSet<Uuid> = event_rep::relevant_actors(Vec3D { x, y, z }, &candidates).into_iter().collect();
+    let nearby: HashMap<Uuid, [f64; 3]> = candidates
+        .iter()
+        .filter(|actor| relevant.contains(&actor.uuid))
+        .map(|actor| (actor.uuid, [actor.location.x, actor.location.y, actor.location.z]))
+        .collect();
+
+    for thread in threads {
+        let mut players = thread.players.lock().await;
+        for (player_id, pooled) in players.iter_mut() {
+            if *player_id == entity_id {
+                update_own_subscriptions(pooled, &nearby);
+                continue;
+            }
+
+            let visibility = interest::observe(&mut pooled.subscriptions, entity_id, nearby.contains_key(player_id));
+            match visibility {
+                interest::Visibility::Entered => {
+                    let _ = pooled.socket.emit("entity_enter", &serde_json::json!({
+                        "entity": entity_id.to_string(),
+                        "position": position,
+                    }));
+                }
+                interest::Visibility::Left => {
+                    pooled.sent_state.forget(entity_id);
+                    let _ = pooled.socket.emit("entity_leave", &serde_json::json!({ "entity": entity_id.to_string() }));
+                }
+                interest::Visibility::Visible | interest::Visibility::Hidden => {}
+            }
+            if !visibility.receives_state() {
+                continue;
+            }
+
+            let Some(delta) = pooled.sent_state.next(entity_id, snapshot) else {
+                continue;
+            };
+            let update = serde_json::json!({ "entity": entity_id.to_string(), "delta": delta });
+            if let Err(e) = pooled.socket.emit("state_delta", &update) {
+                log_debug!(LOGGER, "STATE", "Failed to send state of {} to {}: {}", entity_id, player_id, e);
+            }
+        }
+    }
+}
+
+/// Replaces a player's subscriptions with `nearby`, notifying it of entities that entered or left
+fn update_own_subscriptions(pooled: &mut PooledPlayer, nearby: &HashMap<Uuid, [f64; 3]>) {
+    let (entered, left) = interest::resubscribe(&mut pooled.subscriptions, nearby);
+    for entity in left {
+        pooled.sent_state.forget(entity);
+        let _ = pooled.socket.emit("entity_leave", &serde_json::json!({ "entity": entity.to_string() }));
+    }
+    for entity in entered {
+        let _ = pooled.socket.emit("entity_enter", &serde_json::json!({
+            "entity": entity.to_string(),
+            "position": nearby[&entity],
+        }));
+    }
+}
+
+/// Removes a player from whichever thread pool currently holds it
+///
+/// Players that had it in view are sent an `entity_leave` event.
+async fn remove_player(player_id: Uuid) {
+    let threads: Vec<Arc<HorizonThread>> = SERVER.get_instance().read().threads.read().clone();
+    for thread in &threads {
+        if thread.remove_player(player_id).await {
+            break;
+        }
+    }
+    chat::remove_player(player_id);
+    notifications::remove_player(player_id);
+    history::forget(player_id);
+    emit_event(GameEvent::PlayerLeft { player: player_id });
+    {
+        let server_instance = SERVER.get_instance();
+        let server = server_instance.read();
+        // A player that already reconnected keeps its group
+        if !server.player_sockets.read().contains_key(&player_id) {
+            server.player_groups.write().remove(&player_id);
+        }
+    }
+
+    for thread in &threads {
+        let mut players = thread.players.lock().await;
+        for pooled in players.values_mut() {
+            if pooled.subscriptions.remove(&player_id) {
+                pooled.sent_state.forget(player_id);
+                let _ = pooled.socket.emit("entity_leave", &serde_json::json!({ "entity": player_id.to_string() }));
+            }
+        }
+    }
+}
+
+/// Tells the client the server is full and closes the connection
+fn refuse_full(socket: SocketRef, reason: &str) {
+    let rejected = SERVER
+        .get_instance()
+        .read()
+        .rejected_connections
+        .fetch_add(1, Ordering::Relaxed)
+        + 1;
+    log_warn!(LOGGER, "SOCKET NET", "Refused {}: {} ({} connections rejected so far)", socket.id, reason, rejected);
+
+    let _ = socket.emit("server_full", &serde_json::json!({ "reason": reason }));
+    let _ = socket.disconnect();
+}
+
+/// Holds a connection until a pool slot frees up or the queue timeout expires
+fn queue_connection(socket: SocketRef, pool_group: String, player_id: Uuid, resumed: bool) {
+    let (queue_length, timeout) = {
+        let server_instance = SERVER.get_instance();
+        let server = server_instance.read();
+        (
+            server.config.connection_queue_length,
+            std::time::Duration::from_secs(server.config.connection_queue_timeout_secs),
+        )
+    };
+
+    let position = SERVER.get_instance().read().queued_connections.fetch_add(1, Ordering::SeqCst) + 1;
+    if position > queue_length {
+        SERVER.get_instance().read().queued_connections.fetch_sub(1, Ordering::SeqCst);
+        abandon_admission(player_id, resumed);
+        refuse_full(socket, "All thread pools are full and the connection queue is full");
+        return;
+    }
+
+    log_info!(LOGGER, "SOCKET NET", "Queued {} at position {}", socket.id, position);
+    let _ = socket.emit("queued", &serde_json::json!({ "position": position }));
+
+    tokio::spawn(async move {
+        let deadline = std::time::Instant::now() + timeout;
+        let outcome = loop {
+            tokio::time::sleep(CONNECTION_QUEUE_POLL_INTERVAL).await;
+
+            if !socket.connected() {
+                break None;
+            }
+            if let Some(thread) = SERVER.get_instance().read().available_thread(&pool_group) {
+                break Some(Ok(thread));
+            }
+            if std::time::Instant::now() >= deadline {
+                break Some(Err(()));
+            }
+        };
+
+        SERVER.get_instance().read().queued_connections.fetch_sub(1, Ordering::SeqCst);
+        match outcome {
+            Some(Ok(thread)) => admit_player(socket, thread, player_id, resumed),
+            Some(Err(())) => {
+                abandon_admission(player_id, resumed);
+                refuse_full(socket, "Timed out waiting for a free slot");
+            }
+            None => abandon_admission(player_id, resumed),
+        }
+    });
+}
+
+/// Moves every player off the thread at `thread_index` and retires it
+///
+/// Players go to the other threads of the same pool group, so they keep
+/// seeing the same players. Returns how many players were moved.
+pub async fn drain_thread(thread_index: usize) -> Result<usize> {
+    let (thread, others) = {
+        let server_instance = SERVER.get_instance();
+        let server = server_instance.read();
+        let thread = server
+            .threads
+            .read()
+            .get(thread_index)
+            .cloned()
+            .with_context(|| format!("No thread with index {}", thread_index))?;
+        let others = server.group_threads(&thread.pool_group);
+        (thread, others)
+    };
+
+    let moved = thread.drain_to(&others).await?;
+    SERVER
+        .get_instance()
+        .read()
+        .threads
+        .write()
+        .retain(|other| !Arc::ptr_eq(other, &thread));
+    Ok(moved)
+}
+
+//-----------------------------------------------------------------------------
+// Config hot-reload
+//-----------------------------------------------------------------------------
+
+/// Applies a reloaded config to the running server
+fn apply_config_reload(config: &ServerConfig) {
+    let server_instance = SERVER.get_instance();
+    server_instance.write().config = config.clone();
+    Plugin {}.set_world_bounds(config.world_bounds);
+    Plugin {}.set_autosave_interval(config.vault_autosave_interval_secs);
+    install_authenticator(config);
+}
+
+/// Requires signed session tokens from new connections when the config asks for them
+fn install_authenticator(config: &ServerConfig) {
+    if config.require_session_token {
+        auth::set_authenticator(token::SessionTokenAuthenticator);
+    } else {
+        auth::set_authenticator(auth::NoAuthentication);
+    }
+}
+
+//-----------------------------------------------------------------------------
+// Plugin server handle
+//-----------------------------------------------------------------------------
+
+/// Context handed to every plugin, whichever pool group loaded it
+///
+/// Installed process-wide the first time a thread pool is built. Its RPC
+/// router reaches the plugins of every group, so a plugin only one group
+/// loads can still be called from the others.
+fn shared_plugin_context(io: &SocketIo) -> plugin_api::PluginContext {
+    PLUGIN_CONTEXT
+        .get_or_init(|| {
+            let context = plugin_api::PluginContext {
+                server: Arc::new(SocketServerHandle { io: io.clone() }),
+                rpc: Arc::new(PLUGIN_RPC.clone()),
+            };
+            horizon_events::set_plugin_context(context.clone());
+            context
+        })
+        .clone()
+}
+
+/// Gives plugins broadcast access to every client connected to the server
+struct SocketServerHandle {
+    io: SocketIo,
+}
+
+impl plugin_api::ServerHandle for SocketServerHandle {
+    fn broadcast_message(&self, message: &str) {
+        self.broadcast_event("server_message", serde_json::Value::String(message.to_string()));
+    }
+
+    fn broadcast_event(&self, name: &str, payload: serde_json::Value) {
+        for namespace in live_config().namespaces {
+            let namespace = namespace.path;
+            let Some(operators) = self.io.of(namespace.as_str()) else {
+                continue;
+            };
+            if let Err(e) = operators.emit(name.to_string(), &payload) {
+                log_error!(LOGGER, "SOCKET EVENT", "Failed to broadcast {} on {}: {}", name, namespace, e);
+            }
+        }
+    }
+
+    fn broadcast_to_region(&self, pool_group: &str, center: [f64; 3], radius: f64, name: &str, payload: serde_json::Value) -> usize {
+        broadcast_to_region(pool_group, center, radius, name, &payload).unwrap_or_else(|e| {
+            log_error!(LOGGER, "SOCKET EVENT", "Failed to broadcast {} to a region: {:#}", name, e);
+            0
+        })
+    }
+
+    fn plugin_data_dir(&self) -> std::path::PathBuf {
+        live_config().plugin_data_dir.into()
+    }
+
+    fn log_error(&self, category: &str, message: &str) {
+        log_error!(LOGGER, category, "{}", message);
+    }
+}
+
+//-----------------------------------------------------------------------------
+// Status page
+//-----------------------------------------------------------------------------
+
+/// Serves a small HTML status page built from live server state
+///
+/// Operators running a separate admin panel can set `status_redirect` in the
+/// server config to send browser visitors there instead.
+async fn status_page() -> Response {
+    let server_instance = SERVER.get_instance();
+    let server = server_instance.read();
+
+    if let Some(target) = &server.config.status_redirect {
+        return Redirect::temporary(target).into_response();
+    }
+
+    let uptime = server.started_at.elapsed().as_secs();
+    let page = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head><title>{name}</title></head>
+<body>
+    <h1>{name}</h1>
+    <p>Version: {version}</p>
+    <p>Players online: {players}</p>
+    <p>Uptime: {hours}h {minutes}m {seconds}s</p>
+</body>
+</html>"#,
+        name = server.config.server_name,
+        version = env!("CARGO_PKG_VERSION"),
+        players = server.total_players(),
+        hours = uptime / 3600,
+        minutes = (uptime % 3600) / 60,
+        seconds = uptime % 60,
+    );
+
+    Html(page).into_response()
+}
+
+//-----------------------------------------------------------------------------
+// Spatial broadcasting
+//-----------------------------------------------------------------------------
+
+/// Ids of the players PebbleVault has within `radius` units of `center`
+pub fn players_in_region(center: [f64; 3], radius: f64) -> Result<Vec<Uuid>> {
+    let nearby = Plugin {}
+        .query_sphere(center, radius)
+        .map_err(anyhow::Error::msg)
+        .context("Failed to query players in range")?;
+    Ok(nearby
+        .into_iter()
+        .filter(|object| object.object_type == "player")
+        .map(|object| object.uuid)
+        .collect())
+}
+
+/// Emits `event` to every connected player of `pool_group` within `radius` units of `center`
+///
+/// Positions come from PebbleVault, so only player objects stored in a region
+/// are considered. Players of other pool groups are in another game mode and
+/// never receive it. Returns the number of players the event was delivered to.
+pub fn broadcast_to_region(pool_group: &str, center: [f64; 3], radius: f64, event: &str, payload: &serde_json::Value) -> Result<usize> {
+    let nearby = players_in_region(center, radius)?;
+    let recipients = SERVER.get_instance().read().region_recipients(pool_group, nearby);
+    Ok(emit_to_players(Some(&recipients), event, payload))
+}
+
+/// Delivers a game event to plugins and other subscribers
+pub fn emit_event(event: GameEvent) {
+    plugin_api::global_bus().emit(&event);
+}
+
+/// Whether `event` should be delivered to `plugin`, given the pool groups loading it
+///
+/// Events about a player only reach plugins loaded by that player's pool
+/// group; events about no one in particular reach every plugin.
+pub fn event_reaches(plugin: &str, event: &GameEvent) -> bool {
+    SERVER.get_instance().read().event_reaches(plugin, event)
+}
+
+/// Players in the same pool group as `player_id`, including itself
+///
+/// Players in other groups are in another game mode, so nothing one player
+/// does is delivered outside this set.
+pub fn group_members(player_id: Uuid) -> HashSet<Uuid> {
+    SERVER.get_instance().read().group_members(player_id)
+}
+
+/// Players in the world in `pool_group`
+pub fn group_players(pool_group: &str) -> HashSet<Uuid> {
+    SERVER.get_instance().read().group_players(pool_group)
+}
+
+/// Pool groups the server was started with, in configuration order
+pub fn pool_groups() -> Vec<String> {
+    live_config().pool_groups().into_iter().map(|(pool_group, _)| pool_group).collect()
+}
+
+/// Sets the server up to replay an event log instead of serving players
+///
+/// The vault is kept in `data_dir` so the replay never touches the real
+/// world, nothing is recorded, and every client is admitted without its
+/// token being checked or its address being rate limited, since replayed
+/// clients carry the original sessions' tokens and all connect from one address.
+pub fn prepare_replay(data_dir: String) {
+    let mut config = live_config();
+    config.vault_data_dir = data_dir;
+    config.event_log_path = None;
+    config.require_session_token = false;
+    apply_config_reload(&config);
+    REPLAYING.store(true, Ordering::Relaxed);
+}
+
+/// Snapshot of the server config currently in effect, including hot-reloaded changes
+pub fn live_config() -> ServerConfig {
+    SERVER.get_instance().read().config.clone()
+}
+
+/// Last accepted position of a player, if it has reported one
+pub fn player_position(player_id: Uuid) -> Option<[f64; 3]> {
+    Plugin {}.get_object(player_id).ok().flatten().map(|object| object.point)
+}
+
+/// Position and health of a player that is in the world
+pub fn player_snapshot(player_id: Uuid) -> Option<([f64; 3], i32)> {
+    Plugin {}
+        .get_object(player_id)
+        .ok()
+        .flatten()
+        .map(|object| (object.point, object.custom_data.health.unwrap_or_default()))
+}
+
+/// Adds a player to the world region at `position`
+pub fn place_player(player_id: Uuid, position: [f64; 3], health: i32) -> Result<()> {
+    let vault = Plugin {};
+    let custom_data = PebbleVaultCustomData { name: player_id.to_string(), value: 0, velocity: [0.0; 3], health: Some(health) };
+    let [x, y, z] = position;
+    vault
+        .create_or_load_region(WORLD_REGION_CENTER, WORLD_REGION_RADIUS)
+        .and_then(|region_id| vault.add_object(region_id, player_id, "player", x, y, z, custom_data))
+        .map_err(anyhow::Error::msg)
+        .with_context(|| format!("Failed to place {} in the world", player_id))
+}
+
+/// Number of players in each thread pool, in thread order
+pub fn players_per_pool() -> Vec<usize> {
+    SERVER.get_instance().read().players_per_pool()
+}
+
+/// Ids of every player with a live connection
+pub fn connected_players() -> Vec<Uuid> {
+    SERVER.get_instance().read().player_sockets.read().keys().copied().collect()
+}
+
+/// Disconnects a player after sending them a `kicked` event
+///
+/// Unlike a dropped connection, the player's session is not held for a
+/// reconnect. Returns false if the player is not connected.
+pub fn kick_player(player_id: Uuid, reason: &str) -> bool {
+    let server_instance = SERVER.get_instance();
+    let socket = server_instance.read().player_sockets.read().get(&player_id).cloned();
+    let Some(socket) = socket else {
+        return false;
+    };
+
+    log_info!(LOGGER, "SOCKET NET", "Kicking {}: {}", player_id, reason);
+    let _ = socket.emit("kicked", &serde_json::json!({ "reason": reason }));
+    // The disconnect handler runs before this returns, so its pending
+    // reconnect is already recorded and can be discarded
+    let _ = socket.disconnect();
+    server_instance.read().pending_sessions.forget(player_id);
+    true
+}
+
+/// Connected players of `pool_group` that are in the world, as collidable actors
+pub fn player_actors(pool_group: &str) -> Vec<event_rep::Actor> {
+    let view_radius = live_config().view_radius;
+    let members: Vec<Uuid> = {
+        let server_instance = SERVER.get_instance();
+        let server = server_instance.read();
+        let player_sockets = server.player_sockets.read();
+        server.group_players(pool_group).into_iter().filter(|id| player_sockets.contains_key(id)).collect()
+    };
+    members
+        .into_iter()
+        .filter_map(|id| player_position(id).map(|position| player_actor(id, position, view_radius)))
+        .collect()
+}
+
+/// Pool group of a player in the world
+pub fn player_group(player_id: Uuid) -> Option<String> {
+    SERVER.get_instance().read().player_groups.read().get(&player_id).cloned()
+}
+
+/// A player at `position` as an event_rep actor, replicated to players within `view_radius`
+fn player_actor(player_id: Uuid, [x, y, z]: [f64; 3], view_radius: f64) -> event_rep::Actor {
+    event_rep::Actor {
+        name: player_id.to_string(),
+        location: Vec3D { x, y, z },
+        uuid: player_id,
+        has_collision: true,
+        collision_radius: crate::collision::PLAYER_COLLISION_RADIUS,
+        replication: true,
+        replication_distance: view_radius,
+    }
+}
+
+/// Emits an event to a single player, returning false if it is not connected
+pub fn emit_to_player(player_id: Uuid, event: &str, payload: &serde_json::Value) -> bool {
+    let server_instance = SERVER.get_instance();
+    let server_instance_read = server_instance.read();
+    let player_sockets = server_instance_read.player_sockets.read();
+
+    let Some(socket) = player_sockets.get(&player_id) else {
+        return false;
+    };
+    if let Err(e) = socket.emit(event.to_string(), payload) {
+        log_warn!(LOGGER, "SOCKET EVENT", "Failed to send {} to {}: {}", event, player_id, e);
+    }
+    true
+}
+
+/// Emits an event to the given players, or to every connected player when `players` is `None`
+///
+/// Returns the number of players the event was delivered to.
+pub fn emit_to_players(players: Option<&HashSet<Uuid>>, event: &str, payload: &serde_json::Value) -> usize {
+    let server_instance = SERVER.get_instance();
+    let server_instance_read = server_instance.read();
+    let player_sockets = server_instance_read.player_sockets.read();
+
+    player_sockets
+        .iter()
+        .filter(|(player_id, _)| players.map_or(true, |players| players.contains(player_id)))
+        .filter(|(player_id, socket)| match socket.emit(event.to_string(), payload) {
+            Ok(_) => true,
+            Err(e) => {
+                log_warn!(LOGGER, "SOCKET EVENT", "Failed to send {} to {}: {}", event, player_id, e);
+                false
+            }
+        })
+        .count()
+}
+
+//-----------------------------------------------------------------------------
+// Server startup
+//-----------------------------------------------------------------------------
+/// Creates the Socket.IO layer and spawns the configured number of thread pools
+///
+/// Every pool loads its own plugin set, so this is where plugin load errors
+/// surface. Fails if any pool could not be spawned.
+pub async fn spawn_thread_pools() -> anyhow::Result<()> {
+    let config = live_config();
+    install_authenticator(&config);
+    let max_message_bytes = config.max_message_bytes;
+    // Oversized packets are refused by the transport before reaching any handler
+    let (layer, io) = SocketIo::builder().max_payload(max_message_bytes).build_layer();
+    let _ = SOCKET_IO.set(io);
+    let _ = SOCKET_LAYER.set(layer);
+
+    let thread_count = config.num_thread_pools;
+    let groups = config.pool_groups();
+
+    log_info!(LOGGER, "SERVER", "Preparing to start {} threads for each of {} pool groups", thread_count, groups.len());
+    // Start the configured number of threads for each group of namespaces
+
+    let server_instance = &SERVER.get_instance();
+    let spawn_futures: Vec<_> = groups
+        .iter()
+        .flat_map(|(group, plugins)| (0..thread_count).map(move |_| (group, plugins)))
+        .map(|(group, plugins)| async move {
+            log_debug!(LOGGER, "SERVER", "Spawning thread for pool group {}", group);
+            let thread_id = server_instance.read().spawn_thread(group, plugins.as_deref())?;
+            log_debug!(LOGGER, "SERVER", "Thread spawned: {}", thread_id);
+            anyhow::Ok(thread_id)
+        })
+        .collect();
+
+    for result in futures::future::join_all(spawn_futures).await {
+        result.context("Failed to spawn thread")?;
+    }
+
+    log_info!(LOGGER, "SERVER", "Spawned {} threads", thread_count as usize * groups.len());
+    Ok(())
+}
+
+/// Opens PebbleVault and starts its autosave thread
+pub fn open_vault() -> anyhow::Result<()> {
+    let data_dir = live_config().vault_data_dir;
+    vault_lib::init_vault(data_dir.as_str()).map_err(anyhow::Error::msg)?;
+    log_info!(LOGGER, "PEBBLEVAULT", "Storing vault data in {}", data_dir);
+    // The interval must be in place before the autosave thread first checks it
+    Plugin {}.set_autosave_interval(live_config().vault_autosave_interval_secs);
+    <Plugin as vault_lib::PluginConstruct>::new(HashMap::new());
+    Plugin {}.set_world_bounds(live_config().world_bounds);
+    Plugin {}
+        .create_or_load_region(WORLD_REGION_CENTER, WORLD_REGION_RADIUS)
+        .map_err(anyhow::Error::msg)
+        .context("Failed to open the world region")?;
+    // Preloading only saves first-query latency, so it runs in the background
+    // and each region that fails to load is logged and skipped by preload_regions
+    let preload = live_config().preload_regions;
+    if !preload.is_empty() {
+        std::thread::spawn(move || {
+            let _ = Plugin {}.preload_regions(&preload);
+        });
+    }
+    Ok(())
+}
+
+/// Runs every plugin's `on_shutdown` so plugins can save their state before exit
+///
+/// Only the first call runs the hooks, so a Ctrl+C during shutdown doesn't run them twice.
+pub fn shutdown_plugins() {
+    static SHUT_DOWN: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+    if SHUT_DOWN.swap(true, std::sync::atomic::Ordering::SeqCst) {
+        return;
+    }
+    for hook in SHUTDOWN_HOOKS.get().into_iter().flatten() {
+        if std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| hook.shutdown())).is_err() {
+            log_error!(LOGGER, "PLUGIN", "Plugin {} panicked during on_shutdown", hook.name);
+        }
+    }
+}
+
+/// Registers socket namespaces, starts background tasks and binds the listener
+///
+/// Must run after `spawn_thread_pools`.
+pub async fn bind(address: &str) -> anyhow::Result<tokio::net::TcpListener> {
+    let io = SOCKET_IO.get().context("Socket.IO layer has not been created")?;
+
+    // Configure socket namespaces, each routed to its own group of pools
+    for namespace in live_config().namespaces {
+        let pool_group = namespace.pool_group.clone();
+        io.ns(namespace.path.clone(), move |socket: SocketRef, data: Data<serde_json::Value>| {
+            on_connect(socket, data, pool_group.clone())
+        });
+        log_info!(LOGGER, "SOCKET NET", "Serving namespace {} from pool group {}", namespace.path, namespace.pool_group);
+    }
+    io.ns(admin::ADMIN_NAMESPACE, admin::on_connect);
+
+    // Every pool constructs its own `Plugin` instance, but plugins are unit
+    // structs that keep their state in statics, so all copies of a plugin
+    // drive the same state. Ticking every copy would advance it once per pool,
+    // so each plugin enabled in any group ticks, handles game events and shuts
+    // down exactly once, through the copy of the first pool that loaded it.
+    let (tick_hooks, tick_rate_hz) = {
+        let server_instance = SERVER.get_instance();
+        let server = server_instance.read();
+        let managers: Vec<Arc<HorizonThread>> = server
+            .config
+            .pool_groups()
+            .into_iter()
+            .filter_map(|(group, _)| server.group_threads(&group).into_iter().next())
+            .collect();
+        let tick_hooks = game_logic::one_per_plugin(managers.iter().flat_map(|thread| thread.plugin_manager.tick_hooks()), |hook| &hook.name);
+        let event_hooks = game_logic::one_per_plugin(managers.iter().flat_map(|thread| thread.plugin_manager.event_hooks()), |hook| &hook.name);
+        let shutdown_hooks =
+            game_logic::one_per_plugin(managers.iter().flat_map(|thread| thread.plugin_manager.shutdown_hooks()), |hook| &hook.name);
+        let _ = SHUTDOWN_HOOKS.set(shutdown_hooks);
+        let timeout = std::time::Duration::from_millis(server.config.plugin_event_timeout_ms);
+        event_dispatch::subscribe(plugin_api::global_bus(), event_hooks, timeout);
+        (tick_hooks, server.config.tick_rate_hz)
+    };
+    tokio::spawn(game_logic::run(tick_hooks, tick_rate_hz));
+    ServerConfig::watch(config::config_path(), apply_config_reload);
+
+    // Players that never came back within the grace period leave the world for good
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(SESSION_SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            let expired = SERVER.get_instance().read().evict_expired_sessions();
+            for player_id in expired {
+                log_info!(LOGGER, "SOCKET NET", "Reconnect window for {} expired", player_id);
+                player_data::forget(player_id);
+                if let Err(e) = vault_lib::nonblocking::remove_object(player_id).await {
+                    log_debug!(LOGGER, "SOCKET NET", "No world object to remove for {}: {}", player_id, e);
+                }
+            }
+        }
+    });
+
+    // Addresses that stopped connecting don't need a bucket any more
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(CONNECTION_LIMITER_SWEEP_INTERVAL);
+        loop {
+            interval.tick().await;
+            CONNECTION_LIMITER.lock().evict_idle(CONNECTION_LIMITER_SWEEP_INTERVAL);
+        }
+    });
+
+    log_info!(LOGGER, "SOCKET NET", "Starting server on {}", address);
+    tokio::net::TcpListener::bind(address)
+        .await
+        .context(format!("Failed to bind to {}", address))
+}
+
+/// Serves HTTP and Socket.IO connections on `listener` until the server stops
+pub async fn serve(listener: tokio::net::TcpListener) -> anyhow::Result<()> {
+    let layer = SOCKET_LAYER.get().context("Socket.IO layer has not been created")?.clone();
+
+    log_info!(LOGGER, "SOCKET NET", "Accepting socket connections");
+    // Build the application with routes
+    let app = Router::new()
+        .route("/", get(status_page))
+        .layer(layer);
+
+    // Connect info lets socket handlers see the client's address
+    axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>())
         .await
         .context("Failed to start server")?;
     Ok(())
 }
+
+/// Real Socket.IO connections for tests that check what players receive
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::*;
+    use futures::FutureExt;
+    use rust_socketio::asynchronous::{Client, ClientBuilder};
+    use rust_socketio::{Event, Payload};
+    use std::time::Duration;
+
+    /// Starts a server whose connections join the world as the player and
+    /// pool group named in their connect payload, and returns its url
+    ///
+    /// Players are registered the way `accept_connection` registers them, so
+    /// everything that sends to players by id reaches their sockets.
+    pub(crate) async fn serve_players() -> String {
+        let (layer, io) = SocketIo::new_layer();
+        io.ns("/", |socket: SocketRef, Data(auth): Data<serde_json::Value>| {
+            let Some(player_id) = auth["player_id"].as_str().and_then(|id| Uuid::parse_str(id).ok()) else {
+                let _ = socket.disconnect();
+                return;
+            };
+            let pool_group = auth["pool_group"].as_str().unwrap_or("default").to_string();
+            {
+                let server_instance = SERVER.get_instance();
+                let server = server_instance.read();
+                server.player_sockets.write().insert(player_id, socket.clone());
+                server.player_groups.write().insert(player_id, pool_group);
+            }
+            socket.on_disconnect(move |_: SocketRef| {
+                let server_instance = SERVER.get_instance();
+                let server = server_instance.read();
+                server.player_sockets.write().remove(&player_id);
+                server.player_groups.write().remove(&player_id);
+            });
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, axum::Router::new().layer(layer)).await });
+        url
+    }
+
+    /// A player connected to a server started by `serve_players`, and every event it received
+    pub(crate) struct TestPlayer {
+        pub(crate) id: Uuid,
+        received: Arc<parking_lot::Mutex<Vec<(String, serde_json::Value)>>>,
+        _client: Client,
+    }
+
+    impl TestPlayer {
+        /// Connects a new player of `pool_group`, returning once the server has registered it
+        pub(crate) async fn connect(url: &str, pool_group: &str) -> TestPlayer {
+            let id = Uuid::new_v4();
+            let received = Arc::new(parking_lot::Mutex::new(Vec::new()));
+            let client = ClientBuilder::new(url)
+                .auth(serde_json::json!({ "player_id": id.to_string(), "pool_group": pool_group }))
+                .on_any({
+                    let received = Arc::clone(&received);
+                    move |event: Event, payload: Payload, _: Client| {
+                        if let Payload::Text(values) = payload {
+                            received.lock().push((String::from(event), values.into_iter().next().unwrap_or_default()));
+                        }
+                        async {}.boxed()
+                    }
+                })
+                .connect()
+                .await
+                .expect("test player failed to connect");
+
+            for _ in 0..100 {
+                if SERVER.get_instance().read().player_sockets.read().contains_key(&id) {
+                    return TestPlayer { id, received, _client: client };
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            panic!("test player {} was never registered", id);
+        }
+
+        /// Payloads of every `event` received so far
+        pub(crate) fn received(&self, event: &str) -> Vec<serde_json::Value> {
+            self.received.lock().iter().filter(|(name, _)| name == event).map(|(_, payload)| payload.clone()).collect()
+        }
+
+        /// Payloads of every `event` received, waiting up to two seconds for the first one
+        pub(crate) async fn wait_for(&self, event: &str) -> Vec<serde_json::Value> {
+            for _ in 0..200 {
+                let received = self.received(event);
+                if !received.is_empty() {
+                    return received;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_player_is_admitted_by_only_one_connection_at_a_time() {
+        let server = HorizonServer::new().unwrap();
+        let player_id = Uuid::new_v4();
+
+        let winners = std::thread::scope(|scope| {
+            let racers: Vec<_> = (0..8).map(|_| scope.spawn(|| server.reserve_player(player_id))).collect();
+            racers.into_iter().map(|racer| racer.join().unwrap()).filter(|won| *won).count()
+        });
+        assert_eq!(winners, 1);
+
+        server.release_player(player_id);
+        assert!(server.reserve_player(player_id));
+        assert!(server.reserve_player(Uuid::new_v4()));
+    }
+
+    #[test]
+    fn players_and_events_stay_in_their_pool_group() {
+        let server = HorizonServer::new().unwrap();
+        let (pvp_a, pvp_b, pve) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        server.player_groups.write().extend([(pvp_a, "pvp".to_string()), (pvp_b, "pvp".to_string()), (pve, "pve".to_string())]);
+        server.group_plugins.write().extend([
+            ("pvp".to_string(), HashSet::from(["arena".to_string()])),
+            ("pve".to_string(), HashSet::from(["quests".to_string()])),
+        ]);
+
+        assert_eq!(server.group_members(pvp_a), HashSet::from([pvp_a, pvp_b]));
+        assert_eq!(server.group_members(pve), HashSet::from([pve]));
+        assert!(server.group_members(Uuid::new_v4()).is_empty());
+
+        let moved = GameEvent::PlayerMoved { player: pve, from: [0.0; 3], to: [1.0; 3] };
+        assert!(server.event_reaches("quests", &moved));
+        assert!(!server.event_reaches("arena", &moved));
+
+        let collision = GameEvent::custom("collision", serde_json::json!({ "a": pvp_a, "b": pvp_b, "pool_group": "pvp" }));
+        assert!(server.event_reaches("arena", &collision));
+        assert!(!server.event_reaches("quests", &collision));
+
+        // Events about no one in particular reach every plugin
+        let announcement = GameEvent::custom("server_announcement", serde_json::Value::Null);
+        assert!(server.event_reaches("arena", &announcement));
+        assert!(server.event_reaches("quests", &announcement));
+    }
+
+    #[test]
+    fn region_broadcasts_reach_only_group_players_in_range() {
+        let vault = vault_lib::test_support::vault();
+        let center = vault_lib::test_support::unique_center();
+        let region_id = vault.create_or_load_region(center, 1000.0).unwrap();
+        let [x, y, z] = center;
+        let (near, edge, far, other_group) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        for (player_id, offset) in [(near, 5.0), (edge, 49.0), (far, 500.0), (other_group, 1.0)] {
+            vault.add_object(region_id, player_id, "player", x + offset, y, z, vault_lib::test_support::data("player")).unwrap();
+        }
+        vault.add_object(region_id, Uuid::new_v4(), "item", x, y, z, vault_lib::test_support::data("crate")).unwrap();
+
+        let server = HorizonServer::new().unwrap();
+        server.player_groups.write().extend([
+            (near, "pvp".to_string()),
+            (edge, "pvp".to_string()),
+            (far, "pvp".to_string()),
+            (other_group, "pve".to_string()),
+        ]);
+
+        let nearby = players_in_region(center, 50.0).unwrap();
+        assert_eq!(server.region_recipients("pvp", nearby.clone()), HashSet::from([near, edge]));
+        assert_eq!(server.region_recipients("pve", nearby), HashSet::from([other_group]));
+    }
+
+    const NO_PLUGINS: &[String] = &[];
+
+    #[tokio::test]
+    async fn busy_pools_are_still_counted() {
+        let server = HorizonServer::new().unwrap();
+        let (quiet, busy) = (
+            Arc::new(HorizonThread::new("count", Some(NO_PLUGINS)).unwrap()),
+            Arc::new(HorizonThread::new("count", Some(NO_PLUGINS)).unwrap()),
+        );
+        server.threads.write().extend([Arc::clone(&quiet), Arc::clone(&busy)]);
+        quiet.player_count.store(2, Ordering::Release);
+        busy.player_count.store(3, Ordering::Release);
+
+        let _held = busy.players.lock().await;
+
+        assert_eq!(server.players_per_pool(), vec![2, 3]);
+        assert_eq!(server.total_players(), 5);
+    }
+
+    #[tokio::test]
+    async fn the_last_thread_of_a_group_is_not_drained() {
+        let only = Arc::new(HorizonThread::new("drain-last", Some(NO_PLUGINS)).unwrap());
+
+        assert!(only.drain_to(&[Arc::clone(&only)]).await.is_err());
+        // A failed drain leaves the thread taking players again
+        assert!(!only.draining.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn a_thread_drains_into_the_rest_of_its_group() {
+        let first = Arc::new(HorizonThread::new("drain-pair", Some(NO_PLUGINS)).unwrap());
+        let second = Arc::new(HorizonThread::new("drain-pair", Some(NO_PLUGINS)).unwrap());
+        let group = [Arc::clone(&first), Arc::clone(&second)];
+
+        assert_eq!(first.drain_to(&group).await.unwrap(), 0);
+        assert!(first.draining.load(Ordering::SeqCst));
+
+        // With the first thread retired, the second is now the last of the group
+        assert!(second.drain_to(&group).await.is_err());
+        assert!(!second.draining.load(Ordering::SeqCst));
+        assert!(first.drain_to(&group).await.is_err());
+    }
+
+    #[test]
+    fn a_flooding_client_is_throttled_and_then_disconnected() {
+        let mut state = InboundState::new();
+
+        let verdicts: Vec<Inbound> = (0..8).map(|_| state.check(1.0, 3.0, 4)).collect();
+
+        assert_eq!(verdicts[..3], [Inbound::Admit, Inbound::Admit, Inbound::Admit]);
+        assert_eq!(verdicts[3..6], [Inbound::Drop, Inbound::Drop, Inbound::Drop]);
+        assert_eq!(verdicts[6], Inbound::Disconnect);
+        assert_eq!(state.dropped, 5);
+    }
+
+    #[test]
+    fn a_client_that_slows_down_is_not_disconnected() {
+        let mut state = InboundState::new();
+        assert_eq!(state.check(100.0, 1.0, 2), Inbound::Admit);
+        assert_eq!(state.check(100.0, 1.0, 2), Inbound::Drop);
+
+        std::thread::sleep(std::time::Duration::from_millis(20));
+
+        assert_eq!(state.check(100.0, 1.0, 2), Inbound::Admit);
+        assert_eq!(state.dropped, 0);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn oversized_payloads_never_reach_a_handler() {
+        static SMALL: AtomicUsize = AtomicUsize::new(0);
+        static BIG: AtomicUsize = AtomicUsize::new(0);
+
+        // Built the way spawn_thread_pools builds the real layer
+        let (layer, io) = SocketIo::builder().max_payload(1024).build_layer();
+        io.ns("/", |socket: SocketRef| {
+            socket.on("small", || {
+                SMALL.fetch_add(1, Ordering::SeqCst);
+            });
+            socket.on("big", || {
+                BIG.fetch_add(1, Ordering::SeqCst);
+            });
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, Router::new().route("/", get(|| async { "" })).layer(layer)).await });
+
+        let client = rust_socketio::asynchronous::ClientBuilder::new(url).connect().await.unwrap();
+        client.emit("small", serde_json::json!("hello")).await.unwrap();
+        let _ = client.emit("big", serde_json::json!("x".repeat(64 * 1024))).await;
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+        assert_eq!(SMALL.load(Ordering::SeqCst), 1);
+        assert_eq!(BIG.load(Ordering::SeqCst), 0);
+    }
+}
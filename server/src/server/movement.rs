@@ -0,0 +1,97 @@
+//-----------------------------------------------------------------------------
+// Server-Authoritative Movement Validation
+//   - Rejects client-reported moves that exceed the allowed speed
+//   - Returns a corrected position so the client can be snapped back
+//-----------------------------------------------------------------------------
+
+use horizon_data_types::Vec3D;
+
+/// Default distance allowed on top of `max_speed * dt` to absorb jitter
+pub const DEFAULT_MOVEMENT_TOLERANCE: f64 = 0.5;
+
+/// Longest interval a single move is credited with, in seconds
+///
+/// Without a cap, a client could stand still for a while and then cover the
+/// whole distance it "saved up" in one teleport.
+pub const MAX_MOVE_INTERVAL_SECS: f64 = 1.0;
+
+/// Outcome of validating a client-reported move
+pub enum MovementVerdict {
+    /// The move is plausible and may be applied
+    Accept,
+    /// The move is impossible at the allowed speed; the player should be
+    /// moved back to `corrected_position`
+    Reject { corrected_position: Vec3D },
+}
+
+/// Checks whether moving from `old` to `new` in `dt` seconds is possible at `max_speed`
+///
+/// The allowed distance is `max_speed * dt` plus `tolerance`, with `dt` capped
+/// at `MAX_MOVE_INTERVAL_SECS`. Callers pass the `movement_tolerance` of the
+/// live server config. Non-finite coordinates are always rejected.
+pub fn validate_movement(old: Vec3D, new: Vec3D, dt: f64, max_speed: f64, tolerance: f64) -> MovementVerdict {
+    if !(new.x.is_finite() && new.y.is_finite() && new.z.is_finite()) {
+        return MovementVerdict::Reject { corrected_position: old };
+    }
+
+    let dx = new.x - old.x;
+    let dy = new.y - old.y;
+    let dz = new.z - old.z;
+    let distance = (dx * dx + dy * dy + dz * dz).sqrt();
+    let allowed = max_speed * dt.clamp(0.0, MAX_MOVE_INTERVAL_SECS) + tolerance;
+
+    if distance <= allowed {
+        MovementVerdict::Accept
+    } else {
+        MovementVerdict::Reject { corrected_position: old }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: f64) -> Vec3D {
+        Vec3D { x, y: 0.0, z: 0.0 }
+    }
+
+    fn accepted(verdict: MovementVerdict) -> bool {
+        matches!(verdict, MovementVerdict::Accept)
+    }
+
+    #[test]
+    fn legal_step_is_accepted() {
+        assert!(accepted(validate_movement(at(0.0), at(0.5), 0.1, 10.0, 0.5)));
+    }
+
+    #[test]
+    fn borderline_step_is_accepted() {
+        // 10 units/s for 0.5s plus 0.5 of tolerance
+        assert!(accepted(validate_movement(at(0.0), at(5.5), 0.5, 10.0, 0.5)));
+        assert!(!accepted(validate_movement(at(0.0), at(5.51), 0.5, 10.0, 0.5)));
+    }
+
+    #[test]
+    fn teleport_is_rejected_back_to_the_old_position() {
+        match validate_movement(at(3.0), at(1000.0), 0.1, 10.0, 0.5) {
+            MovementVerdict::Reject { corrected_position } => assert_eq!(corrected_position.x, 3.0),
+            MovementVerdict::Accept => panic!("teleport was accepted"),
+        }
+    }
+
+    #[test]
+    fn idle_time_does_not_allow_a_teleport() {
+        assert!(!accepted(validate_movement(at(0.0), at(500.0), 60.0, 10.0, 0.5)));
+    }
+
+    #[test]
+    fn the_given_tolerance_is_allowed_on_top_of_the_speed() {
+        assert!(accepted(validate_movement(at(0.0), at(50.0), 0.0, 0.0, 100.0)));
+        assert!(!accepted(validate_movement(at(0.0), at(50.0), 0.0, 0.0, 49.0)));
+    }
+
+    #[test]
+    fn non_finite_positions_are_rejected() {
+        assert!(!accepted(validate_movement(at(0.0), at(f64::NAN), 0.1, 10.0, 0.5)));
+    }
+}
@@ -0,0 +1,111 @@
+//-----------------------------------------------------------------------------
+// Thread Pool Player Sets
+//   - The players a thread pool holds, keyed by player id
+//   - Keeps a shared counter in step with every insertion and removal, so
+//     load balancing never has to wait on a busy pool's lock
+//-----------------------------------------------------------------------------
+
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Players held by one thread pool
+///
+/// Reads go through `Deref` to the underlying map. Every change goes through
+/// the methods below, which update the counter returned by `counter`.
+pub struct PoolPlayers<P> {
+    players: HashMap<Uuid, P>,
+    count: Arc<AtomicUsize>,
+}
+
+impl<P> PoolPlayers<P> {
+    pub fn new() -> Self {
+        Self {
+            players: HashMap::new(),
+            count: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Number of players in the pool, readable without holding its lock
+    pub fn counter(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.count)
+    }
+
+    pub fn insert(&mut self, player_id: Uuid, player: P) -> Option<P> {
+        let previous = self.players.insert(player_id, player);
+        self.sync();
+        previous
+    }
+
+    pub fn remove(&mut self, player_id: &Uuid) -> Option<P> {
+        let removed = self.players.remove(player_id);
+        self.sync();
+        removed
+    }
+
+    /// Takes every player out of the pool
+    pub fn drain(&mut self) -> Vec<(Uuid, P)> {
+        let drained = self.players.drain().collect();
+        self.sync();
+        drained
+    }
+
+    pub fn extend(&mut self, players: impl IntoIterator<Item = (Uuid, P)>) {
+        self.players.extend(players);
+        self.sync();
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (&Uuid, &mut P)> {
+        self.players.iter_mut()
+    }
+
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut P> {
+        self.players.values_mut()
+    }
+
+    fn sync(&self) {
+        self.count.store(self.players.len(), Ordering::Release);
+    }
+}
+
+impl<P> Deref for PoolPlayers<P> {
+    type Target = HashMap<Uuid, P>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.players
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count(pool: &PoolPlayers<u32>) -> usize {
+        pool.counter().load(Ordering::Acquire)
+    }
+
+    #[test]
+    fn counter_follows_every_change() {
+        let mut pool = PoolPlayers::new();
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+
+        pool.insert(a, 1);
+        pool.insert(b, 2);
+        pool.insert(a, 3);
+        assert_eq!(count(&pool), 2);
+
+        assert_eq!(pool.remove(&a), Some(3));
+        assert_eq!(pool.remove(&a), None);
+        assert_eq!(count(&pool), 1);
+
+        let drained = pool.drain();
+        assert_eq!(drained.len(), 1);
+        assert_eq!(count(&pool), 0);
+
+        pool.extend(drained);
+        assert_eq!(count(&pool), 1);
+        assert_eq!(pool.len(), 1);
+    }
+}
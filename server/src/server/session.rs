@@ -0,0 +1,110 @@
+//-----------------------------------------------------------------------------
+// Session Resumption
+//   - Holds dropped players for a grace period so they can reconnect
+//   - A held player can be taken back by exactly one connection
+//-----------------------------------------------------------------------------
+
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Default lifetime of the session tokens handed to clients, in seconds
+pub const DEFAULT_SESSION_TOKEN_TTL_SECS: u64 = 24 * 60 * 60;
+
+/// Dropped players waiting to be resumed, keyed by player id
+pub struct PendingSessions {
+    disconnected_at: Mutex<HashMap<Uuid, Instant>>,
+}
+
+impl PendingSessions {
+    pub fn new() -> Self {
+        Self { disconnected_at: Mutex::new(HashMap::new()) }
+    }
+
+    /// Holds `player_id` for a reconnect, starting its grace period now
+    pub fn hold(&self, player_id: Uuid) {
+        self.disconnected_at.lock().insert(player_id, Instant::now());
+    }
+
+    /// Claims a held player if it dropped no more than `grace` ago
+    ///
+    /// The player is removed in the same step, so when two connections race
+    /// to resume the same session only one of them gets it. Players past
+    /// their grace period stay held until `evict_expired` cleans them up.
+    pub fn take(&self, player_id: Uuid, grace: Duration) -> bool {
+        let mut disconnected_at = self.disconnected_at.lock();
+        match disconnected_at.get(&player_id) {
+            Some(at) if at.elapsed() <= grace => {
+                disconnected_at.remove(&player_id);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Drops every player held for longer than `grace` and returns their ids
+    pub fn evict_expired(&self, grace: Duration) -> Vec<Uuid> {
+        let mut expired = Vec::new();
+        self.disconnected_at.lock().retain(|player_id, at| {
+            let keep = at.elapsed() <= grace;
+            if !keep {
+                expired.push(*player_id);
+            }
+            keep
+        });
+        expired
+    }
+
+    /// Stops holding a player without waiting for its grace period
+    pub fn forget(&self, player_id: Uuid) {
+        self.disconnected_at.lock().remove(&player_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GRACE: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn reconnecting_in_the_window_resumes_the_player() {
+        let sessions = PendingSessions::new();
+        let player_id = Uuid::new_v4();
+        sessions.hold(player_id);
+
+        assert!(sessions.take(player_id, GRACE));
+        assert!(sessions.evict_expired(Duration::ZERO).is_empty());
+    }
+
+    #[test]
+    fn a_session_is_resumed_only_once() {
+        let sessions = PendingSessions::new();
+        let player_id = Uuid::new_v4();
+        sessions.hold(player_id);
+
+        let winners = std::thread::scope(|scope| {
+            let racers: Vec<_> = (0..8).map(|_| scope.spawn(|| sessions.take(player_id, GRACE))).collect();
+            racers.into_iter().map(|racer| racer.join().unwrap()).filter(|won| *won).count()
+        });
+        assert_eq!(winners, 1);
+    }
+
+    #[test]
+    fn reconnecting_too_late_does_not_resume() {
+        let sessions = PendingSessions::new();
+        let player_id = Uuid::new_v4();
+        sessions.hold(player_id);
+        std::thread::sleep(Duration::from_millis(5));
+
+        assert!(!sessions.take(player_id, Duration::from_millis(1)));
+        assert_eq!(sessions.evict_expired(Duration::from_millis(1)), vec![player_id]);
+        assert!(!sessions.take(player_id, GRACE));
+    }
+
+    #[test]
+    fn unknown_players_are_not_resumed() {
+        assert!(!PendingSessions::new().take(Uuid::new_v4(), GRACE));
+    }
+}
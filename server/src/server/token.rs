@@ -0,0 +1,174 @@
+//-----------------------------------------------------------------------------
+// Session Tokens
+//   - HMAC-SHA256 signed tokens carrying a player id and an expiry
+//   - Signed with the server secret from config, or a per-process secret
+//   - Back both session resumption and the connection auth handshake
+//-----------------------------------------------------------------------------
+
+use super::live_config;
+use crate::LOGGER;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use crate::log_warn;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Key tokens are signed with
+///
+/// Without a configured `session_secret`, a random key is generated at
+/// startup, so tokens do not survive a restart.
+static SECRET: Lazy<Vec<u8>> = Lazy::new(|| {
+    match live_config().session_secret {
+        Some(secret) => secret.into_bytes(),
+        None => {
+            log_warn!(LOGGER, "TOKEN", "No session_secret configured, session tokens will not survive a restart");
+            [Uuid::new_v4().into_bytes(), Uuid::new_v4().into_bytes()].concat()
+        }
+    }
+});
+
+/// What a session token asserts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Claims {
+    pub player_id: Uuid,
+    /// Unix time in seconds after which the token is no longer valid
+    pub expires_at: u64,
+}
+
+/// Why a token was not accepted
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenError {
+    /// The token is not of the form `payload.signature`
+    Malformed,
+    /// The signature does not match the payload
+    BadSignature,
+    /// The token was valid but its expiry has passed
+    Expired,
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenError::Malformed => write!(f, "Token is malformed"),
+            TokenError::BadSignature => write!(f, "Token signature is invalid"),
+            TokenError::Expired => write!(f, "Token has expired"),
+        }
+    }
+}
+
+impl std::error::Error for TokenError {}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or(0)
+}
+
+fn mac(key: &[u8]) -> HmacSha256 {
+    HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length")
+}
+
+/// Issues a token for `player_id` that stays valid for `ttl`
+pub fn issue_token(player_id: Uuid, ttl: Duration) -> String {
+    let claims = Claims { player_id, expires_at: unix_now().saturating_add(ttl.as_secs()) };
+    sign(&claims, &SECRET)
+}
+
+/// Checks a token's signature and expiry and returns its claims
+pub fn verify_token(token: &str) -> Result<Claims, TokenError> {
+    verify(token, &SECRET, unix_now())
+}
+
+/// Encodes and signs claims with `key`
+fn sign(claims: &Claims, key: &[u8]) -> String {
+    let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims).expect("Claims always serialize"));
+    let mut signer = mac(key);
+    signer.update(payload.as_bytes());
+    let signature = URL_SAFE_NO_PAD.encode(signer.finalize().into_bytes());
+    format!("{}.{}", payload, signature)
+}
+
+/// Verifies a token signed with `key`, treating `now` as the current Unix time
+fn verify(token: &str, key: &[u8], now: u64) -> Result<Claims, TokenError> {
+    let (payload, signature) = token.split_once('.').ok_or(TokenError::Malformed)?;
+    let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| TokenError::Malformed)?;
+
+    // verify_slice compares in constant time
+    let mut verifier = mac(key);
+    verifier.update(payload.as_bytes());
+    verifier.verify_slice(&signature).map_err(|_| TokenError::BadSignature)?;
+
+    let payload = URL_SAFE_NO_PAD.decode(payload).map_err(|_| TokenError::Malformed)?;
+    let claims: Claims = serde_json::from_slice(&payload).map_err(|_| TokenError::Malformed)?;
+    if now > claims.expires_at {
+        return Err(TokenError::Expired);
+    }
+    Ok(claims)
+}
+
+/// Authenticator that accepts session tokens issued by this server
+///
+/// Installed when `require_session_token` is set in the server config, so
+/// every connection must present a valid, unexpired token. Clients get one
+/// in the `session` event each time they are admitted.
+pub struct SessionTokenAuthenticator;
+
+impl super::auth::Authenticator for SessionTokenAuthenticator {
+    fn verify(&self, token: &str) -> Result<super::auth::PlayerIdentity, super::auth::AuthError> {
+        let claims = verify_token(token).map_err(|e| super::auth::AuthError::Rejected(e.to_string()))?;
+        Ok(super::auth::PlayerIdentity { player_id: Some(claims.player_id), name: None })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::auth::{AuthError, Authenticator};
+
+    const KEY: &[u8] = b"test secret";
+    const NOW: u64 = 1_700_000_000;
+
+    fn claims(expires_at: u64) -> Claims {
+        Claims { player_id: Uuid::new_v4(), expires_at }
+    }
+
+    #[test]
+    fn a_valid_token_returns_its_claims() {
+        let claims = claims(NOW + 60);
+        assert_eq!(verify(&sign(&claims, KEY), KEY, NOW), Ok(claims));
+    }
+
+    #[test]
+    fn an_expired_token_is_rejected() {
+        let token = sign(&claims(NOW - 1), KEY);
+        assert_eq!(verify(&token, KEY, NOW), Err(TokenError::Expired));
+    }
+
+    #[test]
+    fn a_token_with_a_flipped_byte_is_rejected() {
+        let mut token = sign(&claims(NOW + 60), KEY).into_bytes();
+        token[3] = if token[3] == b'A' { b'B' } else { b'A' };
+        let token = String::from_utf8(token).unwrap();
+
+        assert_eq!(verify(&token, KEY, NOW), Err(TokenError::BadSignature));
+        assert_eq!(verify(&sign(&claims(NOW + 60), b"other secret"), KEY, NOW), Err(TokenError::BadSignature));
+        assert_eq!(verify("no separator", KEY, NOW), Err(TokenError::Malformed));
+    }
+
+    #[test]
+    fn issued_tokens_authenticate_their_player() {
+        let player_id = Uuid::new_v4();
+        let token = issue_token(player_id, Duration::from_secs(60));
+
+        let identity = SessionTokenAuthenticator.verify(&token).unwrap();
+        assert_eq!(identity.player_id, Some(player_id));
+
+        let tampered = format!("{}x", token);
+        assert!(matches!(SessionTokenAuthenticator.verify(&tampered), Err(AuthError::Rejected(_))));
+    }
+}
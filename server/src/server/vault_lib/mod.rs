@@ -1,18 +1,487 @@
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
 pub use horizon_plugin_api::{Plugin, Pluginstate, LoadedPlugin};
 use serde::{Serialize, Deserialize};
 use PebbleVault::{VaultManager, SpatialObject, VaultRegion};
-use std::sync::{Arc, Mutex};
+use rstar::AABB;
+use std::sync::{Arc, Mutex, Once, RwLock};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
 use uuid::Uuid;
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
+use crate::utilities::logging::{Subsystem, SubsystemLogger};
+
+pub mod nonblocking;
+
+const LOG: SubsystemLogger = Subsystem::logger("PEBBLEVAULT");
+
+/// Data directory used when neither `init_vault` nor `PEBBLEVAULT_DATA_DIR` set one
+pub const DEFAULT_VAULT_DATA_DIR: &str = "./pv-horizon-plugin-data";
+
+/// Environment variable that overrides the default data directory
+pub const VAULT_DATA_DIR_ENV: &str = "PEBBLEVAULT_DATA_DIR";
+
+/// Data directory chosen by `init_vault`
+static VAULT_DATA_DIR: OnceCell<String> = OnceCell::new();
+
+/// Vault opened by `init_vault`, handed over to `VAULT_MANAGER` on first use
+static OPENED_VAULT: Mutex<Option<VaultManager<PebbleVaultCustomData>>> = Mutex::new(None);
 
 /// VaultManager instance for the PebbleVault plugin
-static VAULT_MANAGER: Lazy<Arc<Mutex<VaultManager<PebbleVaultCustomData>>>> = 
+///
+/// Takes over the vault opened by `init_vault`. If it is used before
+/// `init_vault` was called, it opens one in `vault_data_dir_from_env`.
+///
+/// The lock only guards the region map: adding or dropping a region takes it
+/// for writing, everything else takes it for reading and then locks just the
+/// regions it touches, so work on different regions runs in parallel. Readers
+/// keep the map locked until they are done with its regions, so a region can't
+/// be unloaded or deleted between being looked up and being written.
+static VAULT_MANAGER: Lazy<Arc<RwLock<VaultManager<PebbleVaultCustomData>>>> = 
     Lazy::new(|| {
-        let vault_manager = VaultManager::new("./pv-horizon-plugin-data").expect("Failed to create VaultManager");
-        Arc::new(Mutex::new(vault_manager))
+        let opened = OPENED_VAULT.lock().unwrap().take();
+        let vault_manager = opened.unwrap_or_else(|| {
+            let data_dir = VAULT_DATA_DIR.get_or_init(vault_data_dir_from_env);
+            VaultManager::new(data_dir).expect("Failed to create VaultManager")
+        });
+
+        // Regions the manager loaded while opening never went through create_or_load_region
+        let loaded: Vec<RegionBounds> = vault_manager
+            .regions
+            .iter()
+            .map(|(region_id, region)| {
+                let region = region.lock().unwrap();
+                RegionBounds { region_id: *region_id, center: region.center, radius: region.radius }
+            })
+            .collect();
+        for bounds in loaded {
+            if let Err(e) = seed_region(&vault_manager, bounds) {
+                LOG.error(format_args!("Failed to index region {}: {}", bounds.region_id, e));
+            }
+        }
+        Arc::new(RwLock::new(vault_manager))
     });
 
+/// A loaded region of a region map the caller holds locked
+fn loaded_region(
+    vault_manager: &VaultManager<PebbleVaultCustomData>,
+    region_id: Uuid,
+) -> Result<&Arc<Mutex<VaultRegion<PebbleVaultCustomData>>>, PebbleVaultError> {
+    vault_manager.regions.get(&region_id).ok_or(PebbleVaultError::RegionNotFound(region_id))
+}
+
+/// Takes an object out of a locked region's tree
+fn take_object(region: &mut VaultRegion<PebbleVaultCustomData>, object_id: Uuid) -> Option<SpatialObject<PebbleVaultCustomData>> {
+    let object = region.rtree.iter().find(|object| object.uuid == object_id).cloned()?;
+    region.rtree.remove(&object)
+}
+
+/// Puts an object into a locked region and records it in the object index and type counts
+///
+/// Objects are only written to region trees through `place_object` and
+/// `evict_object`, so `OBJECT_REGIONS` and `TYPE_COUNTS` always agree with
+/// the trees. The manager itself only keeps the trees and its persistent
+/// store, which `persist_region` writes from the trees, so writing a tree
+/// directly leaves nothing of the manager's behind. The exceptions are
+/// `remove_object` and `delete_region`, which go through the manager to reach
+/// the persistent store and update the indexes themselves.
+///
+/// An object whose UUID is already in any region is refused with
+/// `ObjectExists`. The check and the insertion happen under one lock of the
+/// object index, so two writers can't both place the same UUID.
+fn place_object(region_id: Uuid, region: &mut VaultRegion<PebbleVaultCustomData>, object: SpatialObject<PebbleVaultCustomData>) -> Result<(), PebbleVaultError> {
+    match OBJECT_REGIONS.lock().unwrap().entry(object.uuid) {
+        Entry::Occupied(_) => return Err(PebbleVaultError::ObjectExists(object.uuid)),
+        Entry::Vacant(entry) => entry.insert(region_id),
+    };
+    count_added(region_id, &object.object_type);
+    region.rtree.insert(object);
+    Ok(())
+}
+
+/// Takes an object out of a locked region and drops it from the object index and type counts
+fn evict_object(
+    region_id: Uuid,
+    region: &mut VaultRegion<PebbleVaultCustomData>,
+    object_id: Uuid,
+) -> Option<SpatialObject<PebbleVaultCustomData>> {
+    let object = take_object(region, object_id)?;
+    OBJECT_REGIONS.lock().unwrap().remove(&object_id);
+    count_removed(region_id, &object.object_type);
+    Some(object)
+}
+
+/// Moves an object between two regions of a region map the caller holds locked
+///
+/// When `updated` is given, it is placed in the destination instead of the
+/// object that was taken out, with both regions locked throughout.
+fn transfer_object(
+    vault_manager: &VaultManager<PebbleVaultCustomData>,
+    object_id: Uuid,
+    from_region_id: Uuid,
+    to_region_id: Uuid,
+    updated: Option<&SpatialObject<PebbleVaultCustomData>>,
+) -> Result<(), PebbleVaultError> {
+    if from_region_id == to_region_id {
+        return Ok(());
+    }
+    let from_region = loaded_region(vault_manager, from_region_id)?;
+    let to_region = loaded_region(vault_manager, to_region_id)?;
+
+    // Regions are always locked in UUID order, so two opposite transfers can't deadlock
+    let (mut from, mut to) = if from_region_id < to_region_id {
+        let from = from_region.lock().unwrap();
+        (from, to_region.lock().unwrap())
+    } else {
+        let to = to_region.lock().unwrap();
+        (from_region.lock().unwrap(), to)
+    };
+    let object = evict_object(from_region_id, &mut from, object_id)
+        .ok_or(PebbleVaultError::ObjectNotFound(object_id))?;
+    place_object(to_region_id, &mut to, updated.cloned().unwrap_or(object))
+}
+
+/// Data directory from `PEBBLEVAULT_DATA_DIR`, or the default if it is unset
+pub fn vault_data_dir_from_env() -> String {
+    std::env::var(VAULT_DATA_DIR_ENV).unwrap_or_else(|_| DEFAULT_VAULT_DATA_DIR.to_string())
+}
+
+/// Sets the directory the vault stores its data in and opens the vault there
+///
+/// Must be called before the vault is first used, since the directory is
+/// fixed once the VaultManager has been opened.
+///
+/// # Errors
+///
+/// Returns an error if the data directory has already been chosen, or if the
+/// vault cannot be opened in it.
+pub fn init_vault(data_dir: impl Into<String>) -> Result<(), String> {
+    let data_dir = data_dir.into();
+    VAULT_DATA_DIR
+        .set(data_dir.clone())
+        .map_err(|_| format!("PebbleVault data directory is already set to {}", vault_data_dir()))?;
+    let vault_manager = VaultManager::new(&data_dir)
+        .map_err(|e| format!("Failed to open PebbleVault in {}: {}", data_dir, e))?;
+    *OPENED_VAULT.lock().unwrap() = Some(vault_manager);
+    Lazy::force(&VAULT_MANAGER);
+    Ok(())
+}
+
+/// Directory the vault stores its data in
+pub fn vault_data_dir() -> String {
+    VAULT_DATA_DIR.get_or_init(vault_data_dir_from_env).clone()
+}
+
+/// Region each object was added to, so an object's region is an O(1) lookup
+static OBJECT_REGIONS: Lazy<Mutex<HashMap<Uuid, Uuid>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Bounds of a loaded region, as stored in `REGION_INDEX`
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct RegionBounds {
+    region_id: Uuid,
+    center: [f64; 3],
+    radius: f64,
+}
+
+impl rstar::RTreeObject for RegionBounds {
+    type Envelope = AABB<[f64; 3]>;
+
+    fn envelope(&self) -> Self::Envelope {
+        let [x, y, z] = self.center;
+        let r = self.radius;
+        AABB::from_corners([x - r, y - r, z - r], [x + r, y + r, z + r])
+    }
+}
+
+/// Loaded regions indexed by their bounds, so finding the region around a
+/// point never scans every region or takes the vault lock
+static REGION_INDEX: Lazy<RwLock<rstar::RTree<RegionBounds>>> = Lazy::new(|| RwLock::new(rstar::RTree::new()));
+
+fn index_region(bounds: RegionBounds) {
+    let mut index = REGION_INDEX.write().unwrap();
+    if !index.contains(&bounds) {
+        index.insert(bounds);
+    }
+}
+
+fn unindex_region(bounds: RegionBounds) {
+    REGION_INDEX.write().unwrap().remove(&bounds);
+}
+
+/// Adds a region loaded from disk to the region index, object index and type counts
+///
+/// Objects loaded from disk bypass `add_object`, so they are counted and
+/// indexed once here. Regions that are already seeded are left alone.
+fn seed_region(vault_manager: &VaultManager<PebbleVaultCustomData>, bounds: RegionBounds) -> Result<(), PebbleVaultError> {
+    index_region(bounds);
+    if TYPE_COUNTS.lock().unwrap().contains_key(&bounds.region_id) {
+        return Ok(());
+    }
+
+    let RegionBounds { region_id, center, radius } = bounds;
+    let objects = vault_manager.query_region(
+        region_id,
+        center[0] - radius, center[1] - radius, center[2] - radius,
+        center[0] + radius, center[1] + radius, center[2] + radius,
+    )?;
+    let mut region_counts: HashMap<String, usize> = HashMap::new();
+    let mut object_regions = OBJECT_REGIONS.lock().unwrap();
+    for object in &objects {
+        *region_counts.entry(object.object_type.clone()).or_insert(0) += 1;
+        object_regions.insert(object.uuid, region_id);
+    }
+    TYPE_COUNTS.lock().unwrap().insert(region_id, region_counts);
+    Ok(())
+}
+
+/// Number of objects of each type in each region, so counts never need a scan
+static TYPE_COUNTS: Lazy<Mutex<HashMap<Uuid, HashMap<String, usize>>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn count_added(region_id: Uuid, object_type: &str) {
+    *TYPE_COUNTS
+        .lock()
+        .unwrap()
+        .entry(region_id)
+        .or_default()
+        .entry(object_type.to_string())
+        .or_insert(0) += 1;
+}
+
+fn count_removed(region_id: Uuid, object_type: &str) {
+    let mut counts = TYPE_COUNTS.lock().unwrap();
+    let Some(region_counts) = counts.get_mut(&region_id) else {
+        return;
+    };
+    if let Some(count) = region_counts.get_mut(object_type) {
+        *count = count.saturating_sub(1);
+        if *count == 0 {
+            region_counts.remove(object_type);
+        }
+    }
+}
+
+/// Errors returned by the PebbleVault API
+#[derive(Debug, Clone, PartialEq)]
+pub enum PebbleVaultError {
+    /// No loaded region has this id
+    RegionNotFound(Uuid),
+    /// No object has this id
+    ObjectNotFound(Uuid),
+    /// An object with this id is already in the vault
+    ObjectExists(Uuid),
+    /// The region still holds objects and was not force-deleted
+    RegionNotEmpty { region_id: Uuid, objects: usize },
+    /// A coordinate is NaN or infinite
+    InvalidCoordinate { object_id: Uuid, point: [f64; 3] },
+    /// The point lies outside the configured world bounds
+    OutOfBounds { object_id: Uuid, point: [f64; 3], bounds: WorldBounds },
+    /// Reading or writing a file failed
+    Io(String),
+    /// A region export could not be written or read back
+    InvalidExport(String),
+    /// The underlying storage reported an error
+    Storage(String),
+}
+
+impl fmt::Display for PebbleVaultError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PebbleVaultError::RegionNotFound(region_id) => write!(f, "Region {} not found", region_id),
+            PebbleVaultError::ObjectNotFound(object_id) => write!(f, "Object {} not found", object_id),
+            PebbleVaultError::ObjectExists(object_id) => write!(f, "Object {} already exists", object_id),
+            PebbleVaultError::RegionNotEmpty { region_id, objects } => {
+                write!(f, "Region {} still contains {} objects", region_id, objects)
+            }
+            PebbleVaultError::InvalidCoordinate { object_id, point } => write!(
+                f,
+                "Object {} has a non-finite coordinate ({}, {}, {})",
+                object_id, point[0], point[1], point[2]
+            ),
+            PebbleVaultError::OutOfBounds { object_id, point, bounds } => write!(
+                f,
+                "Object {} at ({}, {}, {}) is outside the world bounds {:?} to {:?}",
+                object_id, point[0], point[1], point[2], bounds.min, bounds.max
+            ),
+            PebbleVaultError::Io(message) | PebbleVaultError::InvalidExport(message) | PebbleVaultError::Storage(message) => {
+                write!(f, "{}", message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PebbleVaultError {}
+
+/// Errors from the underlying VaultManager, which only reports messages
+///
+/// Region and object ids are checked before the manager is called, so a
+/// missing region or object is always reported as `RegionNotFound` or
+/// `ObjectNotFound` and what reaches this conversion are storage failures.
+impl From<String> for PebbleVaultError {
+    fn from(message: String) -> Self {
+        PebbleVaultError::Storage(message)
+    }
+}
+
+impl From<PebbleVaultError> for String {
+    fn from(error: PebbleVaultError) -> Self {
+        error.to_string()
+    }
+}
+
+/// Box every object must lie within, or None to only reject non-finite coordinates
+static WORLD_BOUNDS: Lazy<Mutex<Option<WorldBounds>>> = Lazy::new(|| Mutex::new(None));
+
+/// Axis-aligned box enclosing the playable world
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct WorldBounds {
+    pub min: [f64; 3],
+    pub max: [f64; 3],
+}
+
+impl WorldBounds {
+    pub fn contains(&self, point: [f64; 3]) -> bool {
+        (0..3).all(|axis| point[axis] >= self.min[axis] && point[axis] <= self.max[axis])
+    }
+}
+
+/// Rejects coordinates that would corrupt the spatial index
+///
+/// NaN and infinite coordinates are always rejected, and so are points
+/// outside the world bounds when bounds are set.
+fn check_coordinates(object_id: Uuid, point: [f64; 3]) -> Result<(), PebbleVaultError> {
+    check_coordinates_within(object_id, point, *WORLD_BOUNDS.lock().unwrap())
+}
+
+fn check_coordinates_within(object_id: Uuid, point: [f64; 3], bounds: Option<WorldBounds>) -> Result<(), PebbleVaultError> {
+    if point.iter().any(|coordinate| !coordinate.is_finite()) {
+        return Err(PebbleVaultError::InvalidCoordinate { object_id, point });
+    }
+    if let Some(bounds) = bounds {
+        if !bounds.contains(point) {
+            return Err(PebbleVaultError::OutOfBounds { object_id, point, bounds });
+        }
+    }
+    Ok(())
+}
+
+/// Whether `update_object` moves objects into the region containing their new position
+static AUTO_TRANSFER: AtomicBool = AtomicBool::new(true);
+
+/// File in the data directory that region metadata is persisted to
+const REGION_METADATA_FILE: &str = "region_metadata.json";
+
+/// Key/value metadata attached to whole regions, read from the data directory on first use
+static REGION_METADATA: Lazy<Mutex<HashMap<Uuid, HashMap<String, serde_json::Value>>>> =
+    Lazy::new(|| Mutex::new(load_region_metadata()));
+
+fn region_metadata_path() -> PathBuf {
+    Path::new(&vault_data_dir()).join(REGION_METADATA_FILE)
+}
+
+/// Reads persisted region metadata, starting empty if there is none
+fn load_region_metadata() -> HashMap<Uuid, HashMap<String, serde_json::Value>> {
+    let path = region_metadata_path();
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return HashMap::new(),
+        Err(e) => {
+            LOG.error(format_args!("Failed to read region metadata from {}: {}", path.display(), e));
+            return HashMap::new();
+        }
+    };
+    serde_json::from_str(&contents).unwrap_or_else(|e| {
+        LOG.error(format_args!("Failed to parse region metadata in {}: {}", path.display(), e));
+        HashMap::new()
+    })
+}
+
+/// Writes region metadata next to the region data, replacing the file in one step
+///
+/// The metadata stays locked until the file is replaced, so concurrent saves
+/// can't interleave their writes of the temporary file.
+fn save_region_metadata() -> Result<(), PebbleVaultError> {
+    let metadata = REGION_METADATA.lock().unwrap();
+    let contents = serde_json::to_string_pretty(&*metadata)
+        .map_err(|e| PebbleVaultError::Storage(format!("Failed to serialize region metadata: {}", e)))?;
+    let path = region_metadata_path();
+    let temp_path = path.with_extension("json.tmp");
+    std::fs::write(&temp_path, contents)
+        .and_then(|()| std::fs::rename(&temp_path, &path))
+        .map_err(|e| PebbleVaultError::Io(format!("Failed to write {}: {}", path.display(), e)))
+}
+
+/// Writes the objects of one region to the persistent store
+///
+/// Only the region's own lock is taken; the manager is borrowed for its
+/// store, so other regions keep working while this one is written.
+fn persist_region(
+    vault_manager: &VaultManager<PebbleVaultCustomData>,
+    region_id: Uuid,
+    region: &Mutex<VaultRegion<PebbleVaultCustomData>>,
+) -> Result<(), PebbleVaultError> {
+    let region = region.lock().unwrap();
+    for object in region.rtree.iter() {
+        let point = PebbleVault::MySQLGeo::Point {
+            id: Some(object.uuid),
+            x: object.point[0],
+            y: object.point[1],
+            z: object.point[2],
+            object_type: object.object_type.clone(),
+            custom_data: Arc::clone(&object.custom_data),
+        };
+        vault_manager
+            .persistent_db
+            .add_point(&point, region_id)
+            .map_err(|e| PebbleVaultError::Storage(e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Persists every region and the region metadata
+fn persist_vault() -> Result<(), PebbleVaultError> {
+    VAULT_MANAGER.read().unwrap().persist_to_disk()?;
+    save_region_metadata()
+}
+
+/// Seconds between automatic persists, 0 disables autosave
+static AUTOSAVE_INTERVAL_SECS: AtomicU64 = AtomicU64::new(DEFAULT_AUTOSAVE_INTERVAL_SECS);
+
+/// Default number of seconds between automatic persists
+pub const DEFAULT_AUTOSAVE_INTERVAL_SECS: u64 = 60;
+
+/// How often the autosave thread wakes to check whether a persist is due
+const AUTOSAVE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+static AUTOSAVE: Once = Once::new();
+
+/// Starts the background thread that periodically persists the vault to disk
+///
+/// Only the first call spawns the thread. The interval comes from the server
+/// config's `vault_autosave_interval_secs` through `set_autosave_interval` and
+/// is re-read on every wake-up, so reloading the config takes effect without
+/// a restart.
+fn start_autosave() {
+    AUTOSAVE.call_once(|| {
+        std::thread::spawn(|| {
+            let mut last_save = Instant::now();
+            loop {
+                std::thread::sleep(AUTOSAVE_POLL_INTERVAL);
+
+                let interval = AUTOSAVE_INTERVAL_SECS.load(Ordering::Relaxed);
+                if interval == 0 || last_save.elapsed() < Duration::from_secs(interval) {
+                    continue;
+                }
+                last_save = Instant::now();
+
+                if let Err(e) = persist_vault() {
+                    LOG.error(format_args!("Autosave failed: {}", e));
+                }
+            }
+        });
+    });
+}
 
 /// Custom data structure for PebbleVault objects
 ///
@@ -25,20 +494,146 @@ pub struct PebbleVaultCustomData {
     pub name: String,
     /// Custom value associated with the object
     pub value: i32,
+    /// Last known velocity in world units per second, used to predict movement between updates
+    #[serde(default)]
+    pub velocity: [f64; 3],
+    /// Current health of objects that have any, such as players
+    #[serde(default)]
+    pub health: Option<i32>,
+}
+
+/// Format version written by `export_region`
+pub const REGION_EXPORT_VERSION: u32 = 1;
+
+/// A region and its objects in the portable format used by `export_region`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RegionExport {
+    pub version: u32,
+    /// Id the region had on the server it was exported from
+    pub region_id: Uuid,
+    pub center: [f64; 3],
+    pub radius: f64,
+    pub objects: Vec<ExportedObject>,
+}
+
+/// One object of an exported region
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedObject {
+    pub uuid: Uuid,
+    pub object_type: String,
+    pub point: [f64; 3],
+    pub custom_data: PebbleVaultCustomData,
+}
+
+/// Custom event emitted with `{ "region": id }` when a region is created or loaded into memory
+pub const REGION_LOADED_EVENT: &str = "region_loaded";
+
+/// Custom event emitted with `{ "region": id }` when a region is unloaded or deleted
+pub const REGION_UNLOADED_EVENT: &str = "region_unloaded";
+
+/// Tells plugins a region came into or went out of memory
+fn announce_region(event_type: &str, region_id: Uuid) {
+    super::emit_event(horizon_events::GameEvent::custom(event_type, serde_json::json!({
+        "region": region_id.to_string(),
+    })));
+}
+
+/// Removes a region from memory and the persistent store
+///
+/// Objects still inside the region are removed as well when `force` is set,
+/// otherwise their presence is an error and nothing is deleted.
+fn delete_region_inner(vault_manager: &mut VaultManager<PebbleVaultCustomData>, region_id: Uuid, force: bool) -> Result<(), PebbleVaultError> {
+    let (center, radius) = match vault_manager.regions.get(&region_id) {
+        Some(region) => {
+            let region = region.lock().unwrap();
+            (region.center, region.radius)
+        }
+        None => return Err(PebbleVaultError::RegionNotFound(region_id)),
+    };
+
+    let residents = vault_manager.query_region(
+        region_id,
+        center[0] - radius, center[1] - radius, center[2] - radius,
+        center[0] + radius, center[1] + radius, center[2] + radius,
+    )?;
+
+    if !residents.is_empty() && !force {
+        return Err(PebbleVaultError::RegionNotEmpty { region_id, objects: residents.len() });
+    }
+
+    // Go through remove_object so each object is dropped from the persistent store too
+    for object in residents {
+        vault_manager.remove_object(object.uuid)?;
+        OBJECT_REGIONS.lock().unwrap().remove(&object.uuid);
+    }
+
+    vault_manager.regions.remove(&region_id);
+    unindex_region(RegionBounds { region_id, center, radius });
+    TYPE_COUNTS.lock().unwrap().remove(&region_id);
+    REGION_METADATA.lock().unwrap().remove(&region_id);
+    vault_manager
+        .persistent_db
+        .remove_region(region_id)
+        .map_err(|e| PebbleVaultError::Storage(e.to_string()))?;
+    // The file would otherwise bring the metadata back on the next start
+    save_region_metadata()
+}
+
+/// Center and radius of a region, whether it is loaded or only stored on disk
+fn region_geometry(region_id: Uuid) -> Result<([f64; 3], f64), PebbleVaultError> {
+    let vault_manager = VAULT_MANAGER.read().unwrap();
+    if let Some(region) = vault_manager.regions.get(&region_id) {
+        let region = region.lock().unwrap();
+        return Ok((region.center, region.radius));
+    }
+    vault_manager
+        .persistent_db
+        .get_all_regions()
+        .map_err(|e| PebbleVaultError::Storage(e.to_string()))?
+        .into_iter()
+        .find(|region| region.id == region_id)
+        .map(|region| (region.center, region.radius))
+        .ok_or(PebbleVaultError::RegionNotFound(region_id))
+}
+
+/// Euclidean distance between two points
+fn distance(a: [f64; 3], b: [f64; 3]) -> f64 {
+    let [dx, dy, dz] = horizon_coords::world_to_region_local(a, b);
+    (dx * dx + dy * dy + dz * dz).sqrt()
 }
 
 // Define both required traits
 pub trait PluginAPI {
     fn new() -> Plugin;
-    fn persist_to_disk(&self) -> Result<(), String>;
+    fn persist_to_disk(&self) -> Result<(), PebbleVaultError>;
     fn get_region(&self, region_id: Uuid) -> Option<Arc<Mutex<VaultRegion<PebbleVaultCustomData>>>>;
-    fn transfer_player(&self, player_uuid: Uuid, from_region_id: Uuid, to_region_id: Uuid) -> Result<(), String>;
-    fn update_object(&self, object: &SpatialObject<PebbleVaultCustomData>) -> Result<(), String>;
-    fn get_object(&self, object_id: Uuid) -> Result<Option<SpatialObject<PebbleVaultCustomData>>, String>;
-    fn remove_object(&self, object_id: Uuid) -> Result<(), String>;
-    fn add_object(&self, region_id: Uuid, uuid: Uuid, object_type: &str, x: f64, y: f64, z: f64, custom_data: PebbleVaultCustomData) -> Result<(), String>;
-    fn query_region(&self, region_id: Uuid, min_x: f64, min_y: f64, min_z: f64, max_x: f64, max_y: f64, max_z: f64) -> Result<Vec<SpatialObject<PebbleVaultCustomData>>, String>;
-    fn create_or_load_region(&self, center: [f64; 3], radius: f64) -> Result<Uuid, String>;    
+    fn transfer_player(&self, player_uuid: Uuid, from_region_id: Uuid, to_region_id: Uuid) -> Result<(), PebbleVaultError>;
+    fn update_object(&self, object: &SpatialObject<PebbleVaultCustomData>) -> Result<(), PebbleVaultError>;
+    fn get_object(&self, object_id: Uuid) -> Result<Option<SpatialObject<PebbleVaultCustomData>>, PebbleVaultError>;
+    fn remove_object(&self, object_id: Uuid) -> Result<(), PebbleVaultError>;
+    fn add_object(&self, region_id: Uuid, uuid: Uuid, object_type: &str, x: f64, y: f64, z: f64, custom_data: PebbleVaultCustomData) -> Result<(), PebbleVaultError>;
+    fn query_region(&self, region_id: Uuid, min_x: f64, min_y: f64, min_z: f64, max_x: f64, max_y: f64, max_z: f64) -> Result<Vec<SpatialObject<PebbleVaultCustomData>>, PebbleVaultError>;
+    fn query_region_typed(&self, region_id: Uuid, min_x: f64, min_y: f64, min_z: f64, max_x: f64, max_y: f64, max_z: f64, object_types: &[&str]) -> Result<Vec<SpatialObject<PebbleVaultCustomData>>, PebbleVaultError>;
+    fn query_region_paged(&self, region_id: Uuid, min_x: f64, min_y: f64, min_z: f64, max_x: f64, max_y: f64, max_z: f64, after: Option<Uuid>, limit: usize) -> Result<(Vec<SpatialObject<PebbleVaultCustomData>>, bool), PebbleVaultError>;
+    fn create_or_load_region(&self, center: [f64; 3], radius: f64) -> Result<Uuid, PebbleVaultError>;    
+    fn query_sphere(&self, center: [f64; 3], radius: f64) -> Result<Vec<SpatialObject<PebbleVaultCustomData>>, PebbleVaultError>;
+    fn nearest_objects(&self, region_id: Uuid, point: [f64; 3], k: usize) -> Result<Vec<SpatialObject<PebbleVaultCustomData>>, PebbleVaultError>;
+    fn list_regions(&self) -> Vec<Uuid>;
+    fn preload_regions(&self, region_ids: &[Uuid]) -> Result<(), PebbleVaultError>;
+    fn set_region_metadata(&self, region_id: Uuid, key: &str, value: serde_json::Value) -> Result<(), PebbleVaultError>;
+    fn get_region_metadata(&self, region_id: Uuid, key: &str) -> Option<serde_json::Value>;
+    fn set_autosave_interval(&self, secs: u64);
+    fn set_auto_transfer(&self, enabled: bool);
+    fn set_world_bounds(&self, bounds: Option<WorldBounds>);
+    fn delete_region(&self, region_id: Uuid) -> Result<(), PebbleVaultError>;
+    fn delete_region_force(&self, region_id: Uuid) -> Result<(), PebbleVaultError>;
+    fn unload_region(&self, region_id: Uuid) -> Result<(), PebbleVaultError>;
+    fn region_of(&self, object_id: Uuid) -> Option<Uuid>;
+    fn predict_position(&self, object_id: Uuid, dt: f64) -> Option<[f64; 3]>;
+    fn region_containing(&self, point: [f64; 3]) -> Option<Uuid>;
+    fn count_by_type(&self, region_id: Uuid) -> HashMap<String, usize>;
+    fn export_region(&self, region_id: Uuid, path: &Path) -> Result<(), PebbleVaultError>;
+    fn import_region(&self, path: &Path) -> Result<Uuid, PebbleVaultError>;
     fn thing(&self) -> String;
 }
 
@@ -49,7 +644,8 @@ pub trait PluginConstruct {
 // Implement constructor
 impl PluginConstruct for Plugin {
     fn new(plugins: HashMap<&'static str, LoadedPlugin>) -> Plugin {
-        println!("Initializing PebbleVault plugin");
+        LOG.info(format_args!("Initializing PebbleVault plugin"));
+        start_autosave();
         Plugin {}
     }
 }
@@ -87,8 +683,16 @@ impl PluginAPI for Plugin {
     /// let region_id = pebble_vault.create_or_load_region(center, radius).expect("Failed to create region");
     /// println!("Created region with ID: {}", region_id);
     /// ```
-    fn create_or_load_region(&self, center: [f64; 3], radius: f64) -> Result<Uuid, String> {
-        VAULT_MANAGER.lock().unwrap().create_or_load_region(center, radius)
+    fn create_or_load_region(&self, center: [f64; 3], radius: f64) -> Result<Uuid, PebbleVaultError> {
+        let mut vault_manager = VAULT_MANAGER.write().unwrap();
+        let region_id = vault_manager.create_or_load_region(center, radius)?;
+        let newly_loaded = !TYPE_COUNTS.lock().unwrap().contains_key(&region_id);
+        seed_region(&vault_manager, RegionBounds { region_id, center, radius })?;
+        drop(vault_manager);
+        if newly_loaded {
+            announce_region(REGION_LOADED_EVENT, region_id);
+        }
+        Ok(region_id)
     }
 
     /// Queries a region for objects within a bounding box
@@ -120,8 +724,108 @@ impl PluginAPI for Plugin {
     ///     .expect("Failed to query region");
     /// println!("Found {} objects in the region", objects.len());
     /// ```
-    fn query_region(&self, region_id: Uuid, min_x: f64, min_y: f64, min_z: f64, max_x: f64, max_y: f64, max_z: f64) -> Result<Vec<SpatialObject<PebbleVaultCustomData>>, String> {
-        VAULT_MANAGER.lock().unwrap().query_region(region_id, min_x, min_y, min_z, max_x, max_y, max_z)
+    fn query_region(&self, region_id: Uuid, min_x: f64, min_y: f64, min_z: f64, max_x: f64, max_y: f64, max_z: f64) -> Result<Vec<SpatialObject<PebbleVaultCustomData>>, PebbleVaultError> {
+        let vault_manager = VAULT_MANAGER.read().unwrap();
+        loaded_region(&vault_manager, region_id)?;
+        vault_manager.query_region(region_id, min_x, min_y, min_z, max_x, max_y, max_z)
+    }
+
+    /// Queries a region for objects of specific types within a bounding box
+    ///
+    /// This method behaves like `query_region` but only returns objects whose
+    /// `object_type` is one of `object_types`. Types are checked while walking
+    /// the region's tree, so objects of other types are never copied.
+    ///
+    /// # Arguments
+    ///
+    /// * `region_id` - UUID of the region to query
+    /// * `min_x` - Minimum x-coordinate of the bounding box
+    /// * `min_y` - Minimum y-coordinate of the bounding box
+    /// * `min_z` - Minimum z-coordinate of the bounding box
+    /// * `max_x` - Maximum x-coordinate of the bounding box
+    /// * `max_y` - Maximum y-coordinate of the bounding box
+    /// * `max_z` - Maximum z-coordinate of the bounding box
+    /// * `object_types` - Accepted object types (e.g., `&["player", "npc"]`)
+    ///
+    /// # Returns
+    ///
+    /// A Result containing a vector of matching SpatialObjects or an error string
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pebble_vault::PebbleVault;
+    /// # let pebble_vault = PebbleVault::new().unwrap();
+    /// # let region_id = pebble_vault.create_or_load_region([0.0, 0.0, 0.0], 1000.0).unwrap();
+    /// let actors = pebble_vault.query_region_typed(region_id, -100.0, -100.0, -100.0, 100.0, 100.0, 100.0, &["player", "npc"])
+    ///     .expect("Failed to query region");
+    /// println!("Found {} players and NPCs in the region", actors.len());
+    /// ```
+    fn query_region_typed(&self, region_id: Uuid, min_x: f64, min_y: f64, min_z: f64, max_x: f64, max_y: f64, max_z: f64, object_types: &[&str]) -> Result<Vec<SpatialObject<PebbleVaultCustomData>>, PebbleVaultError> {
+        let vault_manager = VAULT_MANAGER.read().unwrap();
+        let envelope = AABB::from_corners([min_x, min_y, min_z], [max_x, max_y, max_z]);
+        let region = loaded_region(&vault_manager, region_id)?.lock().unwrap();
+        Ok(region
+            .rtree
+            .locate_in_envelope(&envelope)
+            .filter(|object| object_types.contains(&object.object_type.as_str()))
+            .cloned()
+            .collect())
+    }
+
+    /// Queries a region for one page of the objects within a bounding box
+    ///
+    /// Matches are ordered by UUID and each page resumes after the last UUID
+    /// of the page before it, so an object that stays in the box is returned
+    /// exactly once even when other objects are added or removed between calls.
+    ///
+    /// # Arguments
+    ///
+    /// * `region_id` - UUID of the region to query
+    /// * `min_x` - Minimum x-coordinate of the bounding box
+    /// * `min_y` - Minimum y-coordinate of the bounding box
+    /// * `min_z` - Minimum z-coordinate of the bounding box
+    /// * `max_x` - Maximum x-coordinate of the bounding box
+    /// * `max_y` - Maximum y-coordinate of the bounding box
+    /// * `max_z` - Maximum z-coordinate of the bounding box
+    /// * `after` - UUID of the last object of the previous page, or `None` for the first page
+    /// * `limit` - Largest number of objects to return
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the page of SpatialObjects and whether more matches
+    /// follow it, or an error string
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pebble_vault::PebbleVault;
+    /// # let pebble_vault = PebbleVault::new().unwrap();
+    /// # let region_id = pebble_vault.create_or_load_region([0.0, 0.0, 0.0], 1000.0).unwrap();
+    /// let mut after = None;
+    /// loop {
+    ///     let (page, has_more) = pebble_vault
+    ///         .query_region_paged(region_id, -100.0, -100.0, -100.0, 100.0, 100.0, 100.0, after, 100)
+    ///         .expect("Failed to query region");
+    ///     after = page.last().map(|object| object.uuid);
+    ///     if !has_more {
+    ///         break;
+    ///     }
+    /// }
+    /// ```
+    fn query_region_paged(&self, region_id: Uuid, min_x: f64, min_y: f64, min_z: f64, max_x: f64, max_y: f64, max_z: f64, after: Option<Uuid>, limit: usize) -> Result<(Vec<SpatialObject<PebbleVaultCustomData>>, bool), PebbleVaultError> {
+        let vault_manager = VAULT_MANAGER.read().unwrap();
+        let envelope = AABB::from_corners([min_x, min_y, min_z], [max_x, max_y, max_z]);
+        let region = loaded_region(&vault_manager, region_id)?.lock().unwrap();
+
+        let mut matches: Vec<_> = region
+            .rtree
+            .locate_in_envelope(&envelope)
+            .filter(|object| Some(object.uuid) > after)
+            .collect();
+        matches.sort_unstable_by_key(|object| object.uuid);
+        let has_more = matches.len() > limit;
+        Ok((matches.into_iter().take(limit).cloned().collect(), has_more))
     }
 
     /// Adds a new object to a region
@@ -140,7 +844,8 @@ impl PluginAPI for Plugin {
     ///
     /// # Returns
     ///
-    /// A Result indicating success or an error string
+    /// A Result indicating success, or `ObjectExists` if an object with the
+    /// same UUID is already in any region
     ///
     /// # Examples
     ///
@@ -153,13 +858,23 @@ impl PluginAPI for Plugin {
     /// let custom_data = PebbleVaultCustomData {
     ///     name: "Example Object".to_string(),
     ///     value: 42,
+    ///     velocity: [0.0; 3],
+    ///     health: None,
     /// };
     /// pebble_vault.add_object(region_id, object_id, "item", 10.0, 20.0, 30.0, custom_data)
     ///     .expect("Failed to add object");
     /// println!("Added object with ID: {}", object_id);
     /// ```
-    fn add_object(&self, region_id: Uuid, uuid: Uuid, object_type: &str, x: f64, y: f64, z: f64, custom_data: PebbleVaultCustomData) -> Result<(), String> {
-        VAULT_MANAGER.lock().unwrap().add_object(region_id, uuid, object_type, x, y, z, Arc::new(custom_data))
+    fn add_object(&self, region_id: Uuid, uuid: Uuid, object_type: &str, x: f64, y: f64, z: f64, custom_data: PebbleVaultCustomData) -> Result<(), PebbleVaultError> {
+        check_coordinates(uuid, [x, y, z])?;
+        let vault_manager = VAULT_MANAGER.read().unwrap();
+        let mut region = loaded_region(&vault_manager, region_id)?.lock().unwrap();
+        place_object(region_id, &mut region, SpatialObject {
+            uuid,
+            object_type: object_type.to_string(),
+            point: [x, y, z],
+            custom_data: Arc::new(custom_data),
+        })
     }
 
     /// Removes an object from its region and the persistent database
@@ -182,13 +897,24 @@ impl PluginAPI for Plugin {
     /// # let pebble_vault = PebbleVault::new().unwrap();
     /// # let region_id = pebble_vault.create_or_load_region([0.0, 0.0, 0.0], 1000.0).unwrap();
     /// # let object_id = Uuid::new_v4();
-    /// # let custom_data = PebbleVaultCustomData { name: "Example Object".to_string(), value: 42 };
+    /// # let custom_data = PebbleVaultCustomData { name: "Example Object".to_string(), value: 42, velocity: [0.0; 3], health: None };
     /// # pebble_vault.add_object(region_id, object_id, "item", 10.0, 20.0, 30.0, custom_data).unwrap();
     /// pebble_vault.remove_object(object_id).expect("Failed to remove object");
     /// println!("Removed object with ID: {}", object_id);
     /// ```
-    fn remove_object(&self, object_id: Uuid) -> Result<(), String> {
-        VAULT_MANAGER.lock().unwrap().remove_object(object_id)
+    fn remove_object(&self, object_id: Uuid) -> Result<(), PebbleVaultError> {
+        // Removal also reaches the persistent store, so it goes through the manager
+        let mut vault_manager = VAULT_MANAGER.write().unwrap();
+        let object_type = vault_manager
+            .get_object(object_id)?
+            .ok_or(PebbleVaultError::ObjectNotFound(object_id))?
+            .object_type;
+        vault_manager.remove_object(object_id)?;
+
+        if let Some(region_id) = OBJECT_REGIONS.lock().unwrap().remove(&object_id) {
+            count_removed(region_id, &object_type);
+        }
+        Ok(())
     }
 
     /// Gets a reference to an object by its ID
@@ -211,7 +937,7 @@ impl PluginAPI for Plugin {
     /// # let pebble_vault = PebbleVault::new().unwrap();
     /// # let region_id = pebble_vault.create_or_load_region([0.0, 0.0, 0.0], 1000.0).unwrap();
     /// # let object_id = Uuid::new_v4();
-    /// # let custom_data = PebbleVaultCustomData { name: "Example Object".to_string(), value: 42 };
+    /// # let custom_data = PebbleVaultCustomData { name: "Example Object".to_string(), value: 42, velocity: [0.0; 3], health: None };
     /// # pebble_vault.add_object(region_id, object_id, "item", 10.0, 20.0, 30.0, custom_data).unwrap();
     /// if let Ok(Some(object)) = pebble_vault.get_object(object_id) {
     ///     println!("Found object: {:?}", object);
@@ -219,13 +945,16 @@ impl PluginAPI for Plugin {
     ///     println!("Object not found");
     /// }
     /// ```
-    fn get_object(&self, object_id: Uuid) -> Result<Option<SpatialObject<PebbleVaultCustomData>>, String> {
-        VAULT_MANAGER.lock().unwrap().get_object(object_id)
+    fn get_object(&self, object_id: Uuid) -> Result<Option<SpatialObject<PebbleVaultCustomData>>, PebbleVaultError> {
+        VAULT_MANAGER.read().unwrap().get_object(object_id)
     }
 
     /// Updates an existing object in the VaultManager's in-memory storage
     ///
     /// This method updates the data of an existing spatial object in the PebbleVault system.
+    /// If the new position lies outside the object's region and another loaded
+    /// region contains it, the object is moved into that region, unless
+    /// auto-transfer has been turned off with `set_auto_transfer`.
     ///
     /// # Arguments
     ///
@@ -243,7 +972,7 @@ impl PluginAPI for Plugin {
     /// # let pebble_vault = PebbleVault::new().unwrap();
     /// # let region_id = pebble_vault.create_or_load_region([0.0, 0.0, 0.0], 1000.0).unwrap();
     /// # let object_id = Uuid::new_v4();
-    /// # let custom_data = PebbleVaultCustomData { name: "Example Object".to_string(), value: 42 };
+    /// # let custom_data = PebbleVaultCustomData { name: "Example Object".to_string(), value: 42, velocity: [0.0; 3], health: None };
     /// # pebble_vault.add_object(region_id, object_id, "item", 10.0, 20.0, 30.0, custom_data).unwrap();
     /// if let Ok(Some(mut object)) = pebble_vault.get_object(object_id) {
     ///     object.point = [15.0, 25.0, 35.0];
@@ -251,8 +980,31 @@ impl PluginAPI for Plugin {
     ///     println!("Updated object position");
     /// }
     /// ```
-    fn update_object(&self, object: &SpatialObject<PebbleVaultCustomData>) -> Result<(), String> {
-        VAULT_MANAGER.lock().unwrap().update_object(object)
+    fn update_object(&self, object: &SpatialObject<PebbleVaultCustomData>) -> Result<(), PebbleVaultError> {
+        check_coordinates(object.uuid, object.point)?;
+        let vault_manager = VAULT_MANAGER.read().unwrap();
+        let region_id = self
+            .region_of(object.uuid)
+            .ok_or(PebbleVaultError::ObjectNotFound(object.uuid))?;
+
+        if AUTO_TRANSFER.load(Ordering::Relaxed) {
+            let left_region = {
+                let region = loaded_region(&vault_manager, region_id)?.lock().unwrap();
+                distance(region.center, object.point) > region.radius
+            };
+            if let Some(to_region_id) = left_region
+                .then(|| self.region_containing(object.point))
+                .flatten()
+                .filter(|to_region_id| *to_region_id != region_id)
+            {
+                return transfer_object(&vault_manager, object.uuid, region_id, to_region_id, Some(object));
+            }
+        }
+
+        // Evicted and placed again so the type counts follow a changed object type
+        let mut region = loaded_region(&vault_manager, region_id)?.lock().unwrap();
+        evict_object(region_id, &mut region, object.uuid).ok_or(PebbleVaultError::ObjectNotFound(object.uuid))?;
+        place_object(region_id, &mut region, object.clone())
     }
 
     /// Transfers a player (object) from one region to another
@@ -279,14 +1031,14 @@ impl PluginAPI for Plugin {
     /// # let region1_id = pebble_vault.create_or_load_region([0.0, 0.0, 0.0], 1000.0).unwrap();
     /// # let region2_id = pebble_vault.create_or_load_region([2000.0, 0.0, 0.0], 1000.0).unwrap();
     /// # let player_id = Uuid::new_v4();
-    /// # let custom_data = PebbleVaultCustomData { name: "Player".to_string(), value: 100 };
+    /// # let custom_data = PebbleVaultCustomData { name: "Player".to_string(), value: 100, velocity: [0.0; 3], health: Some(100) };
     /// # pebble_vault.add_object(region1_id, player_id, "player", 10.0, 20.0, 30.0, custom_data).unwrap();
     /// pebble_vault.transfer_player(player_id, region1_id, region2_id)
     ///     .expect("Failed to transfer player");
     /// println!("Transferred player to new region");
     /// ```
-    fn transfer_player(&self, player_uuid: Uuid, from_region_id: Uuid, to_region_id: Uuid) -> Result<(), String> {
-        VAULT_MANAGER.lock().unwrap().transfer_player(player_uuid, from_region_id, to_region_id)
+    fn transfer_player(&self, player_uuid: Uuid, from_region_id: Uuid, to_region_id: Uuid) -> Result<(), PebbleVaultError> {
+        transfer_object(&VAULT_MANAGER.read().unwrap(), player_uuid, from_region_id, to_region_id, None)
     }
 
     /// Persists all in-memory databases to disk
@@ -305,8 +1057,8 @@ impl PluginAPI for Plugin {
     /// pebble_vault.persist_to_disk().expect("Failed to persist data");
     /// println!("Data persisted to disk");
     /// ```
-    fn persist_to_disk(&self) -> Result<(), String> {
-        VAULT_MANAGER.lock().unwrap().persist_to_disk()
+    fn persist_to_disk(&self) -> Result<(), PebbleVaultError> {
+        persist_vault()
     }
 
     /// Gets a reference to a region by its ID
@@ -335,10 +1087,1374 @@ impl PluginAPI for Plugin {
     /// }
     /// ```
     fn get_region(&self, region_id: Uuid) -> Option<Arc<Mutex<VaultRegion<PebbleVaultCustomData>>>> {
-        VAULT_MANAGER.lock().unwrap().get_region(region_id)
+        VAULT_MANAGER.read().unwrap().get_region(region_id)
     }
-    
-    fn new() -> Plugin {
-        Plugin{}
+
+    /// Queries every loaded region for objects within a sphere
+    ///
+    /// Regions whose bounds do not intersect the sphere are skipped, and objects
+    /// returned by the bounding-box query are filtered down to the exact radius.
+    ///
+    /// # Arguments
+    ///
+    /// * `center` - Center of the query sphere [x, y, z]
+    /// * `radius` - Radius of the query sphere
+    ///
+    /// # Returns
+    ///
+    /// A Result containing a vector of SpatialObjects inside the sphere or an error string
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pebble_vault::PebbleVault;
+    /// # let pebble_vault = PebbleVault::new().unwrap();
+    /// let nearby = pebble_vault.query_sphere([0.0, 0.0, 0.0], 50.0)
+    ///     .expect("Failed to query sphere");
+    /// println!("Found {} objects within 50 units", nearby.len());
+    /// ```
+    fn query_sphere(&self, center: [f64; 3], radius: f64) -> Result<Vec<SpatialObject<PebbleVaultCustomData>>, PebbleVaultError> {
+        let vault_manager = VAULT_MANAGER.read().unwrap();
+        let mut objects = Vec::new();
+
+        for (region_id, region) in vault_manager.regions.iter() {
+            let (region_center, region_radius) = {
+                let region = region.lock().unwrap();
+                (region.center, region.radius)
+            };
+
+            if distance(center, region_center) > radius + region_radius {
+                continue;
+            }
+
+            let candidates = vault_manager.query_region(
+                *region_id,
+                center[0] - radius, center[1] - radius, center[2] - radius,
+                center[0] + radius, center[1] + radius, center[2] + radius,
+            )?;
+            objects.extend(candidates.into_iter().filter(|object| distance(object.point, center) <= radius));
+        }
+
+        Ok(objects)
+    }
+
+    /// Finds the k objects in a region closest to a point
+    ///
+    /// Results are sorted by distance, nearest first. Objects at exactly the same
+    /// distance are ordered by UUID so repeated queries return a stable order.
+    ///
+    /// # Arguments
+    ///
+    /// * `region_id` - UUID of the region to search
+    /// * `point` - Coordinates to measure distance from [x, y, z]
+    /// * `k` - Maximum number of objects to return
+    ///
+    /// # Returns
+    ///
+    /// A Result containing up to `k` SpatialObjects, or every object in the region
+    /// if it holds fewer than `k`, or an error string if the region does not exist
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pebble_vault::PebbleVault;
+    /// # let pebble_vault = PebbleVault::new().unwrap();
+    /// # let region_id = pebble_vault.create_or_load_region([0.0, 0.0, 0.0], 1000.0).unwrap();
+    /// let closest = pebble_vault.nearest_objects(region_id, [10.0, 0.0, 10.0], 5)
+    ///     .expect("Failed to query nearest objects");
+    /// for object in closest {
+    ///     println!("{} at {:?}", object.uuid, object.point);
+    /// }
+    /// ```
+    fn nearest_objects(&self, region_id: Uuid, point: [f64; 3], k: usize) -> Result<Vec<SpatialObject<PebbleVaultCustomData>>, PebbleVaultError> {
+        let vault_manager = VAULT_MANAGER.read().unwrap();
+        let (center, radius) = match vault_manager.regions.get(&region_id) {
+            Some(region) => {
+                let region = region.lock().unwrap();
+                (region.center, region.radius)
+            }
+            None => return Err(PebbleVaultError::RegionNotFound(region_id)),
+        };
+
+        let mut objects = vault_manager.query_region(
+            region_id,
+            center[0] - radius, center[1] - radius, center[2] - radius,
+            center[0] + radius, center[1] + radius, center[2] + radius,
+        )?;
+        drop(vault_manager);
+
+        objects.sort_by(|a, b| {
+            distance(a.point, point)
+                .total_cmp(&distance(b.point, point))
+                .then_with(|| a.uuid.cmp(&b.uuid))
+        });
+        objects.truncate(k);
+
+        Ok(objects)
+    }
+
+    /// Sets how often the vault is automatically persisted to disk
+    ///
+    /// # Arguments
+    ///
+    /// * `secs` - Seconds between automatic persists, or 0 to disable autosave
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pebble_vault::PebbleVault;
+    /// # let pebble_vault = PebbleVault::new().unwrap();
+    /// pebble_vault.set_autosave_interval(30);
+    /// ```
+    fn set_autosave_interval(&self, secs: u64) {
+        AUTOSAVE_INTERVAL_SECS.store(secs, Ordering::Relaxed);
+    }
+
+    /// Sets whether `update_object` moves objects across region boundaries
+    ///
+    /// Auto-transfer is on by default. With it off, objects stay in their
+    /// region until moved with `transfer_player`.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether updated objects follow their position into other regions
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pebble_vault::PebbleVault;
+    /// # let pebble_vault = PebbleVault::new().unwrap();
+    /// pebble_vault.set_auto_transfer(false);
+    /// ```
+    fn set_auto_transfer(&self, enabled: bool) {
+        AUTO_TRANSFER.store(enabled, Ordering::Relaxed);
     }
-}
\ No newline at end of file
+
+    /// Sets the box that added and updated objects must stay within
+    ///
+    /// Objects already outside new bounds are left where they are; only
+    /// later adds and updates are checked.
+    ///
+    /// # Arguments
+    ///
+    /// * `bounds` - The world bounds, or None to accept any finite coordinates
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pebble_vault::{PebbleVault, WorldBounds};
+    /// # let pebble_vault = PebbleVault::new().unwrap();
+    /// pebble_vault.set_world_bounds(Some(WorldBounds { min: [-1e6; 3], max: [1e6; 3] }));
+    /// ```
+    fn set_world_bounds(&self, bounds: Option<WorldBounds>) {
+        *WORLD_BOUNDS.lock().unwrap() = bounds;
+    }
+
+    /// Gets the region an object currently belongs to
+    ///
+    /// # Arguments
+    ///
+    /// * `object_id` - UUID of the object
+    ///
+    /// # Returns
+    ///
+    /// The UUID of the object's region, or None if the object is unknown
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pebble_vault::PebbleVault;
+    /// # use uuid::Uuid;
+    /// # let pebble_vault = PebbleVault::new().unwrap();
+    /// # let player_id = Uuid::new_v4();
+    /// if let Some(region_id) = pebble_vault.region_of(player_id) {
+    ///     println!("Player is in region {}", region_id);
+    /// }
+    /// ```
+    fn region_of(&self, object_id: Uuid) -> Option<Uuid> {
+        OBJECT_REGIONS.lock().unwrap().get(&object_id).copied()
+    }
+
+    /// Predicts where an object will be after `dt` seconds
+    ///
+    /// Dead reckoning from the object's last known position and velocity,
+    /// assuming it keeps moving in a straight line at the same speed.
+    ///
+    /// # Arguments
+    ///
+    /// * `object_id` - UUID of the object
+    /// * `dt` - Seconds to extrapolate ahead
+    ///
+    /// # Returns
+    ///
+    /// The predicted position, or None if the object is unknown
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pebble_vault::PebbleVault;
+    /// # use uuid::Uuid;
+    /// # let pebble_vault = PebbleVault::new().unwrap();
+    /// # let player_id = Uuid::new_v4();
+    /// if let Some(position) = pebble_vault.predict_position(player_id, 0.1) {
+    ///     println!("Player will be at {:?}", position);
+    /// }
+    /// ```
+    fn predict_position(&self, object_id: Uuid, dt: f64) -> Option<[f64; 3]> {
+        let object = self.get_object(object_id).ok().flatten()?;
+        let velocity = object.custom_data.velocity;
+        Some([0, 1, 2].map(|axis| object.point[axis] + velocity[axis] * dt))
+    }
+
+    /// Finds the region a point belongs to
+    ///
+    /// Regions may be nested, for example a dungeon inside the open world, so
+    /// the smallest region containing the point is chosen.
+    ///
+    /// # Arguments
+    ///
+    /// * `point` - Coordinates to look up [x, y, z]
+    ///
+    /// # Returns
+    ///
+    /// The UUID of the smallest loaded region containing the point, or None if
+    /// no region contains it. Regions are looked up in a spatial index, so the
+    /// cost does not grow with the number of loaded regions.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pebble_vault::PebbleVault;
+    /// # let pebble_vault = PebbleVault::new().unwrap();
+    /// # pebble_vault.create_or_load_region([0.0, 0.0, 0.0], 1000.0).unwrap();
+    /// let region_id = pebble_vault.region_containing([10.0, 0.0, 10.0]);
+    /// ```
+    fn region_containing(&self, point: [f64; 3]) -> Option<Uuid> {
+        REGION_INDEX
+            .read()
+            .unwrap()
+            .locate_in_envelope_intersecting(&AABB::from_point(point))
+            .filter(|bounds| distance(bounds.center, point) <= bounds.radius)
+            .min_by(|a, b| a.radius.total_cmp(&b.radius))
+            .map(|bounds| bounds.region_id)
+    }
+
+    /// Lists the IDs of all regions currently loaded
+    ///
+    /// # Returns
+    ///
+    /// A vector containing the UUID of every region
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pebble_vault::PebbleVault;
+    /// # let pebble_vault = PebbleVault::new().unwrap();
+    /// for region_id in pebble_vault.list_regions() {
+    ///     println!("Region: {}", region_id);
+    /// }
+    /// ```
+    fn list_regions(&self) -> Vec<Uuid> {
+        VAULT_MANAGER.read().unwrap().regions.keys().copied().collect()
+    }
+
+    /// Loads regions and indexes their objects ahead of the first query
+    ///
+    /// Loading a region from disk happens on its first use otherwise, which
+    /// stalls whoever enters it first. Preloading popular regions at startup
+    /// moves that cost out of play. Regions already loaded are left as they
+    /// are. How long each region took is logged under `PEBBLEVAULT`, and a
+    /// region that fails to load is logged and skipped so the rest still load.
+    ///
+    /// # Arguments
+    ///
+    /// * `region_ids` - UUIDs of stored or loaded regions to warm up
+    ///
+    /// # Returns
+    ///
+    /// Ok once every region is loaded, or the error of the first region that
+    /// could not be, after every other region was loaded
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pebble_vault::PebbleVault;
+    /// # let pebble_vault = PebbleVault::new().unwrap();
+    /// let region_id = pebble_vault.create_or_load_region([5000.0, 0.0, 0.0], 100.0).unwrap();
+    /// pebble_vault.preload_regions(&[region_id]).expect("Failed to preload regions");
+    /// ```
+    fn preload_regions(&self, region_ids: &[Uuid]) -> Result<(), PebbleVaultError> {
+        let mut first_error = None;
+        for &region_id in region_ids {
+            let started = Instant::now();
+            match region_geometry(region_id).and_then(|(center, radius)| self.create_or_load_region(center, radius)) {
+                Ok(_) => LOG.info(format_args!("Preloaded region {} in {:?}", region_id, started.elapsed())),
+                Err(e) => {
+                    LOG.warn(format_args!("Failed to preload region {}: {}", region_id, e));
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        first_error.map_or(Ok(()), Err)
+    }
+
+    /// Attaches a metadata value to a region, such as its biome or owning faction
+    ///
+    /// Metadata is saved with the region data by `persist_to_disk` and
+    /// autosave, survives unloading the region, and is dropped when the region
+    /// is deleted.
+    ///
+    /// # Arguments
+    ///
+    /// * `region_id` - UUID of a loaded region
+    /// * `key` - Name of the value, replacing any earlier value of the same name
+    /// * `value` - Any JSON value
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pebble_vault::PebbleVault;
+    /// # let pebble_vault = PebbleVault::new().unwrap();
+    /// let region_id = pebble_vault.create_or_load_region([0.0, 0.0, 0.0], 1000.0).unwrap();
+    /// pebble_vault.set_region_metadata(region_id, "biome", serde_json::json!("desert")).unwrap();
+    /// ```
+    fn set_region_metadata(&self, region_id: Uuid, key: &str, value: serde_json::Value) -> Result<(), PebbleVaultError> {
+        if !VAULT_MANAGER.read().unwrap().regions.contains_key(&region_id) {
+            return Err(PebbleVaultError::RegionNotFound(region_id));
+        }
+        REGION_METADATA
+            .lock()
+            .unwrap()
+            .entry(region_id)
+            .or_default()
+            .insert(key.to_string(), value);
+        Ok(())
+    }
+
+    /// Reads a metadata value set with `set_region_metadata`
+    ///
+    /// # Returns
+    ///
+    /// The value, or None if the region has no value under `key`
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pebble_vault::PebbleVault;
+    /// # let pebble_vault = PebbleVault::new().unwrap();
+    /// # let region_id = pebble_vault.create_or_load_region([0.0, 0.0, 0.0], 1000.0).unwrap();
+    /// if let Some(biome) = pebble_vault.get_region_metadata(region_id, "biome") {
+    ///     println!("Biome: {}", biome);
+    /// }
+    /// ```
+    fn get_region_metadata(&self, region_id: Uuid, key: &str) -> Option<serde_json::Value> {
+        REGION_METADATA.lock().unwrap().get(&region_id)?.get(key).cloned()
+    }
+
+    /// Deletes an empty region
+    ///
+    /// This method removes the region from memory and from the persistent store, so
+    /// a later `persist_to_disk` will not bring it back. Regions that still contain
+    /// objects are left untouched; use `delete_region_force` to remove those.
+    ///
+    /// # Arguments
+    ///
+    /// * `region_id` - UUID of the region to delete
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success, or an error string if the region does not
+    /// exist or is not empty
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pebble_vault::PebbleVault;
+    /// # let pebble_vault = PebbleVault::new().unwrap();
+    /// let region_id = pebble_vault.create_or_load_region([5000.0, 0.0, 0.0], 100.0).unwrap();
+    /// pebble_vault.delete_region(region_id).expect("Failed to delete region");
+    /// assert!(!pebble_vault.list_regions().contains(&region_id));
+    /// ```
+    fn delete_region(&self, region_id: Uuid) -> Result<(), PebbleVaultError> {
+        delete_region_inner(&mut VAULT_MANAGER.write().unwrap(), region_id, false)?;
+        announce_region(REGION_UNLOADED_EVENT, region_id);
+        Ok(())
+    }
+
+    /// Deletes a region along with every object inside it
+    ///
+    /// # Arguments
+    ///
+    /// * `region_id` - UUID of the region to delete
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success, or an error string if the region does not exist
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pebble_vault::{PebbleVault, PebbleVaultCustomData};
+    /// # use uuid::Uuid;
+    /// # let pebble_vault = PebbleVault::new().unwrap();
+    /// let region_id = pebble_vault.create_or_load_region([5000.0, 0.0, 0.0], 100.0).unwrap();
+    /// # let custom_data = PebbleVaultCustomData { name: "Chest".to_string(), value: 1, velocity: [0.0; 3], health: None };
+    /// # pebble_vault.add_object(region_id, Uuid::new_v4(), "item", 5000.0, 0.0, 0.0, custom_data).unwrap();
+    /// pebble_vault.delete_region_force(region_id).expect("Failed to delete region");
+    /// ```
+    fn delete_region_force(&self, region_id: Uuid) -> Result<(), PebbleVaultError> {
+        delete_region_inner(&mut VAULT_MANAGER.write().unwrap(), region_id, true)?;
+        announce_region(REGION_UNLOADED_EVENT, region_id);
+        Ok(())
+    }
+
+    /// Persists a region and drops it from memory
+    ///
+    /// Unlike `delete_region`, the region and its objects stay in the persistent
+    /// store and come back the next time the region is created or loaded.
+    ///
+    /// # Arguments
+    ///
+    /// * `region_id` - UUID of the region to unload
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success, or an error string if the region does not
+    /// exist or could not be persisted
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pebble_vault::PebbleVault;
+    /// # let pebble_vault = PebbleVault::new().unwrap();
+    /// let region_id = pebble_vault.create_or_load_region([5000.0, 0.0, 0.0], 100.0).unwrap();
+    /// pebble_vault.unload_region(region_id).expect("Failed to unload region");
+    /// ```
+    fn unload_region(&self, region_id: Uuid) -> Result<(), PebbleVaultError> {
+        // Writers keep the region map locked for as long as they use a region, so
+        // once the region is out of the map nothing can write to it any more and
+        // it can be persisted without holding up the rest of the vault
+        let region = VAULT_MANAGER
+            .write()
+            .unwrap()
+            .regions
+            .remove(&region_id)
+            .ok_or(PebbleVaultError::RegionNotFound(region_id))?;
+
+        let persisted = persist_region(&VAULT_MANAGER.read().unwrap(), region_id, &region).and_then(|()| save_region_metadata());
+        if let Err(e) = persisted {
+            // Nothing was dropped yet, so put the region back as it was
+            VAULT_MANAGER.write().unwrap().regions.insert(region_id, region);
+            return Err(e);
+        }
+        let bounds = {
+            let region = region.lock().unwrap();
+            let mut object_regions = OBJECT_REGIONS.lock().unwrap();
+            for object in region.rtree.iter() {
+                object_regions.remove(&object.uuid);
+            }
+            RegionBounds { region_id, center: region.center, radius: region.radius }
+        };
+        unindex_region(bounds);
+        TYPE_COUNTS.lock().unwrap().remove(&region_id);
+        announce_region(REGION_UNLOADED_EVENT, region_id);
+        Ok(())
+    }
+
+    /// Counts the objects of each type in a region
+    ///
+    /// Counts are kept up to date as objects are added, removed and
+    /// transferred, so this does not scan the region.
+    ///
+    /// # Arguments
+    ///
+    /// * `region_id` - UUID of the region to count
+    ///
+    /// # Returns
+    ///
+    /// A map from object type to the number of objects of that type. Types
+    /// with no objects, and regions that are not loaded, have no entries.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pebble_vault::PebbleVault;
+    /// # let pebble_vault = PebbleVault::new().unwrap();
+    /// # let region_id = pebble_vault.create_or_load_region([0.0, 0.0, 0.0], 1000.0).unwrap();
+    /// let enemies = pebble_vault.count_by_type(region_id).get("enemy").copied().unwrap_or(0);
+    /// println!("{} enemies in the region", enemies);
+    /// ```
+    fn count_by_type(&self, region_id: Uuid) -> HashMap<String, usize> {
+        TYPE_COUNTS.lock().unwrap().get(&region_id).cloned().unwrap_or_default()
+    }
+
+    /// Writes a region and all of its objects to a portable JSON file
+    ///
+    /// # Arguments
+    ///
+    /// * `region_id` - UUID of the region to export
+    /// * `path` - File to write, replaced if it already exists
+    ///
+    /// # Returns
+    ///
+    /// A Result indicating success, or an error string if the region does not
+    /// exist or the file could not be written
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pebble_vault::PebbleVault;
+    /// # use std::path::Path;
+    /// # let pebble_vault = PebbleVault::new().unwrap();
+    /// let region_id = pebble_vault.create_or_load_region([5000.0, 0.0, 0.0], 100.0).unwrap();
+    /// pebble_vault.export_region(region_id, Path::new("region.json")).expect("Failed to export region");
+    /// ```
+    fn export_region(&self, region_id: Uuid, path: &Path) -> Result<(), PebbleVaultError> {
+        let vault_manager = VAULT_MANAGER.read().unwrap();
+        let (center, radius) = match vault_manager.regions.get(&region_id) {
+            Some(region) => {
+                let region = region.lock().unwrap();
+                (region.center, region.radius)
+            }
+            None => return Err(PebbleVaultError::RegionNotFound(region_id)),
+        };
+
+        let objects = vault_manager
+            .query_region(
+                region_id,
+                center[0] - radius, center[1] - radius, center[2] - radius,
+                center[0] + radius, center[1] + radius, center[2] + radius,
+            )?
+            .into_iter()
+            .map(|object| ExportedObject {
+                uuid: object.uuid,
+                object_type: object.object_type.clone(),
+                point: object.point,
+                custom_data: (*object.custom_data).clone(),
+            })
+            .collect();
+        drop(vault_manager);
+
+        let export = RegionExport { version: REGION_EXPORT_VERSION, region_id, center, radius, objects };
+        let contents = serde_json::to_string_pretty(&export).map_err(|e| PebbleVaultError::InvalidExport(e.to_string()))?;
+        std::fs::write(path, contents).map_err(|e| PebbleVaultError::Io(format!("Failed to write {}: {}", path.display(), e)))
+    }
+
+    /// Loads a region previously written by `export_region`
+    ///
+    /// The region is created with the exported center and radius, so it gets
+    /// a fresh id unless a region with the same bounds is already loaded.
+    /// Objects keep their original ids. Every object is checked before
+    /// anything is added, so an id that already exists or appears twice, or
+    /// a position that is invalid, outside the world bounds or outside the
+    /// exported region's radius, refuses the whole import,
+    /// and the objects are then added to the region in a single step.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - File written by `export_region`
+    ///
+    /// # Returns
+    ///
+    /// A Result containing the UUID of the imported region, or an error string
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use pebble_vault::PebbleVault;
+    /// # use std::path::Path;
+    /// # let pebble_vault = PebbleVault::new().unwrap();
+    /// let region_id = pebble_vault.import_region(Path::new("region.json")).expect("Failed to import region");
+    /// println!("Imported region {}", region_id);
+    /// ```
+    fn import_region(&self, path: &Path) -> Result<Uuid, PebbleVaultError> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| PebbleVaultError::Io(format!("Failed to read {}: {}", path.display(), e)))?;
+        let export: RegionExport = serde_json::from_str(&contents)
+            .map_err(|e| PebbleVaultError::InvalidExport(format!("Failed to parse {}: {}", path.display(), e)))?;
+        if export.version != REGION_EXPORT_VERSION {
+            return Err(PebbleVaultError::InvalidExport(format!("Unsupported region export version {}", export.version)));
+        }
+
+        let mut seen = std::collections::HashSet::new();
+        for object in &export.objects {
+            check_coordinates(object.uuid, object.point)?;
+            if distance(export.center, object.point) > export.radius {
+                return Err(PebbleVaultError::InvalidExport(format!(
+                    "Object {} at ({}, {}, {}) lies outside the region of radius {} around ({}, {}, {})",
+                    object.uuid, object.point[0], object.point[1], object.point[2],
+                    export.radius, export.center[0], export.center[1], export.center[2]
+                )));
+            }
+            if !seen.insert(object.uuid) || self.get_object(object.uuid)?.is_some() {
+                return Err(PebbleVaultError::ObjectExists(object.uuid));
+            }
+        }
+
+        let region_id = self.create_or_load_region(export.center, export.radius)?;
+        let vault_manager = VAULT_MANAGER.read().unwrap();
+        let mut region = loaded_region(&vault_manager, region_id)?.lock().unwrap();
+        for object in export.objects {
+            place_object(region_id, &mut region, SpatialObject {
+                uuid: object.uuid,
+                object_type: object.object_type,
+                point: object.point,
+                custom_data: Arc::new(object.custom_data),
+            })?;
+        }
+        Ok(region_id)
+    }
+    
+    fn new() -> Plugin {
+        Plugin{}
+    }
+}
+/// Vault setup shared by the tests of every module that uses PebbleVault
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    /// Opens the vault in a scratch directory shared by every test in this process
+    pub(crate) fn vault() -> Plugin {
+        let data_dir = std::env::temp_dir().join(format!("pebblevault-tests-{}", std::process::id()));
+        std::fs::create_dir_all(&data_dir).unwrap();
+        let _ = init_vault(data_dir.to_string_lossy().into_owned());
+        Plugin {}
+    }
+
+    /// Center of a region no other test uses
+    pub(crate) fn unique_center() -> [f64; 3] {
+        static NEXT: AtomicUsize = AtomicUsize::new(1);
+        [NEXT.fetch_add(1, Ordering::Relaxed) as f64 * 10_000.0, 0.0, 0.0]
+    }
+
+    pub(crate) fn data(name: &str) -> PebbleVaultCustomData {
+        PebbleVaultCustomData { name: name.to_string(), value: 0, velocity: [0.0; 3], health: None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::test_support::{data, unique_center, vault};
+
+    fn ids(objects: &[SpatialObject<PebbleVaultCustomData>]) -> Vec<Uuid> {
+        let mut ids: Vec<Uuid> = objects.iter().map(|object| object.uuid).collect();
+        ids.sort();
+        ids
+    }
+
+    fn ids_in_order(objects: &[SpatialObject<PebbleVaultCustomData>]) -> Vec<Uuid> {
+        objects.iter().map(|object| object.uuid).collect()
+    }
+
+    /// A fresh UUID for which `keep` holds
+    fn uuid_where(keep: impl Fn(Uuid) -> bool) -> Uuid {
+        std::iter::repeat_with(Uuid::new_v4).find(|id| keep(*id)).unwrap()
+    }
+
+    #[test]
+    fn the_data_directory_is_chosen_only_once() {
+        vault();
+        let chosen = vault_data_dir();
+
+        let second = init_vault(std::env::temp_dir().join("pebblevault-elsewhere").to_string_lossy().into_owned());
+
+        assert!(second.unwrap_err().contains(&chosen));
+        assert_eq!(vault_data_dir(), chosen);
+    }
+
+    /// Tells `data_directory_step` what to do when it runs in a child process
+    const STEP_ENV: &str = "PEBBLEVAULT_TEST_STEP";
+
+    /// One step of `vaults_in_different_directories_do_not_share_objects`
+    ///
+    /// The data directory is chosen once per process, so each directory is
+    /// opened by a child process running only this test with `STEP_ENV` set.
+    /// Run any other way it does nothing.
+    #[test]
+    fn data_directory_step() {
+        let Ok(step) = std::env::var(STEP_ENV) else {
+            return;
+        };
+        init_vault(vault_data_dir_from_env()).unwrap();
+        let vault = Plugin {};
+        match step.split(':').collect::<Vec<_>>().as_slice() {
+            ["write", object_id] => {
+                let region_id = vault.create_or_load_region([0.0; 3], 100.0).unwrap();
+                vault.add_object(region_id, Uuid::parse_str(object_id).unwrap(), "item", 1.0, 2.0, 3.0, data("written")).unwrap();
+                vault.persist_to_disk().unwrap();
+                println!("region={}", region_id);
+            }
+            ["read", region_id, object_id] => {
+                // Fails where the region was never stored, which is the point
+                let _ = vault.preload_regions(&[Uuid::parse_str(region_id).unwrap()]);
+                println!("found={}", vault.get_object(Uuid::parse_str(object_id).unwrap()).unwrap().is_some());
+            }
+            _ => panic!("unknown step {}", step),
+        }
+    }
+
+    /// Runs `data_directory_step` in a child process with its vault in `data_dir`, returning its output
+    fn run_step(data_dir: &Path, step: &str) -> String {
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", "server::vault_lib::tests::data_directory_step", "--nocapture"])
+            .env(VAULT_DATA_DIR_ENV, data_dir)
+            .env(STEP_ENV, step)
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        assert!(output.status.success(), "step {} failed: {}{}", step, stdout, String::from_utf8_lossy(&output.stderr));
+        stdout
+    }
+
+    /// Value a step printed as `key=value`
+    ///
+    /// The test harness may print the test's name on the same line first.
+    fn printed<'a>(stdout: &'a str, key: &str) -> &'a str {
+        let marker = format!("{}=", key);
+        stdout
+            .lines()
+            .find_map(|line| line.split_once(marker.as_str()).map(|(_, value)| value.trim()))
+            .unwrap_or_else(|| panic!("no {} in {}", key, stdout))
+    }
+
+    #[test]
+    fn vaults_in_different_directories_do_not_share_objects() {
+        let root = std::env::temp_dir().join(format!("pebblevault-directories-{}", Uuid::new_v4()));
+        let (first, second) = (root.join("first"), root.join("second"));
+        std::fs::create_dir_all(&first).unwrap();
+        std::fs::create_dir_all(&second).unwrap();
+        let object_id = Uuid::new_v4();
+
+        let written = run_step(&first, &format!("write:{}", object_id));
+        let read = |data_dir: &Path| {
+            let output = run_step(data_dir, &format!("read:{}:{}", printed(&written, "region"), object_id));
+            printed(&output, "found").to_string()
+        };
+
+        assert_eq!(read(&first), "true");
+        assert_eq!(read(&second), "false");
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn typed_query_returns_only_the_requested_types() {
+        let vault = vault();
+        let center = unique_center();
+        let region_id = vault.create_or_load_region(center, 100.0).unwrap();
+        let [x, y, z] = center;
+        let (player, npc, item, far_player) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        vault.add_object(region_id, player, "player", x, y, z, data("player")).unwrap();
+        vault.add_object(region_id, npc, "npc", x + 1.0, y, z, data("npc")).unwrap();
+        vault.add_object(region_id, item, "item", x + 2.0, y, z, data("item")).unwrap();
+        vault.add_object(region_id, far_player, "player", x + 50.0, y, z, data("far")).unwrap();
+
+        let found = vault
+            .query_region_typed(region_id, x - 10.0, y - 10.0, z - 10.0, x + 10.0, y + 10.0, z + 10.0, &["player", "npc"])
+            .unwrap();
+
+        let mut expected = vec![player, npc];
+        expected.sort();
+        assert_eq!(ids(&found), expected);
+    }
+
+    #[test]
+    fn unloading_keeps_the_region_on_disk_and_other_regions_loaded() {
+        let vault = vault();
+        let (unloaded_center, kept_center) = (unique_center(), unique_center());
+        let unloaded = vault.create_or_load_region(unloaded_center, 100.0).unwrap();
+        let kept = vault.create_or_load_region(kept_center, 100.0).unwrap();
+        let (stored, resident) = (Uuid::new_v4(), Uuid::new_v4());
+        let [x, y, z] = unloaded_center;
+        vault.add_object(unloaded, stored, "item", x, y, z, data("stored")).unwrap();
+        let [x, y, z] = kept_center;
+        vault.add_object(kept, resident, "item", x, y, z, data("resident")).unwrap();
+
+        vault.unload_region(unloaded).unwrap();
+        assert!(!vault.list_regions().contains(&unloaded));
+        assert!(vault.get_object(resident).unwrap().is_some());
+        assert!(matches!(vault.unload_region(unloaded), Err(PebbleVaultError::RegionNotFound(_))));
+
+        assert_eq!(vault.region_containing(unloaded_center), None);
+        let reloaded = vault.create_or_load_region(unloaded_center, 100.0).unwrap();
+        let [x, y, z] = unloaded_center;
+        let found = vault.query_region(reloaded, x - 1.0, y - 1.0, z - 1.0, x + 1.0, y + 1.0, z + 1.0).unwrap();
+        assert_eq!(ids(&found), vec![stored]);
+        assert_eq!(vault.region_of(stored), Some(reloaded));
+    }
+
+    #[test]
+    fn writes_keep_the_object_index_and_type_counts_in_step() {
+        let vault = vault();
+        let (home_center, away_center) = (unique_center(), unique_center());
+        let home = vault.create_or_load_region(home_center, 100.0).unwrap();
+        let away = vault.create_or_load_region(away_center, 100.0).unwrap();
+        let [x, y, z] = home_center;
+        let (player, chest) = (Uuid::new_v4(), Uuid::new_v4());
+        vault.add_object(home, player, "player", x, y, z, data("player")).unwrap();
+        vault.add_object(home, chest, "item", x + 1.0, y, z, data("chest")).unwrap();
+        assert_eq!(vault.count_by_type(home), HashMap::from([("player".to_string(), 1), ("item".to_string(), 1)]));
+
+        vault.transfer_player(player, home, away).unwrap();
+        assert_eq!(vault.region_of(player), Some(away));
+        assert_eq!(vault.count_by_type(home), HashMap::from([("item".to_string(), 1)]));
+        assert_eq!(vault.count_by_type(away), HashMap::from([("player".to_string(), 1)]));
+
+        let mut mimic = vault.get_object(chest).unwrap().unwrap();
+        mimic.object_type = "npc".to_string();
+        vault.update_object(&mimic).unwrap();
+        assert_eq!(vault.count_by_type(home), HashMap::from([("npc".to_string(), 1)]));
+
+        vault.remove_object(chest).unwrap();
+        assert_eq!(vault.region_of(chest), None);
+        assert!(vault.count_by_type(home).is_empty());
+        assert!(vault.get_object(chest).unwrap().is_none());
+    }
+
+    #[test]
+    fn objects_added_while_a_region_unloads_are_never_lost() {
+        let vault = vault();
+        let center = unique_center();
+        let region_id = vault.create_or_load_region(center, 100.0).unwrap();
+        let [x, y, z] = center;
+
+        let added: Vec<Uuid> = std::thread::scope(|scope| {
+            let writer = scope.spawn(|| {
+                (0..200)
+                    .map(|_| Uuid::new_v4())
+                    .filter(|id| vault.add_object(region_id, *id, "item", x, y, z, data("item")).is_ok())
+                    .collect()
+            });
+            std::thread::sleep(Duration::from_millis(1));
+            vault.unload_region(region_id).unwrap();
+            writer.join().unwrap()
+        });
+
+        assert!(added.iter().all(|id| vault.region_of(*id).is_none()));
+        let reloaded = vault.create_or_load_region(center, 100.0).unwrap();
+        let found = vault.query_region(reloaded, x - 1.0, y - 1.0, z - 1.0, x + 1.0, y + 1.0, z + 1.0).unwrap();
+        let mut added = added;
+        added.sort();
+        assert_eq!(ids(&found), added);
+    }
+
+    #[test]
+    fn missing_regions_and_objects_are_reported_by_variant() {
+        let vault = vault();
+        let center = unique_center();
+        let region_id = vault.create_or_load_region(center, 100.0).unwrap();
+        let (missing_region, missing_object) = (Uuid::new_v4(), Uuid::new_v4());
+        let [x, y, z] = center;
+
+        assert!(matches!(
+            vault.query_region(missing_region, x - 1.0, y - 1.0, z - 1.0, x + 1.0, y + 1.0, z + 1.0),
+            Err(PebbleVaultError::RegionNotFound(id)) if id == missing_region
+        ));
+        assert_eq!(
+            vault.add_object(missing_region, missing_object, "item", x, y, z, data("item")),
+            Err(PebbleVaultError::RegionNotFound(missing_region))
+        );
+        assert!(matches!(
+            vault.nearest_objects(missing_region, center, 1),
+            Err(PebbleVaultError::RegionNotFound(id)) if id == missing_region
+        ));
+        assert_eq!(vault.delete_region(missing_region), Err(PebbleVaultError::RegionNotFound(missing_region)));
+        assert_eq!(vault.remove_object(missing_object), Err(PebbleVaultError::ObjectNotFound(missing_object)));
+        assert_eq!(
+            vault.transfer_player(missing_object, region_id, vault.create_or_load_region(unique_center(), 100.0).unwrap()),
+            Err(PebbleVaultError::ObjectNotFound(missing_object))
+        );
+    }
+
+    #[test]
+    fn deleted_regions_are_no_longer_listed() {
+        let vault = vault();
+        let region_id = vault.create_or_load_region(unique_center(), 100.0).unwrap();
+        assert!(vault.list_regions().contains(&region_id));
+
+        vault.delete_region(region_id).unwrap();
+
+        assert!(!vault.list_regions().contains(&region_id));
+    }
+
+    #[test]
+    fn only_a_forced_delete_removes_a_region_with_objects() {
+        let vault = vault();
+        let center = unique_center();
+        let region_id = vault.create_or_load_region(center, 100.0).unwrap();
+        let [x, y, z] = center;
+        let (first, second) = (Uuid::new_v4(), Uuid::new_v4());
+        vault.add_object(region_id, first, "item", x, y, z, data("first")).unwrap();
+        vault.add_object(region_id, second, "item", x + 1.0, y, z, data("second")).unwrap();
+
+        assert_eq!(vault.delete_region(region_id), Err(PebbleVaultError::RegionNotEmpty { region_id, objects: 2 }));
+        assert!(vault.list_regions().contains(&region_id));
+
+        vault.delete_region_force(region_id).unwrap();
+        assert!(!vault.list_regions().contains(&region_id));
+        assert!(vault.get_object(first).unwrap().is_none());
+        assert!(vault.get_object(second).unwrap().is_none());
+    }
+
+    #[test]
+    fn a_deleted_region_stays_deleted_after_persisting() {
+        let vault = vault();
+        let region_id = vault.create_or_load_region(unique_center(), 100.0).unwrap();
+        vault.delete_region(region_id).unwrap();
+
+        vault.persist_to_disk().unwrap();
+
+        assert!(!vault.list_regions().contains(&region_id));
+        // Nothing is left on disk to load it from either
+        assert_eq!(vault.preload_regions(&[region_id]), Err(PebbleVaultError::RegionNotFound(region_id)));
+        assert!(!vault.list_regions().contains(&region_id));
+    }
+
+    #[test]
+    fn a_preloaded_region_is_loaded_before_its_first_query() {
+        let vault = vault();
+        let center = unique_center();
+        let region_id = vault.create_or_load_region(center, 100.0).unwrap();
+        let [x, y, z] = center;
+        let stored = Uuid::new_v4();
+        vault.add_object(region_id, stored, "item", x, y, z, data("stored")).unwrap();
+        vault.unload_region(region_id).unwrap();
+        let missing = Uuid::new_v4();
+
+        // A region that can't be found doesn't stop the ones after it
+        let result = vault.preload_regions(&[missing, region_id]);
+
+        assert_eq!(result, Err(PebbleVaultError::RegionNotFound(missing)));
+        assert!(vault.list_regions().contains(&region_id));
+        let found = vault.query_region(region_id, x - 1.0, y - 1.0, z - 1.0, x + 1.0, y + 1.0, z + 1.0).unwrap();
+        assert_eq!(ids(&found), vec![stored]);
+    }
+
+    #[test]
+    fn region_metadata_is_set_overwritten_and_read_back() {
+        let vault = vault();
+        let region_id = vault.create_or_load_region(unique_center(), 100.0).unwrap();
+        let missing = Uuid::new_v4();
+
+        vault.set_region_metadata(region_id, "biome", serde_json::json!("forest")).unwrap();
+        assert_eq!(vault.get_region_metadata(region_id, "biome"), Some(serde_json::json!("forest")));
+        vault.set_region_metadata(region_id, "biome", serde_json::json!("desert")).unwrap();
+        assert_eq!(vault.get_region_metadata(region_id, "biome"), Some(serde_json::json!("desert")));
+
+        assert_eq!(vault.get_region_metadata(region_id, "faction"), None);
+        assert_eq!(
+            vault.set_region_metadata(missing, "biome", serde_json::json!("forest")),
+            Err(PebbleVaultError::RegionNotFound(missing))
+        );
+    }
+
+    #[test]
+    fn region_metadata_is_saved_on_unload_and_dropped_on_delete() {
+        let vault = vault();
+        let (unloaded, deleted) = (
+            vault.create_or_load_region(unique_center(), 100.0).unwrap(),
+            vault.create_or_load_region(unique_center(), 100.0).unwrap(),
+        );
+        vault.set_region_metadata(unloaded, "faction", serde_json::json!("guild")).unwrap();
+        vault.set_region_metadata(deleted, "faction", serde_json::json!("horde")).unwrap();
+
+        vault.unload_region(unloaded).unwrap();
+        vault.delete_region(deleted).unwrap();
+
+        let saved = load_region_metadata();
+        assert_eq!(saved[&unloaded]["faction"], serde_json::json!("guild"));
+        assert!(!saved.contains_key(&deleted));
+        assert_eq!(vault.get_region_metadata(unloaded, "faction"), Some(serde_json::json!("guild")));
+        assert_eq!(vault.get_region_metadata(deleted, "faction"), None);
+    }
+
+    #[test]
+    fn plugins_hear_when_regions_load_and_unload() {
+        let vault = vault();
+        let announced = Arc::new(Mutex::new(Vec::new()));
+        for event_type in [REGION_LOADED_EVENT, REGION_UNLOADED_EVENT] {
+            let announced = Arc::clone(&announced);
+            plugin_api::global_bus().register_custom_event(event_type, move |event| {
+                if let horizon_events::GameEvent::Custom { event_type, data } = event {
+                    announced.lock().unwrap().push((event_type.clone(), data["region"].as_str().unwrap_or_default().to_string()));
+                }
+            });
+        }
+        let announced_for = |region_id: Uuid| -> Vec<String> {
+            let region_id = region_id.to_string();
+            announced.lock().unwrap().iter().filter(|(_, region)| *region == region_id).map(|(event_type, _)| event_type.clone()).collect()
+        };
+
+        let center = unique_center();
+        let unloaded = vault.create_or_load_region(center, 100.0).unwrap();
+        assert_eq!(vault.create_or_load_region(center, 100.0).unwrap(), unloaded);
+        vault.unload_region(unloaded).unwrap();
+        let deleted = vault.create_or_load_region(unique_center(), 100.0).unwrap();
+        vault.delete_region(deleted).unwrap();
+
+        assert_eq!(announced_for(unloaded), vec![REGION_LOADED_EVENT, REGION_UNLOADED_EVENT]);
+        assert_eq!(announced_for(deleted), vec![REGION_LOADED_EVENT, REGION_UNLOADED_EVENT]);
+    }
+
+    #[test]
+    fn a_still_object_is_predicted_where_it_is() {
+        let vault = vault();
+        let center = unique_center();
+        let region_id = vault.create_or_load_region(center, 100.0).unwrap();
+        let [x, y, z] = center;
+        let crate_id = Uuid::new_v4();
+        vault.add_object(region_id, crate_id, "item", x + 1.0, y, z, data("crate")).unwrap();
+
+        assert_eq!(vault.predict_position(crate_id, 5.0), Some([x + 1.0, y, z]));
+        assert_eq!(vault.predict_position(Uuid::new_v4(), 5.0), None);
+    }
+
+    #[test]
+    fn a_moving_object_is_predicted_along_its_velocity() {
+        let vault = vault();
+        let center = unique_center();
+        let region_id = vault.create_or_load_region(center, 100.0).unwrap();
+        let [x, y, z] = center;
+        let runner = Uuid::new_v4();
+        let moving = PebbleVaultCustomData { velocity: [2.0, -1.0, 0.5], ..data("runner") };
+        vault.add_object(region_id, runner, "player", x, y, z, moving).unwrap();
+
+        assert_eq!(vault.predict_position(runner, 0.0), Some([x, y, z]));
+        assert_eq!(vault.predict_position(runner, 2.0), Some([x + 4.0, y - 2.0, z + 1.0]));
+    }
+
+    #[test]
+    fn an_object_moved_past_the_edge_lands_in_the_next_region() {
+        let vault = vault();
+        let center = unique_center();
+        let [x, y, z] = center;
+        let west = vault.create_or_load_region(center, 100.0).unwrap();
+        let east = vault.create_or_load_region([x + 200.0, y, z], 100.0).unwrap();
+        let runner = Uuid::new_v4();
+        vault.add_object(west, runner, "player", x + 95.0, y, z, data("runner")).unwrap();
+
+        let mut moved = vault.get_object(runner).unwrap().unwrap();
+        moved.point = [x + 105.0, y, z];
+        vault.update_object(&moved).unwrap();
+
+        let found = vault.query_region(east, x + 100.0, y - 1.0, z - 1.0, x + 110.0, y + 1.0, z + 1.0).unwrap();
+        assert_eq!(ids(&found), vec![runner]);
+        assert_eq!(found[0].point, [x + 105.0, y, z]);
+        assert!(vault.query_region(west, x, y - 1.0, z - 1.0, x + 110.0, y + 1.0, z + 1.0).unwrap().is_empty());
+        assert_eq!(vault.region_of(runner), Some(east));
+    }
+
+    #[test]
+    fn the_smallest_region_around_a_point_contains_it() {
+        let vault = vault();
+        let center = unique_center();
+        let [x, y, z] = center;
+        let world = vault.create_or_load_region(center, 1000.0).unwrap();
+        let dungeon = vault.create_or_load_region([x + 100.0, y, z], 10.0).unwrap();
+
+        assert_eq!(vault.region_containing([x + 105.0, y, z]), Some(dungeon));
+        assert_eq!(vault.region_containing([x + 200.0, y, z]), Some(world));
+        // Inside the world's bounding box but outside its sphere
+        assert_eq!(vault.region_containing([x + 900.0, y + 900.0, z]), None);
+    }
+
+    #[test]
+    fn non_finite_coordinates_are_rejected() {
+        let vault = vault();
+        let center = unique_center();
+        let region_id = vault.create_or_load_region(center, 100.0).unwrap();
+        let object_id = Uuid::new_v4();
+
+        let error = vault.add_object(region_id, object_id, "item", f64::NAN, 0.0, 0.0, data("nan")).unwrap_err();
+        assert!(matches!(error, PebbleVaultError::InvalidCoordinate { .. }));
+        assert!(error.to_string().contains("non-finite coordinate"));
+        assert!(vault.get_object(object_id).unwrap().is_none());
+
+        let [x, y, z] = center;
+        vault.add_object(region_id, object_id, "item", x, y, z, data("moved")).unwrap();
+        let mut object = vault.get_object(object_id).unwrap().unwrap();
+        object.point = [x, f64::INFINITY, z];
+        assert!(matches!(vault.update_object(&object), Err(PebbleVaultError::InvalidCoordinate { .. })));
+        assert_eq!(vault.get_object(object_id).unwrap().unwrap().point, center);
+    }
+
+    #[test]
+    fn coordinates_outside_the_world_bounds_are_rejected() {
+        let bounds = WorldBounds { min: [-10.0; 3], max: [10.0; 3] };
+        let object_id = Uuid::new_v4();
+
+        assert!(check_coordinates_within(object_id, [10.0, -10.0, 0.0], Some(bounds)).is_ok());
+        let error = check_coordinates_within(object_id, [10.5, 0.0, 0.0], Some(bounds)).unwrap_err();
+        assert_eq!(error, PebbleVaultError::OutOfBounds { object_id, point: [10.5, 0.0, 0.0], bounds });
+        assert!(error.to_string().contains("outside the world bounds"));
+        assert!(check_coordinates_within(object_id, [1e12, 0.0, 0.0], None).is_ok());
+    }
+
+    #[test]
+    fn pages_cover_every_match_once() {
+        let vault = vault();
+        let center = unique_center();
+        let region_id = vault.create_or_load_region(center, 100.0).unwrap();
+        let [x, y, z] = center;
+        let mut expected = Vec::new();
+        for i in 0..25 {
+            let object_id = Uuid::new_v4();
+            vault.add_object(region_id, object_id, "item", x + i as f64, y, z, data("item")).unwrap();
+            expected.push(object_id);
+        }
+        // Outside the queried box, so it never shows up in a page
+        vault.add_object(region_id, Uuid::new_v4(), "item", x + 90.0, y, z, data("far")).unwrap();
+        expected.sort();
+
+        let mut seen = Vec::new();
+        let mut after = None;
+        loop {
+            let (page, has_more) = vault
+                .query_region_paged(region_id, x - 1.0, y - 1.0, z - 1.0, x + 30.0, y + 1.0, z + 1.0, after, 10)
+                .unwrap();
+            assert!(page.len() <= 10);
+            after = page.last().map(|object| object.uuid);
+            seen.extend(page);
+            if !has_more {
+                break;
+            }
+        }
+
+        assert_eq!(ids(&seen), expected);
+    }
+
+    #[test]
+    fn pages_come_in_uuid_order_up_to_the_last() {
+        let vault = vault();
+        let center = unique_center();
+        let region_id = vault.create_or_load_region(center, 100.0).unwrap();
+        let [x, y, z] = center;
+        let mut expected: Vec<Uuid> = (0..25).map(|_| Uuid::new_v4()).collect();
+        for (i, object_id) in expected.iter().enumerate() {
+            vault.add_object(region_id, *object_id, "item", x + i as f64, y, z, data("item")).unwrap();
+        }
+        expected.sort();
+        let page = |after| vault.query_region_paged(region_id, x - 1.0, y - 1.0, z - 1.0, x + 30.0, y + 1.0, z + 1.0, after, 10).unwrap();
+
+        let (first, has_more) = page(None);
+        assert_eq!(ids_in_order(&first), expected[..10]);
+        assert!(has_more);
+
+        let (middle, has_more) = page(Some(expected[9]));
+        assert_eq!(ids_in_order(&middle), expected[10..20]);
+        assert!(has_more);
+
+        let (last, has_more) = page(Some(expected[19]));
+        assert_eq!(ids_in_order(&last), expected[20..]);
+        assert!(!has_more);
+    }
+
+    #[test]
+    fn writes_between_pages_neither_repeat_nor_skip_objects() {
+        let vault = vault();
+        let center = unique_center();
+        let region_id = vault.create_or_load_region(center, 100.0).unwrap();
+        let [x, y, z] = center;
+        let mut expected: Vec<Uuid> = (0..20).map(|_| Uuid::new_v4()).collect();
+        for (i, object_id) in expected.iter().enumerate() {
+            vault.add_object(region_id, *object_id, "item", x + i as f64, y, z, data("item")).unwrap();
+        }
+        expected.sort();
+        let page = |after| vault.query_region_paged(region_id, x - 1.0, y - 1.0, z - 1.0, x + 30.0, y + 1.0, z + 1.0, after, 10).unwrap();
+
+        let (first, _) = page(None);
+        assert_eq!(ids_in_order(&first), expected[..10]);
+
+        // One object lands before the pages already read, one after them, and
+        // one not yet read is removed
+        let early = uuid_where(|id| id < expected[9]);
+        vault.add_object(region_id, early, "item", x, y, z + 0.5, data("early")).unwrap();
+        let latest = uuid_where(|id| id > expected[19]);
+        vault.add_object(region_id, latest, "item", x, y, z - 0.5, data("late")).unwrap();
+        vault.remove_object(expected[15]).unwrap();
+
+        let (rest, has_more) = page(first.last().map(|object| object.uuid));
+        assert!(!has_more);
+        let mut rest_expected: Vec<Uuid> = expected[10..].iter().copied().filter(|id| *id != expected[15]).collect();
+        rest_expected.push(latest);
+        assert_eq!(ids_in_order(&rest), rest_expected);
+    }
+
+    #[test]
+    fn an_object_id_is_placed_only_once() {
+        let vault = vault();
+        let first_center = unique_center();
+        let second_center = unique_center();
+        let first = vault.create_or_load_region(first_center, 100.0).unwrap();
+        let second = vault.create_or_load_region(second_center, 100.0).unwrap();
+        let object_id = Uuid::new_v4();
+        let [x, y, z] = first_center;
+        vault.add_object(first, object_id, "item", x, y, z, data("original")).unwrap();
+
+        let same_region = vault.add_object(first, object_id, "item", x + 1.0, y, z, data("copy"));
+        let [x, y, z] = second_center;
+        let other_region = vault.add_object(second, object_id, "item", x, y, z, data("copy"));
+
+        assert_eq!(same_region, Err(PebbleVaultError::ObjectExists(object_id)));
+        assert_eq!(other_region, Err(PebbleVaultError::ObjectExists(object_id)));
+        assert_eq!(vault.region_of(object_id), Some(first));
+        assert_eq!(vault.get_object(object_id).unwrap().unwrap().custom_data.name, "original");
+    }
+
+    #[test]
+    fn writes_to_one_region_do_not_wait_for_another() {
+        let vault = vault();
+        let busy_center = unique_center();
+        let free_center = unique_center();
+        let busy = vault.create_or_load_region(busy_center, 100.0).unwrap();
+        let free = vault.create_or_load_region(free_center, 100.0).unwrap();
+
+        // Holding one region's lock must not keep writers out of the other
+        let busy_region = vault.get_region(busy).unwrap();
+        let _held = busy_region.lock().unwrap();
+        let (done, finished) = std::sync::mpsc::channel();
+        let writer = std::thread::spawn(move || {
+            let [x, y, z] = free_center;
+            for i in 0..50 {
+                Plugin {}.add_object(free, Uuid::new_v4(), "item", x + i as f64, y, z, data("item")).unwrap();
+            }
+            done.send(()).unwrap();
+        });
+
+        assert!(finished.recv_timeout(Duration::from_secs(2)).is_ok());
+        writer.join().unwrap();
+        assert_eq!(vault.count_by_type(free).get("item"), Some(&50));
+    }
+
+    #[test]
+    fn type_counts_follow_adds_and_removes() {
+        let vault = vault();
+        let center = unique_center();
+        let region_id = vault.create_or_load_region(center, 100.0).unwrap();
+        let [x, y, z] = center;
+        let enemies: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+        for (i, enemy) in enemies.iter().enumerate() {
+            vault.add_object(region_id, *enemy, "enemy", x + i as f64, y, z, data("enemy")).unwrap();
+        }
+        vault.add_object(region_id, Uuid::new_v4(), "item", x, y + 1.0, z, data("item")).unwrap();
+
+        assert_eq!(vault.count_by_type(region_id), HashMap::from([("enemy".to_string(), 3), ("item".to_string(), 1)]));
+
+        vault.remove_object(enemies[0]).unwrap();
+        vault.remove_object(enemies[1]).unwrap();
+        assert_eq!(vault.count_by_type(region_id).get("enemy"), Some(&1));
+
+        vault.remove_object(enemies[2]).unwrap();
+        assert_eq!(vault.count_by_type(region_id), HashMap::from([("item".to_string(), 1)]));
+    }
+
+    #[test]
+    fn transfers_move_the_count_between_regions() {
+        let vault = vault();
+        let (from_center, to_center) = (unique_center(), unique_center());
+        let from = vault.create_or_load_region(from_center, 100.0).unwrap();
+        let to = vault.create_or_load_region(to_center, 100.0).unwrap();
+        let player = Uuid::new_v4();
+        let [x, y, z] = from_center;
+        vault.add_object(from, player, "player", x, y, z, data("player")).unwrap();
+
+        vault.transfer_player(player, from, to).unwrap();
+
+        assert!(vault.count_by_type(from).is_empty());
+        assert_eq!(vault.count_by_type(to), HashMap::from([("player".to_string(), 1)]));
+        assert_eq!(vault.region_of(player), Some(to));
+    }
+
+    fn exported(uuid: Uuid, point: [f64; 3]) -> ExportedObject {
+        ExportedObject { uuid, object_type: "item".to_string(), point, custom_data: data("imported") }
+    }
+
+    /// Writes an export of a region of radius 100 around `center`
+    fn write_export(center: [f64; 3], objects: Vec<ExportedObject>) -> PathBuf {
+        let export = RegionExport { version: REGION_EXPORT_VERSION, region_id: Uuid::new_v4(), center, radius: 100.0, objects };
+        let path = std::env::temp_dir().join(format!("pebblevault-import-{}.json", Uuid::new_v4()));
+        std::fs::write(&path, serde_json::to_string(&export).unwrap()).unwrap();
+        path
+    }
+
+    #[test]
+    fn importing_adds_every_object_to_the_region() {
+        let vault = vault();
+        let center = unique_center();
+        let [x, y, z] = center;
+        let (a, b) = (Uuid::new_v4(), Uuid::new_v4());
+        let path = write_export(center, vec![exported(a, center), exported(b, [x + 1.0, y, z])]);
+
+        let region_id = vault.import_region(&path).unwrap();
+
+        assert!(vault.get_object(a).unwrap().is_some());
+        assert!(vault.get_object(b).unwrap().is_some());
+        assert_eq!(vault.count_by_type(region_id).get("item"), Some(&2));
+    }
+
+    #[test]
+    fn a_bad_object_refuses_the_whole_import() {
+        let vault = vault();
+        let center = unique_center();
+        let [x, y, z] = center;
+        let (valid, duplicated) = (Uuid::new_v4(), Uuid::new_v4());
+        let path = write_export(center, vec![
+            exported(valid, center),
+            exported(duplicated, [x + 1.0, y, z]),
+            exported(duplicated, [x + 2.0, y, z]),
+        ]);
+
+        assert!(matches!(vault.import_region(&path), Err(PebbleVaultError::ObjectExists(id)) if id == duplicated));
+        assert!(vault.get_object(valid).unwrap().is_none());
+        assert!(vault.get_object(duplicated).unwrap().is_none());
+    }
+
+    #[test]
+    fn objects_outside_the_region_refuse_the_import() {
+        let vault = vault();
+        let center = unique_center();
+        let [x, y, z] = center;
+        let (inside, outside) = (Uuid::new_v4(), Uuid::new_v4());
+        let path = write_export(center, vec![exported(inside, center), exported(outside, [x + 150.0, y, z])]);
+
+        match vault.import_region(&path) {
+            Err(PebbleVaultError::InvalidExport(reason)) => assert!(reason.contains(&outside.to_string()), "{}", reason),
+            other => panic!("import outside the region was not refused: {:?}", other),
+        }
+        assert!(vault.get_object(inside).unwrap().is_none());
+    }
+
+    #[test]
+    fn an_exported_region_imports_back_as_it_was() {
+        let vault = vault();
+        let center = unique_center();
+        let [x, y, z] = center;
+        let region_id = vault.create_or_load_region(center, 100.0).unwrap();
+        for (i, name) in ["crate", "barrel", "chest"].into_iter().enumerate() {
+            vault.add_object(region_id, Uuid::new_v4(), name, x + 10.0 * i as f64, y - 5.0, z + 2.5, data(name)).unwrap();
+        }
+        let contents = |region_id| {
+            let mut objects: Vec<(Uuid, String, [f64; 3], String)> = vault
+                .query_region(region_id, x - 100.0, y - 100.0, z - 100.0, x + 100.0, y + 100.0, z + 100.0)
+                .unwrap()
+                .into_iter()
+                .map(|object| (object.uuid, object.object_type.clone(), object.point, object.custom_data.name.clone()))
+                .collect();
+            objects.sort_by_key(|object| object.0);
+            objects
+        };
+        let before = contents(region_id);
+        let path = std::env::temp_dir().join(format!("pebblevault-roundtrip-{}.json", Uuid::new_v4()));
+
+        vault.export_region(region_id, &path).unwrap();
+        vault.delete_region_force(region_id).unwrap();
+        assert!(before.iter().all(|(object_id, ..)| vault.get_object(*object_id).unwrap().is_none()));
+        let imported = vault.import_region(&path).unwrap();
+
+        assert_eq!(before.len(), 3);
+        assert_eq!(contents(imported), before);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,75 @@
+//-----------------------------------------------------------------------------
+// Async PebbleVault Access
+//   - Async versions of the PluginAPI calls used from socket handlers
+//   - Each call runs on Tokio's blocking pool, so waiting on the vault lock
+//     never stalls a runtime worker thread
+//-----------------------------------------------------------------------------
+
+use super::{PebbleVaultCustomData, PebbleVaultError, Plugin, PluginAPI};
+use PebbleVault::SpatialObject;
+use uuid::Uuid;
+
+/// Runs a vault call on the blocking pool
+async fn run<T, F>(call: F) -> Result<T, PebbleVaultError>
+where
+    F: FnOnce(Plugin) -> Result<T, PebbleVaultError> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(move || call(Plugin {}))
+        .await
+        .map_err(|e| PebbleVaultError::Storage(format!("PebbleVault task failed: {}", e)))?
+}
+
+/// Async version of `PluginAPI::get_object`
+pub async fn get_object(object_id: Uuid) -> Result<Option<SpatialObject<PebbleVaultCustomData>>, PebbleVaultError> {
+    run(move |vault| vault.get_object(object_id)).await
+}
+
+/// Async version of `PluginAPI::update_object`
+pub async fn update_object(object: SpatialObject<PebbleVaultCustomData>) -> Result<(), PebbleVaultError> {
+    run(move |vault| vault.update_object(&object)).await
+}
+
+/// Async version of `PluginAPI::remove_object`
+pub async fn remove_object(object_id: Uuid) -> Result<(), PebbleVaultError> {
+    run(move |vault| vault.remove_object(object_id)).await
+}
+
+/// Async version of `PluginAPI::query_sphere`
+pub async fn query_sphere(center: [f64; 3], radius: f64) -> Result<Vec<SpatialObject<PebbleVaultCustomData>>, PebbleVaultError> {
+    run(move |vault| vault.query_sphere(center, radius)).await
+}
+
+/// Async version of `PluginAPI::region_of`
+pub async fn region_of(object_id: Uuid) -> Result<Option<Uuid>, PebbleVaultError> {
+    run(move |vault| Ok(vault.region_of(object_id))).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::vault_lib::test_support::{unique_center, vault};
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn queries_waiting_on_a_region_leave_the_runtime_free() {
+        let vault = vault();
+        let center = unique_center();
+        let region_id = vault.create_or_load_region(center, 100.0).unwrap();
+        let region = vault.get_region(region_id).unwrap();
+        let held = region.lock().unwrap();
+
+        let queries: Vec<_> = (0..8).map(|_| tokio::spawn(query_sphere(center, 10.0))).collect();
+
+        // The queries wait on the region lock, yet the timer still fires on
+        // this single-threaded runtime
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(queries.iter().all(|query| !query.is_finished()));
+
+        drop(held);
+        for query in queries {
+            let result = tokio::time::timeout(Duration::from_secs(2), query).await.unwrap().unwrap();
+            assert!(result.is_ok());
+        }
+    }
+}
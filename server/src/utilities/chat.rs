@@ -0,0 +1,311 @@
+//-----------------------------------------------------------------------------
+// Chat System
+//   - Global, proximity and party channels
+//   - Per-player token bucket rate limiting
+//   - Length limits and control character stripping
+//-----------------------------------------------------------------------------
+
+use super::rate_limit::TokenBuckets;
+use crate::server;
+use horizon_events::GameEvent;
+use lazy_static::lazy_static;
+use parking_lot::{Mutex, RwLock};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use uuid::Uuid;
+
+lazy_static! {
+    static ref RATE_LIMITER: Mutex<TokenBuckets<Uuid>> = Mutex::new(TokenBuckets::new());
+    static ref PARTIES: RwLock<HashMap<String, Party>> = RwLock::new(HashMap::new());
+}
+
+/// A named party and who may join it
+struct Party {
+    /// The only member allowed to invite others
+    leader: Uuid,
+    members: HashSet<Uuid>,
+    /// Players invited by the leader who have not joined yet
+    invited: HashSet<Uuid>,
+}
+
+impl Party {
+    fn led_by(leader: Uuid) -> Self {
+        Self { leader, members: HashSet::from([leader]), invited: HashSet::new() }
+    }
+
+    /// Removes a member, handing leadership on if it was the leader
+    ///
+    /// Returns false once the party has no members left.
+    fn remove(&mut self, player_id: Uuid) -> bool {
+        self.members.remove(&player_id);
+        self.invited.remove(&player_id);
+        if self.leader == player_id {
+            match self.members.iter().min() {
+                Some(next) => self.leader = *next,
+                None => return false,
+            }
+        }
+        true
+    }
+}
+
+/// Channel a chat message is sent on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChatChannel {
+    /// Every connected player
+    Global,
+    /// Players within `chat_local_radius` of the sender
+    Local,
+    /// Members of the named party
+    Party(String),
+}
+
+impl ChatChannel {
+    /// Parses `"global"`, `"local"` or `"party:<name>"`
+    pub fn parse(channel: &str) -> Option<Self> {
+        match channel {
+            "global" => Some(ChatChannel::Global),
+            "local" => Some(ChatChannel::Local),
+            _ => channel
+                .strip_prefix("party:")
+                .filter(|name| !name.is_empty())
+                .map(|name| ChatChannel::Party(name.to_string())),
+        }
+    }
+
+    fn name(&self) -> String {
+        match self {
+            ChatChannel::Global => "global".to_string(),
+            ChatChannel::Local => "local".to_string(),
+            ChatChannel::Party(name) => format!("party:{}", name),
+        }
+    }
+}
+
+/// Reasons a chat message can be refused
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChatError {
+    /// The sender exceeded its message allowance
+    RateLimited,
+    /// The message is longer than `chat_max_length` characters
+    TooLong { max: usize },
+    /// Nothing was left after stripping control characters
+    Empty,
+    /// The sender is not a member of the party it tried to message
+    NotInParty(String),
+    /// The player tried to join a party it was not invited to
+    NotInvited(String),
+    /// Only the party's leader can invite players
+    NotPartyLeader(String),
+    /// Proximity chat needs a position and the sender has none yet
+    NoPosition,
+    /// Recipients could not be looked up
+    Delivery(String),
+}
+
+impl fmt::Display for ChatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChatError::RateLimited => write!(f, "Sending messages too quickly"),
+            ChatError::TooLong { max } => write!(f, "Message is longer than {} characters", max),
+            ChatError::Empty => write!(f, "Message is empty"),
+            ChatError::NotInParty(party) => write!(f, "Not a member of party {}", party),
+            ChatError::NotInvited(party) => write!(f, "Not invited to party {}", party),
+            ChatError::NotPartyLeader(party) => write!(f, "Only the leader of party {} can invite players", party),
+            ChatError::NoPosition => write!(f, "Local chat requires a position in the world"),
+            ChatError::Delivery(reason) => write!(f, "Failed to deliver message: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ChatError {}
+
+/// Removes control characters and surrounding whitespace, then enforces `max_length`
+fn sanitize(text: &str, max_length: usize) -> Result<String, ChatError> {
+    let cleaned: String = text.chars().filter(|c| !c.is_control()).collect();
+    let cleaned = cleaned.trim();
+
+    if cleaned.is_empty() {
+        return Err(ChatError::Empty);
+    }
+    if cleaned.chars().count() > max_length {
+        return Err(ChatError::TooLong { max: max_length });
+    }
+    Ok(cleaned.to_string())
+}
+
+/// Adds a player to a party
+///
+/// A party that does not exist yet is created with the player as its leader.
+/// Joining an existing party takes an invite from its leader, which is used up.
+pub fn join_party(player_id: Uuid, party: &str) -> Result<(), ChatError> {
+    let mut parties = PARTIES.write();
+    let Some(existing) = parties.get_mut(party) else {
+        parties.insert(party.to_string(), Party::led_by(player_id));
+        return Ok(());
+    };
+    if existing.members.contains(&player_id) {
+        return Ok(());
+    }
+    if !existing.invited.remove(&player_id) {
+        return Err(ChatError::NotInvited(party.to_string()));
+    }
+    existing.members.insert(player_id);
+    Ok(())
+}
+
+/// Lets `invitee` join a party led by `leader`
+pub fn invite_to_party(leader: Uuid, party: &str, invitee: Uuid) -> Result<(), ChatError> {
+    let mut parties = PARTIES.write();
+    match parties.get_mut(party) {
+        Some(existing) if existing.leader == leader => {
+            if !existing.members.contains(&invitee) {
+                existing.invited.insert(invitee);
+            }
+            Ok(())
+        }
+        _ => Err(ChatError::NotPartyLeader(party.to_string())),
+    }
+}
+
+/// Removes a player from a party, dropping the party once it is empty
+pub fn leave_party(player_id: Uuid, party: &str) {
+    let mut parties = PARTIES.write();
+    if let Some(existing) = parties.get_mut(party) {
+        if !existing.remove(player_id) {
+            parties.remove(party);
+        }
+    }
+}
+
+/// Forgets all chat state for a player that left the server
+pub fn remove_player(player_id: Uuid) {
+    RATE_LIMITER.lock().remove(&player_id);
+    PARTIES.write().retain(|_, party| party.remove(player_id));
+}
+
+/// Sends a chat message from `player_id` on `channel`
+///
+/// Recipients receive a `chat` event with the sender, channel and cleaned
+/// text. Only players in the sender's pool group can receive it, whatever
+/// the channel. Returns the number of players the message was delivered to.
+pub fn send_message(player_id: Uuid, channel: ChatChannel, text: &str) -> Result<usize, ChatError> {
+    let config = server::live_config();
+
+    let text = sanitize(text, config.chat_max_length)?;
+    if !RATE_LIMITER.lock().try_acquire(player_id, config.chat_rate_per_sec, config.chat_burst) {
+        return Err(ChatError::RateLimited);
+    }
+
+    let payload = serde_json::json!({
+        "sender": player_id.to_string(),
+        "channel": channel.name(),
+        "text": text,
+    });
+
+    let group = server::group_members(player_id);
+    let recipients: HashSet<Uuid> = match &channel {
+        ChatChannel::Global => group,
+        ChatChannel::Local => {
+            let position = server::player_position(player_id).ok_or(ChatError::NoPosition)?;
+            server::players_in_region(position, config.chat_local_radius)
+                .map_err(|e| ChatError::Delivery(format!("{:#}", e)))?
+                .into_iter()
+                .filter(|nearby| group.contains(nearby))
+                .collect()
+        }
+        ChatChannel::Party(party) => PARTIES
+            .read()
+            .get(party)
+            .filter(|existing| existing.members.contains(&player_id))
+            .map(|existing| existing.members.intersection(&group).copied().collect())
+            .ok_or_else(|| ChatError::NotInParty(party.clone()))?,
+    };
+    let delivered = server::emit_to_players(Some(&recipients), "chat", &payload);
+
+    // Only messages that actually went out are announced to plugins
+    server::emit_event(GameEvent::ChatMessage { player: player_id, channel: channel.name(), text });
+    Ok(delivered)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::test_support::{serve_players, TestPlayer};
+    use crate::server::vault_lib;
+    use std::time::Duration;
+
+    fn party_name() -> String {
+        format!("party-{}", Uuid::new_v4())
+    }
+
+    #[test]
+    fn joining_without_an_invite_is_refused() {
+        let (party, leader, stranger) = (party_name(), Uuid::new_v4(), Uuid::new_v4());
+        join_party(leader, &party).unwrap();
+
+        assert_eq!(join_party(stranger, &party), Err(ChatError::NotInvited(party.clone())));
+        assert!(!PARTIES.read()[&party].members.contains(&stranger));
+    }
+
+    #[test]
+    fn an_invite_admits_the_player_once() {
+        let (party, leader, friend) = (party_name(), Uuid::new_v4(), Uuid::new_v4());
+        join_party(leader, &party).unwrap();
+        invite_to_party(leader, &party, friend).unwrap();
+
+        assert_eq!(join_party(friend, &party), Ok(()));
+        leave_party(friend, &party);
+        assert_eq!(join_party(friend, &party), Err(ChatError::NotInvited(party.clone())));
+    }
+
+    #[test]
+    fn only_the_leader_can_invite() {
+        let (party, leader, member, stranger) = (party_name(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        join_party(leader, &party).unwrap();
+        invite_to_party(leader, &party, member).unwrap();
+        join_party(member, &party).unwrap();
+
+        assert_eq!(invite_to_party(member, &party, stranger), Err(ChatError::NotPartyLeader(party.clone())));
+
+        // Leadership passes to the remaining member when the leader leaves
+        leave_party(leader, &party);
+        assert_eq!(invite_to_party(member, &party, stranger), Ok(()));
+        assert_eq!(join_party(stranger, &party), Ok(()));
+    }
+
+    #[test]
+    fn a_burst_past_the_allowance_is_rate_limited() {
+        let sender = Uuid::new_v4();
+        let burst = server::live_config().chat_burst.floor() as usize;
+
+        for _ in 0..burst {
+            assert!(send_message(sender, ChatChannel::Global, "hello").is_ok());
+        }
+        assert_eq!(send_message(sender, ChatChannel::Global, "hello"), Err(ChatError::RateLimited));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn local_messages_reach_only_nearby_players() {
+        vault_lib::test_support::vault();
+        let url = serve_players().await;
+        let group = format!("chat-{}", Uuid::new_v4());
+        let sender = TestPlayer::connect(&url, &group).await;
+        let near = TestPlayer::connect(&url, &group).await;
+        let far = TestPlayer::connect(&url, &group).await;
+        let radius = server::live_config().chat_local_radius;
+        for (player, offset) in [(&sender, 0.0), (&near, radius / 2.0), (&far, radius * 10.0)] {
+            server::place_player(player.id, [1_000.0 + offset, 2_000.0, 3_000.0], 100).unwrap();
+        }
+
+        // The sender is within range of itself
+        assert_eq!(send_message(sender.id, ChatChannel::Local, "over here"), Ok(2));
+
+        let heard = near.wait_for("chat").await;
+        assert_eq!(heard.len(), 1);
+        assert_eq!(heard[0]["text"], "over here");
+        assert_eq!(heard[0]["channel"], "local");
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(far.received("chat").is_empty());
+    }
+}
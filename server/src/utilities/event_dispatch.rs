@@ -0,0 +1,198 @@
+//-----------------------------------------------------------------------------
+// Plugin Event Dispatch
+//   - Delivers game events to each plugin on its own worker thread
+//   - Only delivers events of a pool group to the plugins that group loads
+//   - A plugin stuck in on_game_event can't hold up the emitter or other plugins
+//   - Reports handlers that run past the timeout and stops feeding them until
+//     they return
+//   - Reports events dropped on a full queue once per watchdog check, not once
+//     per event
+//-----------------------------------------------------------------------------
+
+use crate::LOGGER;
+use crate::{log_error, log_info, log_warn};
+use parking_lot::Mutex;
+use plugin_api::{EventBus, EventHook, GameEvent};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, TrySendError};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Default time a plugin may spend on one event before it is reported as hung
+pub const DEFAULT_PLUGIN_EVENT_TIMEOUT_MS: u64 = 1000;
+
+/// Events queued for a plugin before further events are dropped
+const QUEUE_CAPACITY: usize = 1024;
+
+/// Watchdog state for one plugin's worker
+struct Worker {
+    name: String,
+    /// When the event currently being handled started, if any
+    started: Mutex<Option<Instant>>,
+    /// Set once the current event has overrun the timeout
+    hung: AtomicBool,
+    /// Events dropped on a full queue since the watchdog last reported them
+    dropped: AtomicU64,
+}
+
+impl Worker {
+    fn new(name: String) -> Self {
+        Self { name, started: Mutex::new(None), hung: AtomicBool::new(false), dropped: AtomicU64::new(0) }
+    }
+
+    /// Counts an event dropped on a full queue
+    ///
+    /// Returns true for the first drop since the last report, so the caller
+    /// warns once when the queue fills instead of once per event.
+    fn record_drop(&self) -> bool {
+        self.dropped.fetch_add(1, Ordering::AcqRel) == 0
+    }
+
+    /// Takes the number of events dropped since the last call
+    fn take_dropped(&self) -> u64 {
+        self.dropped.swap(0, Ordering::AcqRel)
+    }
+}
+
+/// Subscribes every plugin's `on_game_event` to `bus` through its own worker thread
+///
+/// Emitting never waits on a plugin. While a plugin is stuck past `timeout`
+/// its events are dropped instead of queued, and delivery resumes once its
+/// handler returns.
+pub fn subscribe(bus: &EventBus, hooks: Vec<EventHook>, timeout: Duration) {
+    let mut workers = Vec::with_capacity(hooks.len());
+
+    for hook in hooks {
+        let worker = Arc::new(Worker::new(hook.name.clone()));
+        let (sender, receiver) = sync_channel::<GameEvent>(QUEUE_CAPACITY);
+        let routing = hook.clone();
+
+        let running = Arc::clone(&worker);
+        std::thread::Builder::new()
+            .name(format!("plugin-events-{}", hook.name))
+            .spawn(move || {
+                for event in receiver {
+                    *running.started.lock() = Some(Instant::now());
+                    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                        (hook.on_game_event)(&hook.plugin, &event)
+                    }));
+                    *running.started.lock() = None;
+                    if result.is_err() {
+                        log_error!(LOGGER, "PLUGIN", "Plugin {} panicked during on_game_event", running.name);
+                    }
+                    if running.hung.swap(false, Ordering::AcqRel) {
+                        log_info!(LOGGER, "PLUGIN", "Plugin {} recovered, resuming event delivery", running.name);
+                    }
+                }
+            })
+            .expect("Failed to spawn plugin event thread");
+
+        let delivering = Arc::clone(&worker);
+        routing.subscribe_with(bus, move |event| {
+            if delivering.hung.load(Ordering::Acquire) || !crate::server::event_reaches(&delivering.name, event) {
+                return;
+            }
+            if let Err(TrySendError::Full(_)) = sender.try_send(event.clone()) {
+                if delivering.record_drop() {
+                    log_warn!(LOGGER, "PLUGIN", "Event queue for {} is full, dropping events", delivering.name);
+                }
+            }
+        });
+        workers.push(worker);
+    }
+
+    // The watchdog only reports; a blocked thread can't be interrupted safely
+    let check_interval = (timeout / 2).max(Duration::from_millis(10));
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(check_interval);
+        loop {
+            interval.tick().await;
+            for worker in &workers {
+                let overran = worker.started.lock().is_some_and(|started| started.elapsed() > timeout);
+                if overran && !worker.hung.swap(true, Ordering::AcqRel) {
+                    log_error!(LOGGER, "PLUGIN", "Plugin {} has been in on_game_event for over {:?}, dropping its events until it returns", worker.name, timeout);
+                }
+                let dropped = worker.take_dropped();
+                if dropped > 0 {
+                    log_warn!(LOGGER, "PLUGIN", "Dropped {} events for {} on a full queue", dropped, worker.name);
+                }
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use plugin_api::Plugin;
+    use std::sync::atomic::AtomicUsize;
+
+    static SLEEPER_EVENTS: AtomicUsize = AtomicUsize::new(0);
+    static STEADY_EVENTS: AtomicUsize = AtomicUsize::new(0);
+
+    /// Sleeps through the events that ask it to, as a plugin stuck in its handler would
+    fn sleeping_handler(_: &Plugin, event: &GameEvent) {
+        SLEEPER_EVENTS.fetch_add(1, Ordering::SeqCst);
+        if let GameEvent::Custom { data, .. } = event {
+            if data["sleep"] == true {
+                std::thread::sleep(Duration::from_millis(400));
+            }
+        }
+    }
+
+    fn steady_handler(_: &Plugin, _: &GameEvent) {
+        STEADY_EVENTS.fetch_add(1, Ordering::SeqCst);
+    }
+
+    fn hook(name: &str, on_game_event: fn(&Plugin, &GameEvent)) -> EventHook {
+        EventHook { name: name.to_string(), plugin: Plugin {}, on_game_event, custom_events: None }
+    }
+
+    /// Waits up to a second for `count` to reach `expected`
+    async fn settle(count: &AtomicUsize, expected: usize) -> usize {
+        for _ in 0..100 {
+            if count.load(Ordering::SeqCst) >= expected {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        count.load(Ordering::SeqCst)
+    }
+
+    #[test]
+    fn a_full_queue_warns_once_per_report() {
+        let worker = Worker::new("flooded".to_string());
+
+        let warnings = (0..500).filter(|_| worker.record_drop()).count();
+        assert_eq!(warnings, 1);
+        assert_eq!(worker.take_dropped(), 500);
+
+        assert_eq!(worker.take_dropped(), 0);
+        assert!(worker.record_drop());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn a_hung_plugin_misses_events_while_the_others_keep_receiving_them() {
+        let bus = EventBus::new();
+        subscribe(&bus, vec![hook("sleeper", sleeping_handler), hook("steady", steady_handler)], Duration::from_millis(50));
+        let event = |sleep: bool| GameEvent::custom("watchdog_test", serde_json::json!({ "sleep": sleep }));
+
+        bus.emit(&event(true));
+        // Long enough for the watchdog to find the sleeper past its timeout
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        for _ in 0..5 {
+            bus.emit(&event(false));
+        }
+
+        assert_eq!(settle(&STEADY_EVENTS, 6).await, 6);
+        assert_eq!(SLEEPER_EVENTS.load(Ordering::SeqCst), 1);
+
+        // Once the handler returns, the sleeper is fed again, without the events it missed
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        bus.emit(&event(false));
+        assert_eq!(settle(&SLEEPER_EVENTS, 2).await, 2);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(SLEEPER_EVENTS.load(Ordering::SeqCst), 2);
+        assert_eq!(STEADY_EVENTS.load(Ordering::SeqCst), 7);
+    }
+}
@@ -0,0 +1,252 @@
+//-----------------------------------------------------------------------------
+// Event Recording and Replay
+//   - Appends every admitted inbound Socket.IO event to a JSON lines file
+//   - Replays a recorded file against a server through simulated clients,
+//     keeping each player's events on its own connection and in its timing
+//     (needs the `replay` feature)
+//-----------------------------------------------------------------------------
+
+use crate::LOGGER;
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use crate::{log_error, log_info};
+use once_cell::sync::OnceCell;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, LineWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+use uuid::Uuid;
+#[cfg(any(test, feature = "replay"))]
+use {
+    crate::log_warn,
+    rust_socketio::asynchronous::{Client, ClientBuilder},
+    rust_socketio::Payload,
+    std::collections::HashMap,
+    std::time::Duration,
+};
+
+/// Pseudo-event recorded when a player is admitted, with its connect payload
+pub const CONNECT_EVENT: &str = "connect";
+
+/// Pseudo-event recorded when a player's connection closes
+pub const DISCONNECT_EVENT: &str = "disconnect";
+
+/// Event whose payload is raw bytes, recorded as base64
+const BINARY_EVENT: &str = "state_binary";
+
+/// Open recording, set once by `start_recording`
+static RECORDER: OnceCell<Recorder> = OnceCell::new();
+
+struct Recorder {
+    file: Mutex<LineWriter<File>>,
+    started_at: Instant,
+}
+
+/// One inbound event as written to the log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    /// Milliseconds since recording started
+    pub at_ms: u64,
+    pub player: Uuid,
+    /// Namespace the player was connected to
+    pub namespace: String,
+    pub event: String,
+    pub payload: serde_json::Value,
+}
+
+/// Starts appending inbound events to `path`, creating it if needed
+///
+/// Recording lasts until the process exits and can only be started once.
+pub fn start_recording(path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    let file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open event log {}", path.display()))?;
+    RECORDER
+        .set(Recorder { file: Mutex::new(LineWriter::new(file)), started_at: Instant::now() })
+        .map_err(|_| anyhow::anyhow!("Event recording has already started"))?;
+    log_info!(LOGGER, "EVENT LOG", "Recording inbound events to {}", path.display());
+    Ok(())
+}
+
+/// Whether inbound events are being recorded
+pub fn is_recording() -> bool {
+    RECORDER.get().is_some()
+}
+
+/// Records an inbound event, doing nothing unless recording has started
+pub fn record(player: Uuid, namespace: &str, event: &str, payload: serde_json::Value) {
+    let Some(recorder) = RECORDER.get() else {
+        return;
+    };
+    let entry = RecordedEvent {
+        at_ms: recorder.started_at.elapsed().as_millis() as u64,
+        player,
+        namespace: namespace.to_string(),
+        event: event.to_string(),
+        payload,
+    };
+
+    let line = match serde_json::to_string(&entry) {
+        Ok(line) => line,
+        Err(e) => {
+            log_error!(LOGGER, "EVENT LOG", "Failed to serialize {} from {}: {}", event, player, e);
+            return;
+        }
+    };
+    if let Err(e) = writeln!(recorder.file.lock(), "{}", line) {
+        log_error!(LOGGER, "EVENT LOG", "Failed to record {} from {}: {}", event, player, e);
+    }
+}
+
+/// Payload of a binary event in the form it is recorded in
+pub fn binary_payload(data: &[u8]) -> serde_json::Value {
+    serde_json::Value::String(STANDARD.encode(data))
+}
+
+/// Reads a recorded log, in recording order
+pub fn read_log(path: impl AsRef<Path>) -> Result<Vec<RecordedEvent>> {
+    let path = path.as_ref();
+    let file = File::open(path).with_context(|| format!("Failed to open event log {}", path.display()))?;
+    let mut events = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.with_context(|| format!("Failed to read event log {}", path.display()))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event: RecordedEvent = serde_json::from_str(&line)
+            .with_context(|| format!("Invalid entry on line {} of {}", index + 1, path.display()))?;
+        events.push(event);
+    }
+    events.sort_by_key(|event| event.at_ms);
+    Ok(events)
+}
+
+/// Feeds a recorded log to the server at `url` through simulated players
+///
+/// Each recorded player gets its own client, connected with its recorded
+/// connect payload, so events go through the same handlers they did when
+/// recorded. Events are sent with their recorded spacing. The server hands
+/// out fresh player ids, so this is meant for a fresh server that admits
+/// clients without checking their tokens, see `server::prepare_replay`.
+#[cfg(any(test, feature = "replay"))]
+pub async fn replay(url: &str, path: impl AsRef<Path>) -> Result<()> {
+    let events = read_log(path)?;
+    log_info!(LOGGER, "EVENT LOG", "Replaying {} events against {}", events.len(), url);
+
+    let started = Instant::now();
+    let mut clients: HashMap<Uuid, Client> = HashMap::new();
+    for event in events {
+        tokio::time::sleep_until((started + Duration::from_millis(event.at_ms)).into()).await;
+
+        if event.event == DISCONNECT_EVENT {
+            if let Some(client) = clients.remove(&event.player) {
+                if let Err(e) = client.disconnect().await {
+                    log_warn!(LOGGER, "EVENT LOG", "Failed to disconnect replayed player {}: {}", event.player, e);
+                }
+            }
+            continue;
+        }
+
+        // Logs recorded before connect payloads were kept start with the first event
+        let connecting = event.event == CONNECT_EVENT;
+        if connecting || !clients.contains_key(&event.player) {
+            let mut builder = ClientBuilder::new(url).namespace(event.namespace.as_str());
+            if connecting {
+                builder = builder.auth(event.payload.clone());
+            }
+            let client = builder
+                .connect()
+                .await
+                .with_context(|| format!("Failed to connect replayed player {}", event.player))?;
+            if let Some(previous) = clients.insert(event.player, client) {
+                let _ = previous.disconnect().await;
+            }
+        }
+        if connecting {
+            continue;
+        }
+        let client = &clients[&event.player];
+
+        let payload = match (event.event.as_str(), &event.payload) {
+            (BINARY_EVENT, serde_json::Value::String(encoded)) => {
+                let data = STANDARD.decode(encoded).context("Invalid binary payload in event log")?;
+                Payload::Binary(data.into())
+            }
+            (_, payload) => Payload::from(payload.clone()),
+        };
+        if let Err(e) = client.emit(event.event.as_str(), payload).await {
+            log_warn!(LOGGER, "EVENT LOG", "Failed to replay {} for {}: {}", event.event, event.player, e);
+        }
+    }
+
+    for (_, client) in clients {
+        let _ = client.disconnect().await;
+    }
+    log_info!(LOGGER, "EVENT LOG", "Replay finished after {:?}", started.elapsed());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use socketioxide::extract::{Data, SocketRef};
+    use socketioxide::SocketIo;
+    use std::sync::Arc;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn recorded_events_are_replayed_on_their_own_connections() {
+        let path = std::env::temp_dir().join(format!("horizon-event-log-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        start_recording(&path).unwrap();
+        let (alice, bob) = (Uuid::new_v4(), Uuid::new_v4());
+        record(alice, "/", CONNECT_EVENT, serde_json::json!({ "name": "alice" }));
+        record(bob, "/", CONNECT_EVENT, serde_json::json!({ "name": "bob" }));
+        record(alice, "/", "chat", serde_json::json!({ "text": "hi" }));
+        record(bob, "/", BINARY_EVENT, binary_payload(&[1, 2, 3]));
+        record(alice, "/", DISCONNECT_EVENT, serde_json::Value::Null);
+
+        // Other tests may run server code that records into the same log
+        let recorded: Vec<String> = read_log(&path)
+            .unwrap()
+            .into_iter()
+            .filter(|event| event.player == alice || event.player == bob)
+            .map(|event| event.event)
+            .collect();
+        assert_eq!(recorded, [CONNECT_EVENT, CONNECT_EVENT, "chat", BINARY_EVENT, DISCONNECT_EVENT]);
+
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let (layer, io) = SocketIo::new_layer();
+        io.ns("/", {
+            let received = Arc::clone(&received);
+            move |socket: SocketRef, Data(auth): Data<serde_json::Value>| {
+                let name = auth["name"].as_str().unwrap_or_default().to_string();
+                received.lock().push(format!("{} connected", name));
+                socket.on("chat", {
+                    let (received, name) = (Arc::clone(&received), name.clone());
+                    move |Data(data): Data<serde_json::Value>| received.lock().push(format!("{} said {}", name, data["text"]))
+                });
+                socket.on(BINARY_EVENT, {
+                    let (received, name) = (Arc::clone(&received), name.clone());
+                    move |Data(data): Data<bytes::Bytes>| received.lock().push(format!("{} sent {:?}", name, data.as_ref()))
+                });
+            }
+        });
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, axum::Router::new().layer(layer)).await });
+
+        replay(&url, &path).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        // Players recorded by other tests connect without a name
+        let mut received: Vec<String> = received.lock().iter().filter(|line| !line.starts_with(' ')).cloned().collect();
+        received.sort();
+        assert_eq!(received, ["alice connected", "alice said \"hi\"", "bob connected", "bob sent [1, 2, 3]"]);
+    }
+}
@@ -0,0 +1,202 @@
+//-----------------------------------------------------------------------------
+// Game Logic Tick Scheduler
+//   - Calls every active plugin's on_game_tick at a fixed rate
+//   - Passes the real time elapsed since the previous tick
+//   - Ticks plugins one after another in priority order
+//   - Gives each plugin its own budget, so only a slow plugin misses ticks
+//   - Detects player collisions once per tick
+//-----------------------------------------------------------------------------
+
+use crate::collision;
+use crate::server::{emit_event, player_actors, pool_groups};
+use crate::LOGGER;
+use crate::{log_error, log_info, log_warn};
+use plugin_api::{GameEvent, TickHook};
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Default number of game ticks per second
+pub const DEFAULT_TICK_RATE_HZ: u32 = 20;
+
+/// Checks every player in the world against the others of its pool group
+/// and emits a `collision` event for each overlapping pair
+///
+/// Players in different groups are in different games, so they never collide.
+fn detect_collisions() {
+    for pool_group in pool_groups() {
+        for (a, b) in collision::step(&player_actors(&pool_group)) {
+            emit_event(GameEvent::custom("collision", serde_json::json!({ "a": a, "b": b, "pool_group": pool_group })));
+        }
+    }
+}
+
+/// Keeps the first hook of each plugin and drops the copies after it
+///
+/// Thread pools each load their own copy of a plugin, all sharing the
+/// plugin's static state, so only one copy per plugin should be driven.
+pub fn one_per_plugin<H>(hooks: impl IntoIterator<Item = H>, name: impl Fn(&H) -> &String) -> Vec<H> {
+    let mut seen = HashSet::new();
+    hooks.into_iter().filter(|hook| seen.insert(name(hook).clone())).collect()
+}
+
+/// A plugin hook together with whether its previous tick is still running
+struct ScheduledHook {
+    hook: TickHook,
+    busy: Arc<AtomicBool>,
+}
+
+/// Runs the tick loop forever at `tick_rate_hz`
+///
+/// Plugins tick on the blocking pool one after another, by ascending
+/// `tick_priority` and then by plugin name, so the order is the same every
+/// tick. Each plugin gets one tick budget of its own: a plugin that runs past
+/// it is left to finish in the background while the next one starts, and is
+/// skipped on later ticks until it returns, so only the slow plugin misses
+/// ticks. Skipped plugins receive the full elapsed time once they tick again.
+pub async fn run(mut hooks: Vec<TickHook>, tick_rate_hz: u32) {
+    let budget = Duration::from_secs_f64(1.0 / f64::from(tick_rate_hz.max(1)));
+    plugin_api::sort_tick_hooks(&mut hooks);
+    let hooks: Vec<ScheduledHook> = hooks
+        .into_iter()
+        .map(|hook| ScheduledHook { hook, busy: Arc::new(AtomicBool::new(false)) })
+        .collect();
+    let mut last_ticked: Vec<Instant> = vec![Instant::now(); hooks.len()];
+    let collisions_busy = Arc::new(AtomicBool::new(false));
+
+    log_info!(LOGGER, "GAME LOGIC", "Ticking {} plugins at {} Hz", hooks.len(), tick_rate_hz);
+
+    let mut interval = tokio::time::interval(budget);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+    loop {
+        interval.tick().await;
+
+        // Collision detection doesn't depend on plugin order, so it runs alongside them
+        let collisions = if !collisions_busy.swap(true, Ordering::AcqRel) {
+            let busy = Arc::clone(&collisions_busy);
+            Some(tokio::task::spawn_blocking(move || {
+                let result = std::panic::catch_unwind(detect_collisions);
+                busy.store(false, Ordering::Release);
+                if result.is_err() {
+                    log_error!(LOGGER, "GAME LOGIC", "Collision detection panicked");
+                }
+            }))
+        } else {
+            None
+        };
+
+        tick_plugins(&hooks, &mut last_ticked, budget).await;
+
+        if let Some(collisions) = collisions {
+            if tokio::time::timeout(budget, collisions).await.is_err() {
+                log_warn!(LOGGER, "GAME LOGIC", "Collision detection took longer than the {:?} tick budget", budget);
+            }
+        }
+    }
+}
+
+/// Ticks each plugin once, in order, waiting at most `budget` for each
+async fn tick_plugins(hooks: &[ScheduledHook], last_ticked: &mut [Instant], budget: Duration) {
+    for (scheduled, last_ticked) in hooks.iter().zip(last_ticked.iter_mut()) {
+        if scheduled.busy.swap(true, Ordering::AcqRel) {
+            log_warn!(LOGGER, "GAME LOGIC", "Skipping tick for {}, its previous tick is still running", scheduled.hook.name);
+            continue;
+        }
+
+        let now = Instant::now();
+        let delta_time = now.duration_since(*last_ticked).as_secs_f64();
+        *last_ticked = now;
+
+        let hook = scheduled.hook.clone();
+        let busy = Arc::clone(&scheduled.busy);
+        let running = tokio::task::spawn_blocking(move || {
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| hook.tick(delta_time)));
+            busy.store(false, Ordering::Release);
+            if result.is_err() {
+                log_error!(LOGGER, "GAME LOGIC", "Plugin {} panicked during on_game_tick", hook.name);
+            }
+        });
+
+        if tokio::time::timeout(budget, running).await.is_err() {
+            log_warn!(LOGGER, "GAME LOGIC", "Plugin {} took longer than its {:?} tick budget", scheduled.hook.name, budget);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    static SHARED_TICKS: AtomicUsize = AtomicUsize::new(0);
+    static GROUP_ONLY_TICKS: AtomicUsize = AtomicUsize::new(0);
+
+    fn hook(name: &str, on_game_tick: fn(&plugin_api::Plugin, f64)) -> TickHook {
+        TickHook { name: name.to_string(), plugin: plugin_api::Plugin {}, on_game_tick, priority: 0 }
+    }
+
+    #[test]
+    fn plugins_loaded_by_several_pools_tick_once() {
+        let shared = |_: &plugin_api::Plugin, _: f64| {
+            SHARED_TICKS.fetch_add(1, Ordering::SeqCst);
+        };
+        let group_only = |_: &plugin_api::Plugin, _: f64| {
+            GROUP_ONLY_TICKS.fetch_add(1, Ordering::SeqCst);
+        };
+        // Two pool groups both load "shared", and only the second loads "group_only"
+        let pools = vec![vec![hook("shared", shared)], vec![hook("shared", shared), hook("group_only", group_only)]];
+
+        let hooks = one_per_plugin(pools.into_iter().flatten(), |hook| &hook.name);
+        for hook in &hooks {
+            hook.tick(0.05);
+        }
+
+        assert_eq!(hooks.len(), 2);
+        assert_eq!(SHARED_TICKS.load(Ordering::SeqCst), 1);
+        assert_eq!(GROUP_ONLY_TICKS.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn the_scheduler_keeps_ticking() {
+        static TICKS: AtomicUsize = AtomicUsize::new(0);
+        let counter = hook("counter", |_, _| {
+            TICKS.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let scheduler = tokio::spawn(run(vec![counter], 100));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        scheduler.abort();
+
+        assert!(TICKS.load(Ordering::SeqCst) >= 5);
+    }
+
+    #[tokio::test]
+    async fn a_slow_plugin_does_not_hold_back_the_ones_after_it() {
+        static SLOW_TICKS: AtomicUsize = AtomicUsize::new(0);
+        static FAST_TICKS: AtomicUsize = AtomicUsize::new(0);
+        let budget = Duration::from_millis(10);
+        let slow = hook("slow", |_, _| {
+            SLOW_TICKS.fetch_add(1, Ordering::SeqCst);
+            std::thread::sleep(Duration::from_millis(100));
+        });
+        let fast = hook("fast", |_, _| {
+            FAST_TICKS.fetch_add(1, Ordering::SeqCst);
+        });
+        let hooks: Vec<ScheduledHook> = [slow, fast]
+            .into_iter()
+            .map(|hook| ScheduledHook { hook, busy: Arc::new(AtomicBool::new(false)) })
+            .collect();
+        let mut last_ticked = vec![Instant::now(); hooks.len()];
+
+        // The fast plugin ticks on the very tick the slow one overruns, and on
+        // every tick after it while the slow one is skipped
+        for _ in 0..3 {
+            tick_plugins(&hooks, &mut last_ticked, budget).await;
+        }
+
+        assert_eq!(SLOW_TICKS.load(Ordering::SeqCst), 1);
+        assert_eq!(FAST_TICKS.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,184 @@
+//-----------------------------------------------------------------------------
+// Leaderboard
+//   - Multiple named metrics (kills, playtime, ...)
+//   - Scores kept sorted for cheap top-N queries
+//   - Safe to share between thread pools
+//-----------------------------------------------------------------------------
+
+use parking_lot::RwLock;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
+
+/// Entry in a metric's sorted set, highest score first
+///
+/// Equal scores are ordered by player id so ranks are deterministic.
+#[derive(Debug, Clone, PartialEq)]
+struct RankedScore {
+    value: f64,
+    player_id: String,
+}
+
+impl Eq for RankedScore {}
+
+impl Ord for RankedScore {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .value
+            .total_cmp(&self.value)
+            .then_with(|| self.player_id.cmp(&other.player_id))
+    }
+}
+
+impl PartialOrd for RankedScore {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Scores of every player for a single metric
+#[derive(Default)]
+struct MetricBoard {
+    scores: HashMap<String, f64>,
+    ranked: BTreeSet<RankedScore>,
+}
+
+impl MetricBoard {
+    fn submit(&mut self, player_id: &str, value: f64) {
+        if let Some(previous) = self.scores.insert(player_id.to_string(), value) {
+            self.ranked.remove(&RankedScore { value: previous, player_id: player_id.to_string() });
+        }
+        self.ranked.insert(RankedScore { value, player_id: player_id.to_string() });
+    }
+}
+
+/// Per-metric leaderboards
+///
+/// Each metric holds one score per player; submitting again replaces the
+/// previous score. All methods take `&self` and may be called from any thread.
+#[derive(Default)]
+pub struct Leaderboard {
+    metrics: RwLock<HashMap<String, MetricBoard>>,
+}
+
+impl Leaderboard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records `value` as the current score of `player_id` for `metric`
+    ///
+    /// Non-finite values are ignored.
+    pub fn submit_score(&self, player_id: &str, metric: &str, value: f64) {
+        if !value.is_finite() {
+            return;
+        }
+        self.metrics
+            .write()
+            .entry(metric.to_string())
+            .or_default()
+            .submit(player_id, value);
+    }
+
+    /// Returns up to `n` players with the highest scores for `metric`, best first
+    ///
+    /// Players with equal scores are ordered by player id.
+    pub fn top(&self, metric: &str, n: usize) -> Vec<(String, f64)> {
+        self.metrics
+            .read()
+            .get(metric)
+            .map(|board| {
+                board
+                    .ranked
+                    .iter()
+                    .take(n)
+                    .map(|entry| (entry.player_id.clone(), entry.value))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the 1-based rank of `player_id` for `metric`, or `None` if it has no score
+    ///
+    /// Ranks follow the same order as `top`, so tied players get distinct,
+    /// consecutive ranks ordered by player id.
+    pub fn rank_of(&self, player_id: &str, metric: &str) -> Option<usize> {
+        let metrics = self.metrics.read();
+        let board = metrics.get(metric)?;
+        let value = *board.scores.get(player_id)?;
+        let entry = RankedScore { value, player_id: player_id.to_string() };
+        Some(board.ranked.range(..&entry).count() + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ties_are_ordered_by_player_id() {
+        let board = Leaderboard::new();
+        board.submit_score("carol", "kills", 5.0);
+        board.submit_score("alice", "kills", 5.0);
+        board.submit_score("bob", "kills", 7.0);
+
+        assert_eq!(
+            board.top("kills", 3),
+            vec![("bob".to_string(), 7.0), ("alice".to_string(), 5.0), ("carol".to_string(), 5.0)]
+        );
+        assert_eq!(board.rank_of("alice", "kills"), Some(2));
+        assert_eq!(board.rank_of("carol", "kills"), Some(3));
+    }
+
+    #[test]
+    fn resubmitting_replaces_the_previous_score() {
+        let board = Leaderboard::new();
+        board.submit_score("alice", "kills", 10.0);
+        board.submit_score("bob", "kills", 5.0);
+        board.submit_score("alice", "kills", 1.0);
+
+        assert_eq!(board.top("kills", 10), vec![("bob".to_string(), 5.0), ("alice".to_string(), 1.0)]);
+        assert_eq!(board.rank_of("alice", "kills"), Some(2));
+    }
+
+    #[test]
+    fn top_returns_everyone_when_fewer_than_n_players_scored() {
+        let board = Leaderboard::new();
+        board.submit_score("alice", "playtime", 3.5);
+
+        assert_eq!(board.top("playtime", 10), vec![("alice".to_string(), 3.5)]);
+        assert!(board.top("kills", 10).is_empty());
+        assert_eq!(board.rank_of("alice", "kills"), None);
+    }
+
+    #[test]
+    fn metrics_are_independent() {
+        let board = Leaderboard::new();
+        board.submit_score("alice", "kills", 1.0);
+        board.submit_score("alice", "playtime", 100.0);
+        board.submit_score("bob", "kills", 2.0);
+        board.submit_score("bob", "playtime", f64::NAN);
+
+        assert_eq!(board.rank_of("alice", "kills"), Some(2));
+        assert_eq!(board.rank_of("alice", "playtime"), Some(1));
+        assert_eq!(board.rank_of("bob", "playtime"), None);
+    }
+
+    #[test]
+    fn concurrent_submissions_keep_one_score_per_player() {
+        let board = Leaderboard::new();
+        std::thread::scope(|scope| {
+            for thread in 0..4 {
+                let board = &board;
+                scope.spawn(move || {
+                    for player in 0..50 {
+                        board.submit_score(&format!("player-{}", player), "kills", (thread * 100 + player) as f64);
+                    }
+                });
+            }
+        });
+
+        let top = board.top("kills", 100);
+        assert_eq!(top.len(), 50);
+        assert!(top.windows(2).all(|pair| pair[0].1 >= pair[1].1));
+    }
+}
@@ -0,0 +1,205 @@
+//-----------------------------------------------------------------------------
+// Level Data Streaming
+//   - Reads a level definition listing regions and their pre-placed objects
+//   - Streams regions into PebbleVault on demand
+//   - Persists and unloads regions that are no longer needed
+//-----------------------------------------------------------------------------
+
+use crate::server::vault_lib::{PebbleVaultCustomData, Plugin, PluginAPI};
+use anyhow::{Context, Result};
+use parking_lot::RwLock;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::Path;
+use uuid::Uuid;
+
+/// A level file: `{ "regions": [ ... ] }`
+#[derive(Debug, Clone, Deserialize)]
+pub struct LevelDefinition {
+    pub regions: Vec<RegionDefinition>,
+}
+
+/// A region of the level and the objects placed in it at load time
+#[derive(Debug, Clone, Deserialize)]
+pub struct RegionDefinition {
+    /// Name the region is streamed in and out by
+    pub name: String,
+    pub center: [f64; 3],
+    pub radius: f64,
+    #[serde(default)]
+    pub objects: Vec<ObjectDefinition>,
+}
+
+/// A pre-placed object
+///
+/// Objects need a fixed id so streaming a region in again does not duplicate
+/// objects that were persisted when it was streamed out.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ObjectDefinition {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub object_type: String,
+    pub position: [f64; 3],
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub value: i32,
+}
+
+/// Loads level regions into PebbleVault as they are needed
+pub struct LevelData {
+    definition: LevelDefinition,
+    /// Regions currently in PebbleVault, by name
+    resident: RwLock<HashMap<String, Uuid>>,
+}
+
+impl LevelData {
+    /// Reads a level definition from a JSON file
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read level file {}", path.display()))?;
+        let definition: LevelDefinition = serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse level file {}", path.display()))?;
+        Ok(Self::new(definition))
+    }
+
+    pub fn new(definition: LevelDefinition) -> Self {
+        Self { definition, resident: RwLock::new(HashMap::new()) }
+    }
+
+    fn region(&self, name: &str) -> Result<&RegionDefinition> {
+        self.definition
+            .regions
+            .iter()
+            .find(|region| region.name == name)
+            .with_context(|| format!("Level has no region named {}", name))
+    }
+
+    /// Names of the regions currently streamed in
+    pub fn resident_regions(&self) -> Vec<String> {
+        self.resident.read().keys().cloned().collect()
+    }
+
+    /// PebbleVault id of a streamed-in region
+    pub fn region_id(&self, name: &str) -> Option<Uuid> {
+        self.resident.read().get(name).copied()
+    }
+
+    /// Creates or loads a region in PebbleVault and adds its pre-placed objects
+    ///
+    /// Objects already present, for example because they were persisted when
+    /// the region was last streamed out, are left as they are. Streaming in a
+    /// resident region does nothing.
+    pub fn stream_region_in(&self, name: &str) -> Result<Uuid> {
+        if let Some(region_id) = self.region_id(name) {
+            return Ok(region_id);
+        }
+
+        let region = self.region(name)?;
+        let vault = Plugin {};
+        let region_id = vault
+            .create_or_load_region(region.center, region.radius)
+            .map_err(anyhow::Error::msg)
+            .with_context(|| format!("Failed to create region {}", name))?;
+
+        for object in &region.objects {
+            let object_id = Uuid::parse_str(&object.id)
+                .with_context(|| format!("Object {} in region {} has an invalid id", object.id, name))?;
+            let existing = vault.get_object(object_id).map_err(anyhow::Error::msg)?;
+            if existing.is_some() {
+                continue;
+            }
+
+            let [x, y, z] = object.position;
+            let custom_data = PebbleVaultCustomData { name: object.name.clone(), value: object.value, velocity: [0.0; 3], health: None };
+            vault
+                .add_object(region_id, object_id, &object.object_type, x, y, z, custom_data)
+                .map_err(anyhow::Error::msg)
+                .with_context(|| format!("Failed to add object {} to region {}", object.id, name))?;
+        }
+
+        self.resident.write().insert(name.to_string(), region_id);
+        Ok(region_id)
+    }
+
+    /// Persists a resident region and unloads it from memory
+    ///
+    /// Streaming out a region that is not resident does nothing.
+    pub fn stream_region_out(&self, name: &str) -> Result<()> {
+        let Some(region_id) = self.region_id(name) else {
+            return Ok(());
+        };
+
+        Plugin {}
+            .unload_region(region_id)
+            .map_err(anyhow::Error::msg)
+            .with_context(|| format!("Failed to unload region {}", name))?;
+
+        self.resident.write().remove(name);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::vault_lib::test_support::{unique_center, vault};
+
+    /// Writes a level with a `north` and a `south` region holding two objects
+    /// each, and returns its path and the object ids of each region
+    fn write_level() -> (std::path::PathBuf, HashMap<&'static str, Vec<Uuid>>) {
+        let mut objects = HashMap::new();
+        let regions: Vec<serde_json::Value> = ["north", "south"]
+            .into_iter()
+            .map(|name| {
+                let [x, y, z] = unique_center();
+                let ids = vec![Uuid::new_v4(), Uuid::new_v4()];
+                let placed: Vec<serde_json::Value> = ids
+                    .iter()
+                    .enumerate()
+                    .map(|(i, id)| serde_json::json!({ "id": id.to_string(), "type": "crate", "position": [x + i as f64, y, z] }))
+                    .collect();
+                objects.insert(name, ids);
+                serde_json::json!({ "name": name, "center": [x, y, z], "radius": 100.0, "objects": placed })
+            })
+            .collect();
+
+        let path = std::env::temp_dir().join(format!("level-{}.json", Uuid::new_v4()));
+        std::fs::write(&path, serde_json::json!({ "regions": regions }).to_string()).unwrap();
+        (path, objects)
+    }
+
+    #[test]
+    fn objects_are_placed_in_their_own_region() {
+        let vault = vault();
+        let (path, objects) = write_level();
+        let level = LevelData::load(&path).unwrap();
+
+        for (name, ids) in &objects {
+            let region_id = level.stream_region_in(name).unwrap();
+            for id in ids {
+                assert_eq!(vault.region_of(*id), Some(region_id), "{} is not in {}", id, name);
+            }
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn streaming_a_region_out_and_in_again_does_not_duplicate_objects() {
+        let vault = vault();
+        let (path, objects) = write_level();
+        let level = LevelData::load(&path).unwrap();
+        level.stream_region_in("north").unwrap();
+
+        level.stream_region_out("north").unwrap();
+        assert!(level.region_id("north").is_none());
+        let region_id = level.stream_region_in("north").unwrap();
+
+        assert_eq!(vault.count_by_type(region_id).get("crate"), Some(&2));
+        for id in &objects["north"] {
+            assert_eq!(vault.region_of(*id), Some(region_id));
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+}
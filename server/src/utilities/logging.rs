@@ -0,0 +1,148 @@
+//-----------------------------------------------------------------------------
+// Subsystem Logging
+//   - Category-bound loggers so subsystems don't repeat their category
+//   - Per-subsystem minimum level overrides on top of a global level
+//   - Level-checked `log_*!` macros the rest of the server logs through, so
+//     the levels set here apply to every message, not only subsystem loggers
+//-----------------------------------------------------------------------------
+
+use crate::LOGGER;
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::fmt;
+
+lazy_static! {
+    static ref DEFAULT_LEVEL: RwLock<LogLevel> = RwLock::new(LogLevel::Info);
+    static ref LEVEL_OVERRIDES: RwLock<HashMap<String, LogLevel>> = RwLock::new(HashMap::new());
+}
+
+/// Severity of a log message, from least to most severe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Critical,
+}
+
+impl std::str::FromStr for LogLevel {
+    type Err = String;
+
+    /// Parses a level name such as `"debug"` or `"WARN"`
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "debug" => Ok(LogLevel::Debug),
+            "info" => Ok(LogLevel::Info),
+            "warn" | "warning" => Ok(LogLevel::Warn),
+            "error" => Ok(LogLevel::Error),
+            "critical" => Ok(LogLevel::Critical),
+            _ => Err(format!("Unknown log level {}", name)),
+        }
+    }
+}
+
+/// Entry point for subsystem loggers and their level configuration
+pub struct Subsystem;
+
+impl Subsystem {
+    /// Returns a logger that writes every message under the `name` category
+    pub const fn logger(name: &'static str) -> SubsystemLogger {
+        SubsystemLogger { category: name }
+    }
+
+    /// Sets the minimum level for subsystems without an override
+    pub fn set_default_level(level: LogLevel) {
+        *DEFAULT_LEVEL.write() = level;
+    }
+
+    /// Sets the minimum level for a single subsystem, e.g. debug for `"PEBBLEVAULT"`
+    pub fn set_level(name: &str, level: LogLevel) {
+        LEVEL_OVERRIDES.write().insert(name.to_string(), level);
+    }
+
+    /// Minimum level currently in effect for a subsystem
+    pub fn level(name: &str) -> LogLevel {
+        LEVEL_OVERRIDES
+            .read()
+            .get(name)
+            .copied()
+            .unwrap_or_else(|| *DEFAULT_LEVEL.read())
+    }
+
+    /// Whether a message at `level` in the `name` category would be written
+    pub fn enabled(name: &str, level: LogLevel) -> bool {
+        level >= Subsystem::level(name)
+    }
+}
+
+/// Logs through `horizon_logger` if `$level` is enabled for `$category`
+#[macro_export]
+macro_rules! log_at {
+    ($level:ident, $macro:ident, $logger:tt, $category:expr, $($arg:tt)*) => {
+        if $crate::utilities::logging::Subsystem::enabled($category, $crate::utilities::logging::LogLevel::$level) {
+            ::horizon_logger::$macro!($logger, $category, $($arg)*);
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! log_debug {
+    ($logger:tt, $category:expr, $($arg:tt)*) => { $crate::log_at!(Debug, log_debug, $logger, $category, $($arg)*) };
+}
+
+#[macro_export]
+macro_rules! log_info {
+    ($logger:tt, $category:expr, $($arg:tt)*) => { $crate::log_at!(Info, log_info, $logger, $category, $($arg)*) };
+}
+
+#[macro_export]
+macro_rules! log_warn {
+    ($logger:tt, $category:expr, $($arg:tt)*) => { $crate::log_at!(Warn, log_warn, $logger, $category, $($arg)*) };
+}
+
+#[macro_export]
+macro_rules! log_error {
+    ($logger:tt, $category:expr, $($arg:tt)*) => { $crate::log_at!(Error, log_error, $logger, $category, $($arg)*) };
+}
+
+#[macro_export]
+macro_rules! log_critical {
+    ($logger:tt, $category:expr, $($arg:tt)*) => { $crate::log_at!(Critical, log_critical, $logger, $category, $($arg)*) };
+}
+
+/// A `HorizonLogger` handle bound to one subsystem's category
+///
+/// ```ignore
+/// let log = Subsystem::logger("PEBBLEVAULT");
+/// log.info(format_args!("Loaded {} regions", count));
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct SubsystemLogger {
+    category: &'static str,
+}
+
+impl SubsystemLogger {
+    pub fn log(&self, level: LogLevel, message: fmt::Arguments) {
+        match level {
+            LogLevel::Debug => crate::log_debug!(LOGGER, self.category, "{}", message),
+            LogLevel::Info => crate::log_info!(LOGGER, self.category, "{}", message),
+            LogLevel::Warn => crate::log_warn!(LOGGER, self.category, "{}", message),
+            LogLevel::Error => crate::log_error!(LOGGER, self.category, "{}", message),
+            LogLevel::Critical => crate::log_critical!(LOGGER, self.category, "{}", message),
+        }
+    }
+
+    pub fn info(&self, message: fmt::Arguments) {
+        self.log(LogLevel::Info, message);
+    }
+
+    pub fn warn(&self, message: fmt::Arguments) {
+        self.log(LogLevel::Warn, message);
+    }
+
+    pub fn error(&self, message: fmt::Arguments) {
+        self.log(LogLevel::Error, message);
+    }
+}
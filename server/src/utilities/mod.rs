@@ -0,0 +1,16 @@
+//-----------------------------------------------------------------------------
+// Server Utilities
+//   - Gameplay services built on top of the core server
+//-----------------------------------------------------------------------------
+
+pub mod chat;
+pub mod event_dispatch;
+pub mod event_log;
+pub mod game_logic;
+pub mod leaderboard;
+pub mod level_data;
+pub mod logging;
+pub mod notifications;
+pub mod player_data;
+pub mod rate_limit;
+pub mod startup;
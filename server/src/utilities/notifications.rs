@@ -0,0 +1,221 @@
+//-----------------------------------------------------------------------------
+// Notifications
+//   - Typed server-to-client messages delivered as "notification" events
+//   - Per-player, global and regional targeting
+//   - Queued for players that have not sent "beginplay" yet, up to a limit
+//-----------------------------------------------------------------------------
+
+use crate::server;
+use lazy_static::lazy_static;
+use parking_lot::RwLock;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Most notifications held for a connecting player; older ones are dropped first
+pub const MAX_PENDING_NOTIFICATIONS: usize = 32;
+
+/// How long notifications are held for a player that never sends `beginplay`
+///
+/// After this the queue is delivered as is and the player is treated as playing.
+pub const PENDING_TIMEOUT: Duration = Duration::from_secs(60);
+
+lazy_static! {
+    /// Notifications held for players that are still connecting
+    static ref PENDING: RwLock<HashMap<Uuid, PendingQueue>> = RwLock::new(HashMap::new());
+}
+
+/// Notifications held for one connecting player
+struct PendingQueue {
+    since: Instant,
+    notifications: VecDeque<Notification>,
+}
+
+impl PendingQueue {
+    fn new() -> Self {
+        Self { since: Instant::now(), notifications: VecDeque::new() }
+    }
+
+    fn expired(&self) -> bool {
+        self.since.elapsed() >= PENDING_TIMEOUT
+    }
+
+    /// Queues a notification, dropping the oldest one once the queue is full
+    fn push(&mut self, notification: Notification) {
+        if self.notifications.len() >= MAX_PENDING_NOTIFICATIONS {
+            self.notifications.pop_front();
+        }
+        self.notifications.push_back(notification);
+    }
+}
+
+/// Severity of a notification, used by clients to pick how it is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationLevel {
+    Info,
+    Warning,
+    Critical,
+}
+
+/// A message shown to players
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Notification {
+    pub level: NotificationLevel,
+    pub title: String,
+    pub body: String,
+    /// Seconds the client should display the notification for, `None` until dismissed
+    pub ttl: Option<u64>,
+}
+
+/// Errors that can occur while delivering a notification
+#[derive(Debug, Clone, PartialEq)]
+pub enum NotificationError {
+    /// No connected player has the given id
+    UnknownPlayer(Uuid),
+    /// The recipients of a regional notification could not be looked up
+    Region(String),
+}
+
+impl fmt::Display for NotificationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotificationError::UnknownPlayer(player_id) => write!(f, "Player {} is not connected", player_id),
+            NotificationError::Region(reason) => write!(f, "Failed to notify region: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for NotificationError {}
+
+fn payload(notification: &Notification) -> serde_json::Value {
+    serde_json::to_value(notification).unwrap_or_default()
+}
+
+/// Starts queueing notifications for a player until it sends `beginplay`
+pub fn begin_connecting(player_id: Uuid) {
+    PENDING.write().entry(player_id).or_insert_with(PendingQueue::new);
+}
+
+/// Delivers every notification queued while the player was connecting
+pub fn flush(player_id: Uuid) {
+    let Some(queued) = PENDING.write().remove(&player_id) else {
+        return;
+    };
+    for notification in queued.notifications {
+        server::emit_to_player(player_id, "notification", &payload(&notification));
+    }
+}
+
+/// Drops any notifications queued for a player that left
+pub fn remove_player(player_id: Uuid) {
+    PENDING.write().remove(&player_id);
+}
+
+/// Sends a notification to one player, queueing it if the player is still connecting
+///
+/// A player that has been connecting for longer than `PENDING_TIMEOUT` gets
+/// its queue delivered first and is no longer queued for.
+pub fn notify_player(player_id: Uuid, notification: Notification) -> Result<(), NotificationError> {
+    if let Some(queue) = PENDING.write().get_mut(&player_id) {
+        if !queue.expired() {
+            queue.push(notification);
+            return Ok(());
+        }
+    }
+    flush(player_id);
+
+    if server::emit_to_player(player_id, "notification", &payload(&notification)) {
+        Ok(())
+    } else {
+        Err(NotificationError::UnknownPlayer(player_id))
+    }
+}
+
+/// Sends a notification to every connected player
+///
+/// Returns the number of players it was delivered or queued for.
+pub fn notify_all(notification: Notification) -> usize {
+    server::connected_players()
+        .into_iter()
+        .filter(|player_id| notify_player(*player_id, notification.clone()).is_ok())
+        .count()
+}
+
+/// Sends a notification to every player within `radius` of `center`
+///
+/// Returns the number of players it was delivered or queued for.
+pub fn notify_region(center: [f64; 3], radius: f64, notification: Notification) -> Result<usize, NotificationError> {
+    let players = server::players_in_region(center, radius).map_err(|e| NotificationError::Region(format!("{:#}", e)))?;
+    Ok(players
+        .into_iter()
+        .filter(|player_id| notify_player(*player_id, notification.clone()).is_ok())
+        .count())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::test_support::{serve_players, TestPlayer};
+
+    fn notification(title: &str) -> Notification {
+        Notification { level: NotificationLevel::Info, title: title.to_string(), body: String::new(), ttl: None }
+    }
+
+    #[test]
+    fn a_full_queue_drops_the_oldest_notification() {
+        let mut queue = PendingQueue::new();
+        for i in 0..=MAX_PENDING_NOTIFICATIONS {
+            queue.push(notification(&i.to_string()));
+        }
+
+        assert_eq!(queue.notifications.len(), MAX_PENDING_NOTIFICATIONS);
+        assert_eq!(queue.notifications.front().unwrap().title, "1");
+        assert_eq!(queue.notifications.back().unwrap().title, MAX_PENDING_NOTIFICATIONS.to_string());
+    }
+
+    #[test]
+    fn queues_expire_after_the_timeout() {
+        let mut queue = PendingQueue::new();
+        assert!(!queue.expired());
+
+        queue.since = Instant::now() - PENDING_TIMEOUT;
+        assert!(queue.expired());
+    }
+
+    #[test]
+    fn connecting_players_have_notifications_queued() {
+        let player_id = Uuid::new_v4();
+        begin_connecting(player_id);
+
+        assert_eq!(notify_player(player_id, notification("welcome")), Ok(()));
+        assert_eq!(PENDING.read()[&player_id].notifications.len(), 1);
+        remove_player(player_id);
+        assert!(!PENDING.read().contains_key(&player_id));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn only_the_target_player_is_notified() {
+        let url = serve_players().await;
+        let target = TestPlayer::connect(&url, "default").await;
+        let bystander = TestPlayer::connect(&url, "default").await;
+
+        assert_eq!(notify_player(target.id, notification("for you")), Ok(()));
+
+        let received = target.wait_for("notification").await;
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0]["title"], "for you");
+        assert_eq!(received[0]["level"], "info");
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        assert!(bystander.received("notification").is_empty());
+    }
+
+    #[test]
+    fn notifying_an_unknown_player_is_an_error() {
+        let player_id = Uuid::new_v4();
+
+        assert_eq!(notify_player(player_id, notification("anyone there")), Err(NotificationError::UnknownPlayer(player_id)));
+    }
+}
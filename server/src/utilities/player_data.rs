@@ -0,0 +1,233 @@
+//-----------------------------------------------------------------------------
+// Persistent Player Data
+//   - Storage-agnostic PlayerDataStore trait
+//   - JSON file store used by default
+//   - Hooks so other subsystems can persist their own per-player state
+//-----------------------------------------------------------------------------
+
+use crate::server;
+use anyhow::{Context, Result};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use uuid::Uuid;
+
+/// Default directory the file store keeps one JSON file per player in
+pub const DEFAULT_PLAYER_DATA_DIR: &str = "./player-data";
+
+static STORE: OnceLock<Box<dyn PlayerDataStore>> = OnceLock::new();
+static HOOKS: RwLock<Vec<Box<dyn PlayerDataHook>>> = parking_lot::const_rwlock(Vec::new());
+/// Players whose id survives between sessions, the only ones saved
+///
+/// Anonymous players get a fresh id every session, so saving them would only
+/// leave files behind that nothing ever loads again.
+static PERSISTENT: RwLock<Option<HashSet<Uuid>>> = parking_lot::const_rwlock(None);
+
+/// Everything persisted for a player between sessions
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PlayerData {
+    /// Last known position, `None` if the player never entered the world
+    #[serde(default)]
+    pub position: Option<[f64; 3]>,
+    #[serde(default = "default_health")]
+    pub health: i32,
+    /// Opaque inventory blob owned by whichever subsystem manages items
+    #[serde(default)]
+    pub inventory: serde_json::Value,
+    /// State saved by registered hooks, keyed by hook name
+    #[serde(default)]
+    pub extensions: HashMap<String, serde_json::Value>,
+}
+
+fn default_health() -> i32 {
+    server::DEFAULT_PLAYER_HEALTH
+}
+
+impl Default for PlayerData {
+    fn default() -> Self {
+        Self {
+            position: None,
+            health: default_health(),
+            inventory: serde_json::Value::Null,
+            extensions: HashMap::new(),
+        }
+    }
+}
+
+/// Storage backend for player data
+pub trait PlayerDataStore: Send + Sync {
+    /// Loads a player's data, returning the default data for unknown players
+    fn load(&self, player_id: Uuid) -> Result<PlayerData>;
+    fn save(&self, player_id: Uuid, data: &PlayerData) -> Result<()>;
+}
+
+/// Lets a subsystem store its own per-player state alongside `PlayerData`
+pub trait PlayerDataHook: Send + Sync {
+    /// Key the hook's state is stored under in `PlayerData::extensions`
+    fn name(&self) -> &'static str;
+    /// Captures the player's state when it is saved, `None` to store nothing
+    fn save(&self, player_id: Uuid) -> Option<serde_json::Value>;
+    /// Restores state previously returned by `save`
+    fn load(&self, player_id: Uuid, state: &serde_json::Value);
+}
+
+/// Stores each player as `<dir>/<player_id>.json`
+pub struct FilePlayerDataStore {
+    dir: PathBuf,
+}
+
+impl FilePlayerDataStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, player_id: Uuid) -> PathBuf {
+        self.dir.join(format!("{}.json", player_id))
+    }
+}
+
+impl PlayerDataStore for FilePlayerDataStore {
+    fn load(&self, player_id: Uuid) -> Result<PlayerData> {
+        let path = self.path(player_id);
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(PlayerData::default()),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        serde_json::from_str(&contents).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    fn save(&self, player_id: Uuid, data: &PlayerData) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+
+        // Write to a temporary file first so a crash mid-write can't corrupt saved data
+        let path = self.path(player_id);
+        let temp_path = path.with_extension("json.tmp");
+        std::fs::write(&temp_path, serde_json::to_vec_pretty(data)?)
+            .with_context(|| format!("Failed to write {}", temp_path.display()))?;
+        std::fs::rename(&temp_path, &path).with_context(|| format!("Failed to replace {}", path.display()))
+    }
+}
+
+/// Replaces the default file store; only effective before the first player connects
+pub fn set_store(store: Box<dyn PlayerDataStore>) -> Result<()> {
+    STORE
+        .set(store)
+        .map_err(|_| anyhow::anyhow!("Player data store is already in use"))
+}
+
+fn store() -> &'static dyn PlayerDataStore {
+    STORE
+        .get_or_init(|| Box::new(FilePlayerDataStore::new(server::live_config().player_data_dir)))
+        .as_ref()
+}
+
+/// Marks a player whose id is stable across sessions, so its data is loaded and saved
+pub fn mark_persistent(player_id: Uuid) {
+    PERSISTENT.write().get_or_insert_with(HashSet::new).insert(player_id);
+}
+
+/// Stops saving a player, once its session can no longer be resumed
+pub fn forget(player_id: Uuid) {
+    if let Some(persistent) = PERSISTENT.write().as_mut() {
+        persistent.remove(&player_id);
+    }
+}
+
+fn is_persistent(player_id: Uuid) -> bool {
+    PERSISTENT.read().as_ref().map_or(false, |persistent| persistent.contains(&player_id))
+}
+
+/// Registers a hook whose state is saved and restored with every player
+pub fn register_hook(hook: Box<dyn PlayerDataHook>) {
+    HOOKS.write().push(hook);
+}
+
+/// Loads a joining player's saved data and applies it to the world and hooks
+///
+/// Players without a saved position, including every player not marked
+/// persistent, start at the configured spawn position.
+pub fn restore_player(player_id: Uuid) -> Result<()> {
+    let data = if is_persistent(player_id) { store().load(player_id)? } else { PlayerData::default() };
+
+    // A resumed session is still in the world, so only place players that are absent
+    if server::player_snapshot(player_id).is_none() {
+        let position = data.position.unwrap_or_else(|| server::live_config().spawn_position);
+        server::place_player(player_id, position, data.health)?;
+    }
+    for hook in HOOKS.read().iter() {
+        if let Some(state) = data.extensions.get(hook.name()) {
+            hook.load(player_id, state);
+        }
+    }
+    Ok(())
+}
+
+/// Captures a leaving player's current state and saves it
+///
+/// Fields nothing on the server tracks, such as the inventory, keep their
+/// previously saved values. Players not marked persistent are not saved.
+pub fn persist_player(player_id: Uuid) -> Result<()> {
+    if !is_persistent(player_id) {
+        return Ok(());
+    }
+    let mut data = store().load(player_id)?;
+
+    if let Some((position, health)) = server::player_snapshot(player_id) {
+        data.position = Some(position);
+        data.health = health;
+    }
+    for hook in HOOKS.read().iter() {
+        if let Some(state) = hook.save(player_id) {
+            data.extensions.insert(hook.name().to_string(), state);
+        }
+    }
+    store().save(player_id, &data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_store() -> FilePlayerDataStore {
+        FilePlayerDataStore::new(std::env::temp_dir().join(format!("player-data-tests-{}", Uuid::new_v4())))
+    }
+
+    #[test]
+    fn saved_data_loads_back() {
+        let store = temp_store();
+        let player_id = Uuid::new_v4();
+        let data = PlayerData {
+            position: Some([1.0, 2.0, 3.0]),
+            health: 42,
+            inventory: serde_json::json!({ "sword": 1 }),
+            extensions: HashMap::from([("recipes".to_string(), serde_json::json!(["bread"]))]),
+        };
+
+        store.save(player_id, &data).unwrap();
+        assert_eq!(store.load(player_id).unwrap(), data);
+        std::fs::remove_dir_all(&store.dir).unwrap();
+    }
+
+    #[test]
+    fn unknown_players_load_the_defaults() {
+        assert_eq!(temp_store().load(Uuid::new_v4()).unwrap(), PlayerData::default());
+    }
+
+    #[test]
+    fn only_persistent_players_are_saved() {
+        let (account, anonymous) = (Uuid::new_v4(), Uuid::new_v4());
+        mark_persistent(account);
+
+        assert!(is_persistent(account));
+        assert!(!is_persistent(anonymous));
+        // Nothing is written for an anonymous player, so no store is touched
+        assert!(persist_player(anonymous).is_ok());
+
+        forget(account);
+        assert!(!is_persistent(account));
+    }
+}
@@ -0,0 +1,103 @@
+//-----------------------------------------------------------------------------
+// Rate Limiting
+//   - Token buckets keyed by player, address or any other id
+//   - Idle buckets can be evicted so the map does not grow forever
+//-----------------------------------------------------------------------------
+
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+/// Token bucket per key: `rate` tokens refill per second, up to `burst`
+pub struct TokenBuckets<K> {
+    buckets: HashMap<K, (f64, Instant)>,
+}
+
+impl<K> Default for TokenBuckets<K> {
+    fn default() -> Self {
+        Self { buckets: HashMap::new() }
+    }
+}
+
+impl<K: Eq + Hash> TokenBuckets<K> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Takes one token for `key`, returning false if none are left
+    pub fn try_acquire(&mut self, key: K, rate: f64, burst: f64) -> bool {
+        let now = Instant::now();
+        let (tokens, last) = self.buckets.entry(key).or_insert((burst, now));
+        *tokens = (*tokens + now.duration_since(*last).as_secs_f64() * rate).min(burst);
+        *last = now;
+
+        if *tokens < 1.0 {
+            return false;
+        }
+        *tokens -= 1.0;
+        true
+    }
+
+    /// Drops the bucket for `key`
+    pub fn remove(&mut self, key: &K) {
+        self.buckets.remove(key);
+    }
+
+    /// Drops buckets that have not been used for `max_idle`
+    ///
+    /// A bucket idle that long has refilled completely as long as
+    /// `max_idle` covers `burst / rate`, so forgetting it changes nothing.
+    pub fn evict_idle(&mut self, max_idle: Duration) {
+        let now = Instant::now();
+        self.buckets.retain(|_, (_, last)| now.duration_since(*last) < max_idle);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7));
+    const OTHER_CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 8));
+
+    #[test]
+    fn connections_within_the_burst_are_accepted() {
+        let mut limiter = TokenBuckets::new();
+        assert!((0..5).all(|_| limiter.try_acquire(CLIENT, 1.0, 5.0)));
+    }
+
+    #[test]
+    fn connections_over_the_limit_are_rejected_per_address() {
+        let mut limiter = TokenBuckets::new();
+        for _ in 0..5 {
+            limiter.try_acquire(CLIENT, 1.0, 5.0);
+        }
+
+        assert!(!limiter.try_acquire(CLIENT, 1.0, 5.0));
+        assert!(limiter.try_acquire(OTHER_CLIENT, 1.0, 5.0));
+    }
+
+    #[test]
+    fn spent_buckets_refill_over_time() {
+        let mut limiter = TokenBuckets::new();
+        assert!(limiter.try_acquire(CLIENT, 100.0, 1.0));
+        assert!(!limiter.try_acquire(CLIENT, 100.0, 1.0));
+
+        std::thread::sleep(Duration::from_millis(20));
+
+        assert!(limiter.try_acquire(CLIENT, 100.0, 1.0));
+    }
+
+    #[test]
+    fn idle_buckets_are_evicted() {
+        let mut limiter = TokenBuckets::new();
+        limiter.try_acquire(CLIENT, 1.0, 5.0);
+        std::thread::sleep(Duration::from_millis(10));
+        limiter.try_acquire(OTHER_CLIENT, 1.0, 5.0);
+
+        limiter.evict_idle(Duration::from_millis(5));
+
+        assert_eq!(limiter.buckets.keys().collect::<Vec<_>>(), vec![&OTHER_CLIENT]);
+    }
+}
@@ -0,0 +1,67 @@
+//-----------------------------------------------------------------------------
+// Startup Sequencer
+//   - Runs registered initialization steps in order
+//   - Logs how long each step took
+//   - Stops at the first failing step and names it in the error
+//-----------------------------------------------------------------------------
+
+use crate::LOGGER;
+use anyhow::Result;
+use futures::future::BoxFuture;
+use crate::{log_error, log_info};
+use std::future::Future;
+use std::time::Instant;
+
+/// A named initialization step
+struct Step {
+    name: &'static str,
+    run: Box<dyn FnOnce() -> BoxFuture<'static, Result<()>> + Send>,
+}
+
+/// Ordered list of initialization steps
+///
+/// Steps run one after another in the order they were added. If a step
+/// returns an error, the remaining steps are skipped.
+#[derive(Default)]
+pub struct Startup {
+    steps: Vec<Step>,
+}
+
+impl Startup {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a step to run after every step added before it
+    pub fn step<F, Fut>(mut self, name: &'static str, step: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<()>> + Send + 'static,
+    {
+        self.steps.push(Step {
+            name,
+            run: Box::new(move || Box::pin(step())),
+        });
+        self
+    }
+
+    /// Runs every step in order, stopping at the first failure
+    pub async fn run(self) -> Result<()> {
+        let started = Instant::now();
+        let total = self.steps.len();
+
+        for (index, step) in self.steps.into_iter().enumerate() {
+            let step_started = Instant::now();
+            log_info!(LOGGER, "STARTUP", "[{}/{}] {}", index + 1, total, step.name);
+
+            if let Err(e) = (step.run)().await {
+                log_error!(LOGGER, "STARTUP", "{} failed after {:?}: {:#}", step.name, step_started.elapsed(), e);
+                return Err(e.context(format!("Startup step '{}' failed", step.name)));
+            }
+            log_info!(LOGGER, "STARTUP", "{} finished in {:?}", step.name, step_started.elapsed());
+        }
+
+        log_info!(LOGGER, "STARTUP", "Startup finished in {:?}", started.elapsed());
+        Ok(())
+    }
+}